@@ -1,5 +1,7 @@
 use std::cell::Cell;
 use std::io;
+use std::mem::swap;
+use std::sync::Mutex;
 
 use error::CliResult;
 use io::*;
@@ -60,6 +62,11 @@ impl<'a> Config<'a> {
         &self.input_opts
     }
 
+    /// Adds default attributes (name, value) to the output headers
+    pub fn add_attrs(&mut self, attrs: &[(String, String)]) {
+        self.output_opts.format.add_attrs(attrs);
+    }
+
     pub fn writer<F, O>(&self, func: F) -> CliResult<O>
     where
         F: FnOnce(&mut output::Writer<&mut io::Write>, var::Vars) -> CliResult<O>,
@@ -133,29 +140,44 @@ impl<'a> Config<'a> {
         Ok(())
     }
 
-    pub fn parallel<W, F, O>(&self, n_threads: u32, work: W, mut func: F) -> CliResult<Vec<()>>
+    /// Processes the records in `n_threads` worker threads (`work`), while
+    /// `func` is called in the main thread with the records in the original order.
+    /// The variables are evaluated in the worker threads if possible, otherwise
+    /// in the main thread before calling `func`.
+    pub fn var_parallel_init<Si, S, Di, W, F, D>(
+        &self,
+        vars: &mut var::Vars,
+        n_threads: u32,
+        local_init: Si,
+        data_init: Di,
+        work: W,
+        mut func: F,
+    ) -> CliResult<Vec<()>>
     where
-        W: Fn(&Record, &mut O) -> CliResult<()> + Send + Sync,
-        F: FnMut(&Record, &mut O) -> CliResult<bool>,
-        O: Send + Default,
+        W: Fn(&Record, &mut D, &mut S) -> CliResult<()> + Send + Sync,
+        F: FnMut(&Record, &mut D, &mut var::Vars) -> CliResult<bool>,
+        Di: Fn() -> D + Send + Sync,
+        D: Send,
+        S: Send,
+        Si: Fn() -> CliResult<S> + Send + Sync,
     {
-        self.check_repetition()?;
-        input::io_readers(&self.input_opts, |o, rdr| {
-            input::read_parallel(
-                o,
-                rdr,
-                n_threads,
-                || Ok(()),
-                Default::default,
-                |rec, out, _| work(rec, out),
-                &mut func,
-            )
-        })
+        self.var_parallel_generic(
+            vars,
+            n_threads,
+            false,
+            &local_init,
+            &data_init,
+            &|rec, d, s, _| work(rec, d, s),
+            &mut |rec, d, _, vars| func(rec, d, vars),
+        )
     }
 
-    pub fn var_parallel_init<Si, S, Di, W, F, D>(
+    /// Like `var_parallel_init`, but `func` additionally obtains the data
+    /// of the record set (created by `local_init`), which allows handing
+    /// over results accumulated in the worker threads.
+    pub fn var_parallel_local<Si, S, Di, W, F, D>(
         &self,
-        mut vars: &mut var::Vars,
+        vars: &mut var::Vars,
         n_threads: u32,
         local_init: Si,
         data_init: Di,
@@ -164,25 +186,120 @@ impl<'a> Config<'a> {
     ) -> CliResult<Vec<()>>
     where
         W: Fn(&Record, &mut D, &mut S) -> CliResult<()> + Send + Sync,
+        F: FnMut(&Record, &mut D, &mut S, &mut var::Vars) -> CliResult<bool>,
+        Di: Fn() -> D + Send + Sync,
+        D: Send,
+        S: Send,
+        Si: Fn() -> CliResult<S> + Send + Sync,
+    {
+        self.var_parallel_generic(
+            vars,
+            n_threads,
+            false,
+            &local_init,
+            &data_init,
+            &|rec, d, s, _| work(rec, d, s),
+            &mut func,
+        )
+    }
+
+    /// Like `var_parallel_init`, but `work` has access to the variables,
+    /// which are evaluated before. This allows editing records based on
+    /// variables in the worker threads. If some variables depend on the order
+    /// of the records (e.g. `num`), everything is done in the main thread.
+    pub fn var_parallel_edit<Si, S, Di, W, F, D>(
+        &self,
+        vars: &mut var::Vars,
+        n_threads: u32,
+        local_init: Si,
+        data_init: Di,
+        work: W,
+        mut func: F,
+    ) -> CliResult<Vec<()>>
+    where
+        W: Fn(&Record, &mut D, &mut S, &var::Vars) -> CliResult<()> + Send + Sync,
         F: FnMut(&Record, &mut D, &mut var::Vars) -> CliResult<bool>,
         Di: Fn() -> D + Send + Sync,
         D: Send,
         S: Send,
         Si: Fn() -> CliResult<S> + Send + Sync,
+    {
+        self.var_parallel_generic(
+            vars,
+            n_threads,
+            true,
+            &local_init,
+            &data_init,
+            &|rec, d, s, vars| work(rec, d, s, vars.unwrap()),
+            &mut |rec, d, _, vars| func(rec, d, vars),
+        )
+    }
+
+    // Trait objects are used here in order to limit the amount of
+    // generated code (the closures are nested deeply in the parallel reader)
+    fn var_parallel_generic<S, D>(
+        &self,
+        vars: &mut var::Vars,
+        n_threads: u32,
+        work_needs_vars: bool,
+        local_init: &(Fn() -> CliResult<S> + Sync),
+        data_init: &(Fn() -> D + Sync),
+        work: &(Fn(&Record, &mut D, &mut S, Option<&var::Vars>) -> CliResult<()> + Sync),
+        func: &mut FnMut(&Record, &mut D, &mut S, &mut var::Vars) -> CliResult<bool>,
+    ) -> CliResult<Vec<()>>
+    where
+        D: Send,
+        S: Send,
     {
         self.check_repetition()?;
         input::io_readers(&self.input_opts, |in_opts, rdr| {
             vars.new_input(in_opts)?;
+
+            // copy of the variables, from which every record set obtains its own copy
+            let forked = if n_threads > 1 { vars.fork()? } else { None };
+
+            if forked.is_none() && work_needs_vars {
+                let mut s = local_init()?;
+                let mut d = data_init();
+                return input::run_reader(rdr, &in_opts.format, in_opts.cap, in_opts.max_mem, &mut |rec| {
+                    vars.set_record(rec)?;
+                    work(rec, &mut d, &mut s, Some(vars))?;
+                    func(rec, &mut d, &mut s, vars)
+                });
+            }
+
+            let forked = Mutex::new(forked);
             input::read_parallel(
                 in_opts,
                 rdr,
                 n_threads,
-                &local_init,
-                &data_init,
-                &work,
-                |rec, out| {
-                    vars.set_record(rec)?;
-                    func(rec, out, &mut vars)
+                || {
+                    let v = match *forked.lock().unwrap() {
+                        Some(ref mut f) => Some(f.fork()?.expect("Bug: vars cannot be copied")),
+                        None => None,
+                    };
+                    Ok((local_init()?, v))
+                },
+                || (data_init(), None::<var::Data>),
+                |rec, &mut (ref mut d, ref mut data), &mut (ref mut s, ref mut v)| {
+                    if let Some(ref mut v) = *v {
+                        v.set_record(rec)?;
+                        work(rec, d, s, Some(v))?;
+                        // the values are passed to the main thread
+                        match *data {
+                            Some(ref mut data) => data.clone_from(v.data()),
+                            None => *data = Some(v.data().clone()),
+                        }
+                        return Ok(());
+                    }
+                    work(rec, d, s, None)
+                },
+                |rec, &mut (ref mut d, ref mut data), &mut (ref mut s, _)| {
+                    match *data {
+                        Some(ref mut data) => swap(vars.mut_data(), data),
+                        None => vars.set_record(rec)?,
+                    }
+                    func(rec, d, s, vars)
                 },
             )
         })
@@ -190,20 +307,24 @@ impl<'a> Config<'a> {
 
     pub fn var_parallel<W, F, O>(
         &self,
-        mut vars: &mut var::Vars,
+        vars: &mut var::Vars,
         n_threads: u32,
         work: W,
-        mut func: F,
+        func: F,
     ) -> CliResult<Vec<()>>
     where
         W: Fn(&Record, &mut O) -> CliResult<()> + Send + Sync,
         F: FnMut(&Record, &mut O, &mut var::Vars) -> CliResult<bool>,
         O: Send + Default,
     {
-        self.parallel(n_threads, work, |rec, out| {
-            vars.set_record(rec)?;
-            func(rec, out, &mut vars)
-        })
+        self.var_parallel_init(
+            vars,
+            n_threads,
+            || Ok(()),
+            Default::default,
+            |rec, out, _| work(rec, out),
+            func,
+        )
     }
 
     pub fn has_stdin(&self) -> bool {
@@ -222,6 +343,18 @@ impl<'a> Config<'a> {
         Ok(())
     }
 
+    /// Registers the variables used by the output format (e.g. in attributes)
+    /// without creating a writer. Useful if the output files are only
+    /// created while reading.
+    pub fn register_output_vars(
+        &self,
+        vars: &mut var::Vars,
+        other_mod: Option<&mut var::VarProvider>,
+    ) -> CliResult<()> {
+        let mut w = output::from_format(io::sink(), &self.output_opts.format)?;
+        vars.build_with(other_mod, |b| w.register_vars(b))
+    }
+
     pub fn other_writer<'c>(
         &self,
         path: &str,
@@ -231,7 +364,7 @@ impl<'a> Config<'a> {
         let mut o = self.output_opts.clone();
         o.kind = output::OutputKind::File(path.into());
         let io_writer = output::io_writer_from_kind(&o.kind)?;
-        let io_writer = output::compr_writer(io_writer, o.compression, o.compression_level)?;
+        let io_writer = output::compr_writer(io_writer, o.compression, o.compression_level, o.compr_threads)?;
         let mut w = output::from_format(io_writer, &o.format)?;
         if let Some(v) = vars {
             v.build_with(other_mod, |b| w.register_vars(b))?;
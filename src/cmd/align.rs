@@ -0,0 +1,435 @@
+use std::io::Write;
+use std::mem;
+
+use bio::alignment::pairwise::{Aligner, MatchParams};
+use bio::alignment::{Alignment, AlignmentOperation};
+use seq_io::fasta::{self, Record as FastaRecord};
+
+use cfg;
+use error::CliResult;
+use io::input;
+use io::output::writer::Writer;
+use io::{Record, SeqQualRecord};
+use opt;
+use var::{self, VarHelp, VarProvider};
+
+static USAGE: &'static str = concat!(
+    "
+Pairwise alignment of sequences to one or more references with affine gap
+penalties. If there are several references, the best scoring alignment is
+reported. Alignment properties are available as variables (see --help-vars),
+optionally the aligned part of the sequences can be returned.
+
+Usage:
+    st align [options] [-a <attr>...] [-l <list>...] <reference> [<input>...]
+    st align (-h | --help)
+    st align --help-vars
+
+Options:
+    <reference>         Reference sequence or 'file:<references.fasta>'
+    --mode <mode>       Alignment mode: 'global' (end-to-end), 'semiglobal'
+                        (whole reference aligned, free end gaps in the
+                        sequence, e.g. for primers), 'seq-global' (whole
+                        sequence aligned, free end gaps in the reference,
+                        e.g. for reads) or 'local' [default: semiglobal]
+    --match <s>         Match score (>= 0) [default: 1]
+    --mismatch <s>      Mismatch score (<= 0) [default: -1]
+    --gap-open <s>      Gap opening score (<= 0) [default: -5]
+    --gap-ext <s>       Gap extension score (<= 0). A gap of length n has the
+                        score <gap-open> + n * <gap-ext> [default: -1]
+    --out <what>        Sequence output: 'seq' (unchanged), 'trim' (aligned
+                        part of the sequence) or 'aligned' (aligned part with
+                        deletions relative to the reference shown as '-';
+                        not possible with FASTQ) [default: seq]
+    -t, --threads <N>   Number of threads [default: 1]
+",
+    common_opts!()
+);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Global,
+    Semiglobal,
+    SeqGlobal,
+    Local,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Out {
+    Seq,
+    Trim,
+    Aligned,
+}
+
+pub fn run() -> CliResult<()> {
+    let args = opt::Args::new(USAGE)?;
+    let cfg = cfg::Config::from_args_with_help(&args, &AlnVarHelp)?;
+    let num_threads = args.thread_num()?;
+
+    let mode = match args.get_str("--mode") {
+        "global" => Mode::Global,
+        "semiglobal" => Mode::Semiglobal,
+        "seq-global" => Mode::SeqGlobal,
+        "local" => Mode::Local,
+        m => return fail!(format!("Unknown alignment mode: {}", m)),
+    };
+    let out = match args.get_str("--out") {
+        "seq" => Out::Seq,
+        "trim" => Out::Trim,
+        "aligned" => Out::Aligned,
+        o => return fail!(format!("Unknown output type: {}", o)),
+    };
+    let match_score: i32 = args.value("--match")?;
+    let mismatch_score: i32 = args.value("--mismatch")?;
+    let gap_open: i32 = args.value("--gap-open")?;
+    let gap_ext: i32 = args.value("--gap-ext")?;
+    if match_score < 0 || mismatch_score > 0 || gap_open > 0 || gap_ext > 0 {
+        return fail!(
+            "The match score must be >= 0, mismatch and gap scores must be <= 0."
+        );
+    }
+
+    let reference = args.get_str("<reference>");
+    let references = if !reference.starts_with("file:") {
+        vec![("reference".to_string(), reference.as_bytes().to_ascii_uppercase())]
+    } else {
+        read_references(&reference[5..])?
+    };
+
+    cfg.writer_with(
+        |_| Ok(AlnVars::new()),
+        |writer, mut vars, mut aln_vars| {
+            let mut out_seq = vec![];
+            let mut out_qual = vec![];
+            let references = &references;
+
+            cfg.var_parallel_init(
+                &mut vars,
+                num_threads,
+                || {
+                    // one aligner per record set
+                    Ok(Aligner::new(
+                        gap_open,
+                        gap_ext,
+                        MatchParams::new(match_score, mismatch_score),
+                    ))
+                },
+                AlnData::default,
+                |record, data, aligner| {
+                    data.seq.clear();
+                    record.write_seq(&mut data.seq);
+                    data.upper.clear();
+                    data.upper.extend(data.seq.iter().map(|b| b.to_ascii_uppercase()));
+                    data.best = align(&data.upper, references, mode, aligner);
+                    Ok(())
+                },
+                |record, data, vars| {
+                    aln_vars.set_with(data, references, &mut vars.mut_data().symbols);
+
+                    if out == Out::Seq {
+                        writer.write(&record, vars)?;
+                        return Ok(true);
+                    }
+
+                    out_seq.clear();
+                    out_qual.clear();
+                    let mut qual = None;
+                    if let Some(&(_, ref aln)) = data.best.as_ref() {
+                        if out == Out::Trim {
+                            out_seq.extend_from_slice(&data.seq[aln.ystart..aln.yend]);
+                            if let Some(q) = record.qual() {
+                                out_qual.extend_from_slice(&q[aln.ystart..aln.yend]);
+                                qual = Some(&out_qual[..]);
+                            }
+                        } else {
+                            if record.qual().is_some() {
+                                return fail!(
+                                    "Aligned sequences with gaps cannot be written together \
+                                     with quality scores."
+                                );
+                            }
+                            write_aligned(&data.seq, aln, &mut out_seq);
+                        }
+                    } else if record.qual().is_some() {
+                        qual = Some(&out_qual[..]);
+                    }
+                    writer.write(&SeqQualRecord::new(&record, &out_seq, qual), vars)?;
+                    Ok(true)
+                },
+            )?;
+            Ok(())
+        },
+    )
+}
+
+fn read_references(path: &str) -> CliResult<Vec<(String, Vec<u8>)>> {
+    let mut reader = fasta::Reader::new(input::file_reader(path)?);
+    let mut out = vec![];
+    while let Some(r) = reader.next() {
+        let r = r?;
+        let seq = r.seq_lines().fold(vec![], |mut s, l| {
+            s.extend(l.iter().map(|b| b.to_ascii_uppercase()));
+            s
+        });
+        out.push((r.id()?.to_string(), seq));
+    }
+    if out.is_empty() {
+        return fail!("Reference file is empty.");
+    }
+    Ok(out)
+}
+
+/// Aligns the sequence to all references and returns the best alignment
+/// with the index of the reference. The reference is always 'x' and the
+/// sequence 'y' in the returned alignment.
+fn align(
+    seq: &[u8],
+    references: &[(String, Vec<u8>)],
+    mode: Mode,
+    aligner: &mut Aligner<MatchParams>,
+) -> Option<(usize, Alignment)> {
+    let mut best: Option<(usize, Alignment)> = None;
+    for (i, &(_, ref reference)) in references.iter().enumerate() {
+        let aln = match mode {
+            Mode::Global => aligner.global(reference, seq),
+            Mode::Semiglobal => aligner.semiglobal(reference, seq),
+            Mode::SeqGlobal => swap_xy(aligner.semiglobal(seq, reference)),
+            Mode::Local => aligner.local(reference, seq),
+        };
+        if best.as_ref().map(|&(_, ref b)| aln.score > b.score).unwrap_or(true) {
+            best = Some((i, aln));
+        }
+    }
+    // local alignment without any matching position
+    best.and_then(|(i, aln)| {
+        if aln.operations.is_empty() {
+            None
+        } else {
+            Some((i, aln))
+        }
+    })
+}
+
+fn swap_xy(mut aln: Alignment) -> Alignment {
+    mem::swap(&mut aln.xstart, &mut aln.ystart);
+    mem::swap(&mut aln.xend, &mut aln.yend);
+    mem::swap(&mut aln.xlen, &mut aln.ylen);
+    for op in &mut aln.operations {
+        *op = match *op {
+            AlignmentOperation::Ins => AlignmentOperation::Del,
+            AlignmentOperation::Del => AlignmentOperation::Ins,
+            o => o,
+        };
+    }
+    aln
+}
+
+// writes the aligned part of the sequence with gaps
+fn write_aligned(seq: &[u8], aln: &Alignment, out: &mut Vec<u8>) {
+    let mut pos = aln.ystart;
+    for op in &aln.operations {
+        match *op {
+            AlignmentOperation::Ins => out.push(b'-'),
+            AlignmentOperation::Xclip(_) | AlignmentOperation::Yclip(_) => {}
+            _ => {
+                out.push(seq[pos]);
+                pos += 1;
+            }
+        }
+    }
+}
+
+/// CIGAR string of the sequence relative to the reference, using
+/// =/X for matches/mismatches and S for unaligned sequence ends.
+fn write_cigar(aln: &Alignment, out: &mut Vec<u8>) {
+    let write_op = |n: usize, op: u8, out: &mut Vec<u8>| {
+        if n > 0 {
+            write!(out, "{}", n).unwrap();
+            out.push(op);
+        }
+    };
+    write_op(aln.ystart, b'S', out);
+    let mut current = None;
+    let mut n = 0;
+    for op in &aln.operations {
+        let c = match *op {
+            AlignmentOperation::Match => b'=',
+            AlignmentOperation::Subst => b'X',
+            // gap in the sequence
+            AlignmentOperation::Ins => b'D',
+            // additional base in the sequence
+            AlignmentOperation::Del => b'I',
+            _ => continue,
+        };
+        if current != Some(c) {
+            if let Some(prev) = current {
+                write_op(n, prev, out);
+            }
+            current = Some(c);
+            n = 0;
+        }
+        n += 1;
+    }
+    if let Some(prev) = current {
+        write_op(n, prev, out);
+    }
+    write_op(aln.ylen - aln.yend, b'S', out);
+}
+
+#[derive(Debug, Default)]
+struct AlnData {
+    seq: Vec<u8>,
+    upper: Vec<u8>,
+    best: Option<(usize, Alignment)>,
+}
+
+pub struct AlnVarHelp;
+
+impl VarHelp for AlnVarHelp {
+    fn name(&self) -> &'static str {
+        "Alignment variables"
+    }
+    fn usage(&self) -> &'static str {
+        "aln:<variable>"
+    }
+    fn desc(&self) -> Option<&'static str> {
+        Some(
+            "All variables except for aln:score are undefined (empty) if nothing \
+             could be aligned (local alignment mode).",
+        )
+    }
+    fn vars(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            ("aln:ref", "Name of the best matching reference"),
+            ("aln:score", "Alignment score"),
+            (
+                "aln:ident",
+                "Identity: number of matches divided by the number of alignment \
+                 columns (including gaps)",
+            ),
+            ("aln:len", "Number of alignment columns"),
+            (
+                "aln:cigar",
+                "CIGAR string of the sequence relative to the reference, with \
+                 unaligned ends soft-clipped (S)",
+            ),
+            ("aln:start", "Start of the aligned region in the sequence"),
+            ("aln:end", "End of the aligned region in the sequence"),
+            ("aln:ref_start", "Start of the aligned region in the reference"),
+            ("aln:ref_end", "End of the aligned region in the reference"),
+        ])
+    }
+    fn examples(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            (
+                "Annotating reads with the identity to a reference and the CIGAR string",
+                "st align --mode seq-global -a ident={aln:ident} -a cigar={aln:cigar} \
+                 file:ref.fasta reads.fa",
+            ),
+            (
+                "Locating and extracting an amplicon using its reference sequence",
+                "st align --out trim file:amplicon.fasta seqs.fa > trimmed.fa",
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AlnVar {
+    Ref,
+    Score,
+    Ident,
+    Len,
+    Cigar,
+    Start,
+    End,
+    RefStart,
+    RefEnd,
+}
+
+#[derive(Debug)]
+pub struct AlnVars {
+    vars: Vec<(AlnVar, usize)>,
+    cigar: Vec<u8>,
+}
+
+impl AlnVars {
+    pub fn new() -> AlnVars {
+        AlnVars {
+            vars: vec![],
+            cigar: vec![],
+        }
+    }
+
+    fn set_with(
+        &mut self,
+        data: &AlnData,
+        references: &[(String, Vec<u8>)],
+        symbols: &mut var::symbols::Table,
+    ) {
+        for &(var, id) in &self.vars {
+            let &(ref_idx, ref aln) = match data.best.as_ref() {
+                Some(b) => b,
+                None => {
+                    match var {
+                        AlnVar::Score => symbols.set_int(id, 0),
+                        _ => symbols.set_none(id),
+                    }
+                    continue;
+                }
+            };
+            match var {
+                AlnVar::Ref => symbols.set_text(id, references[ref_idx].0.as_bytes()),
+                AlnVar::Score => symbols.set_int(id, aln.score as i64),
+                AlnVar::Ident => {
+                    let matches = aln.operations
+                        .iter()
+                        .filter(|&&op| op == AlignmentOperation::Match)
+                        .count();
+                    symbols.set_float(id, matches as f64 / aln.operations.len() as f64)
+                }
+                AlnVar::Len => symbols.set_int(id, aln.operations.len() as i64),
+                AlnVar::Cigar => {
+                    self.cigar.clear();
+                    write_cigar(aln, &mut self.cigar);
+                    symbols.set_text(id, &self.cigar);
+                }
+                AlnVar::Start => symbols.set_int(id, aln.ystart as i64 + 1),
+                AlnVar::End => symbols.set_int(id, aln.yend as i64),
+                AlnVar::RefStart => symbols.set_int(id, aln.xstart as i64 + 1),
+                AlnVar::RefEnd => symbols.set_int(id, aln.xend as i64),
+            }
+        }
+    }
+}
+
+impl VarProvider for AlnVars {
+    fn prefix(&self) -> Option<&str> {
+        Some("aln")
+    }
+
+    fn name(&self) -> &'static str {
+        "alignment"
+    }
+
+    fn register_var(&mut self, name: &str, id: usize, _: &mut var::VarStore) -> CliResult<bool> {
+        let var = match name {
+            "ref" => AlnVar::Ref,
+            "score" => AlnVar::Score,
+            "ident" => AlnVar::Ident,
+            "len" => AlnVar::Len,
+            "cigar" => AlnVar::Cigar,
+            "start" => AlnVar::Start,
+            "end" => AlnVar::End,
+            "ref_start" => AlnVar::RefStart,
+            "ref_end" => AlnVar::RefEnd,
+            _ => return Ok(false),
+        };
+        self.vars.push((var, id));
+        Ok(true)
+    }
+
+    fn has_vars(&self) -> bool {
+        !self.vars.is_empty()
+    }
+}
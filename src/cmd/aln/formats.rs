@@ -0,0 +1,340 @@
+use std::io::{self, BufRead, Write};
+
+use error::CliResult;
+use lib::seqtype::{guess_seqtype, SeqType};
+
+use super::Msa;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlnFormat {
+    Fasta,
+    Phylip,
+    Nexus,
+    Stockholm,
+}
+
+impl AlnFormat {
+    pub fn from_str(s: &str) -> CliResult<AlnFormat> {
+        Ok(match &*s.to_ascii_lowercase() {
+            "fasta" | "fa" => AlnFormat::Fasta,
+            "phylip" | "phy" => AlnFormat::Phylip,
+            "nexus" | "nex" | "nxs" => AlnFormat::Nexus,
+            "stockholm" | "sto" => AlnFormat::Stockholm,
+            _ => {
+                return fail!(format!(
+                    "Unknown alignment format: '{}'. Valid are fasta, phylip, nexus and stockholm.",
+                    s
+                ))
+            }
+        })
+    }
+}
+
+/// Reads PHYLIP, NEXUS or Stockholm alignments (FASTA is read using the
+/// normal sequence readers)
+pub fn read<R: io::Read>(rdr: R, format: AlnFormat, msa: &mut Msa) -> CliResult<()> {
+    let lines = io::BufReader::new(rdr)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()?;
+    match format {
+        AlnFormat::Phylip => read_phylip(&lines, msa),
+        AlnFormat::Nexus => read_named_lines(nexus_matrix(&lines)?, msa, true),
+        AlnFormat::Stockholm => read_named_lines(stockholm_lines(&lines)?, msa, false),
+        AlnFormat::Fasta => unreachable!(),
+    }
+}
+
+/// Relaxed PHYLIP format (names separated from the sequence by whitespace),
+/// sequential or interleaved
+fn read_phylip(lines: &[String], msa: &mut Msa) -> CliResult<()> {
+    let mut lines = lines.iter().map(|l| l.trim());
+    let header = lines.by_ref().find(|l| !l.is_empty()).unwrap_or("");
+    let dims: Vec<usize> = header
+        .split_whitespace()
+        .take(2)
+        .map(|n| n.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid PHYLIP header: '{}'", header))?;
+    if dims.len() != 2 {
+        return fail!(format!("Invalid PHYLIP header: '{}'", header));
+    }
+    let (ntax, nchar) = (dims[0], dims[1]);
+    if ntax == 0 || nchar == 0 {
+        return fail!(format!(
+            "Invalid PHYLIP header: '{}'. The number of sequences and columns must be > 0.",
+            header
+        ));
+    }
+
+    // Interleaved files usually have empty lines between the blocks, but
+    // these are optional. If the layout cannot be read, the other one is tried.
+    let lines: Vec<_> = lines.collect();
+    let non_empty: Vec<_> = lines.iter().cloned().filter(|l| !l.is_empty()).collect();
+    let has_blocks = lines
+        .iter()
+        .skip_while(|l| l.is_empty())
+        .any(|l| l.is_empty());
+    let (first, second): (PhylipReader, PhylipReader) = if has_blocks {
+        (phylip_interleaved, phylip_sequential)
+    } else {
+        (phylip_sequential, phylip_interleaved)
+    };
+    let seqs = match first(&non_empty, ntax, nchar) {
+        Ok(seqs) => seqs,
+        Err(e) => second(&non_empty, ntax, nchar).map_err(|_| e)?,
+    };
+    for (name, seq) in seqs {
+        msa.push(name.as_bytes().to_vec(), None, seq);
+    }
+    Ok(())
+}
+
+type PhylipReader = for<'a> fn(&[&'a str], usize, usize) -> CliResult<Vec<(&'a str, Vec<u8>)>>;
+
+// every sequence may be split across several lines
+fn phylip_sequential<'a>(
+    lines: &[&'a str],
+    ntax: usize,
+    nchar: usize,
+) -> CliResult<Vec<(&'a str, Vec<u8>)>> {
+    let mut lines = lines.iter();
+    let mut out = Vec::with_capacity(ntax);
+    for i in 0..ntax {
+        let line = lines
+            .next()
+            .ok_or_else(|| format!("PHYLIP alignment has less than {} sequences", ntax))?;
+        let (name, mut seq) = phylip_name_seq(line);
+        while seq.len() < nchar {
+            let line = lines
+                .next()
+                .ok_or_else(|| format!("PHYLIP sequence {} shorter than {}", i + 1, nchar))?;
+            extend_seq(&mut seq, line);
+        }
+        check_phylip_len(i, &seq, nchar)?;
+        out.push((name, seq));
+    }
+    if lines.next().is_some() {
+        return fail!("PHYLIP alignment has more lines than expected.");
+    }
+    Ok(out)
+}
+
+// the first block contains the names, the lines of the following blocks
+// are added to the sequences in the same order
+fn phylip_interleaved<'a>(
+    lines: &[&'a str],
+    ntax: usize,
+    nchar: usize,
+) -> CliResult<Vec<(&'a str, Vec<u8>)>> {
+    if lines.len() < ntax {
+        return fail!(format!("PHYLIP alignment has less than {} sequences", ntax));
+    }
+    let mut out: Vec<_> = lines[..ntax].iter().map(|l| phylip_name_seq(l)).collect();
+    for (i, line) in lines[ntax..].iter().enumerate() {
+        if out.iter().all(|&(_, ref s)| s.len() >= nchar) {
+            return fail!("PHYLIP alignment has more lines than expected.");
+        }
+        extend_seq(&mut out[i % ntax].1, line);
+    }
+    for (i, &(_, ref seq)) in out.iter().enumerate() {
+        if seq.len() < nchar {
+            return fail!(format!("PHYLIP sequence {} shorter than {}", i + 1, nchar));
+        }
+        check_phylip_len(i, seq, nchar)?;
+    }
+    Ok(out)
+}
+
+fn phylip_name_seq(line: &str) -> (&str, Vec<u8>) {
+    let mut parts = line.splitn(2, char::is_whitespace);
+    let name = parts.next().unwrap();
+    let mut seq = vec![];
+    extend_seq(&mut seq, parts.next().unwrap_or(""));
+    (name, seq)
+}
+
+fn check_phylip_len(i: usize, seq: &[u8], nchar: usize) -> CliResult<()> {
+    if seq.len() > nchar {
+        return fail!(format!("PHYLIP sequence {} longer than {}", i + 1, nchar));
+    }
+    Ok(())
+}
+
+fn extend_seq(seq: &mut Vec<u8>, text: &str) {
+    seq.extend(text.bytes().filter(|b| !b.is_ascii_whitespace()));
+}
+
+/// Returns the lines of the NEXUS MATRIX command (comments removed)
+fn nexus_matrix(lines: &[String]) -> CliResult<Vec<String>> {
+    let mut out = vec![];
+    let mut in_matrix = false;
+    let mut comment_level = 0;
+    for line in lines {
+        // remove comments in square brackets
+        let mut l = String::with_capacity(line.len());
+        for c in line.chars() {
+            match c {
+                '[' => comment_level += 1,
+                ']' if comment_level > 0 => comment_level -= 1,
+                _ if comment_level == 0 => l.push(c),
+                _ => {}
+            }
+        }
+        let l = l.trim();
+        if !in_matrix {
+            if l.to_ascii_lowercase().starts_with("matrix") {
+                in_matrix = true;
+                let rest = l[6..].trim();
+                if !rest.is_empty() {
+                    out.push(rest.to_string());
+                }
+            }
+            continue;
+        }
+        if let Some(p) = l.find(';') {
+            let rest = l[..p].trim();
+            if !rest.is_empty() {
+                out.push(rest.to_string());
+            }
+            return Ok(out);
+        }
+        if !l.is_empty() {
+            out.push(l.to_string());
+        }
+    }
+    if in_matrix {
+        fail!("Unterminated MATRIX command in NEXUS file")
+    } else {
+        fail!("No MATRIX command found in NEXUS file")
+    }
+}
+
+/// Returns the sequence lines of a Stockholm alignment
+fn stockholm_lines(lines: &[String]) -> CliResult<Vec<String>> {
+    let mut lines = lines.iter().map(|l| l.trim()).skip_while(|l| l.is_empty());
+    if !lines.next().map(|l| l.starts_with("# STOCKHOLM")).unwrap_or(false) {
+        return fail!("Stockholm header (# STOCKHOLM 1.0) not found");
+    }
+    Ok(lines
+        .take_while(|&l| l != "//")
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.to_string())
+        .collect())
+}
+
+/// Parses 'name sequence' lines. Sequences may be split into several blocks,
+/// in which case the name is repeated.
+fn read_named_lines(lines: Vec<String>, msa: &mut Msa, quoted: bool) -> CliResult<()> {
+    let start = msa.seqs.len();
+    for line in &lines {
+        let (name, seq) = if quoted && line.starts_with('\'') {
+            let end = line[1..]
+                .find('\'')
+                .ok_or_else(|| format!("Unterminated quote in line: {}", line))?;
+            (line[1..end + 1].to_string(), &line[end + 2..])
+        } else {
+            let mut parts = line.splitn(2, char::is_whitespace);
+            (parts.next().unwrap().to_string(), parts.next().unwrap_or(""))
+        };
+        let name = name.into_bytes();
+        let i = msa.names[start..].iter().position(|n| *n == name);
+        if let Some(i) = i {
+            extend_seq(&mut msa.seqs[start + i], seq);
+        } else {
+            let mut s = vec![];
+            extend_seq(&mut s, seq);
+            msa.push(name, None, s);
+        }
+    }
+    Ok(())
+}
+
+pub fn write<W: Write>(msa: &Msa, format: AlnFormat, mut out: W) -> CliResult<()> {
+    let aln_len = msa.len();
+    match format {
+        AlnFormat::Fasta => for (i, seq) in msa.seqs.iter().enumerate() {
+            out.write_all(b">")?;
+            out.write_all(&msa.names[i])?;
+            if let Some(ref d) = msa.descs[i] {
+                out.write_all(b" ")?;
+                out.write_all(d)?;
+            }
+            out.write_all(b"\n")?;
+            out.write_all(seq)?;
+            out.write_all(b"\n")?;
+        },
+        AlnFormat::Phylip => {
+            writeln!(out, "{} {}", msa.seqs.len(), aln_len)?;
+            write_named(msa, &mut out, |n| n.iter().map(|&c| no_space(c)).collect())?;
+        }
+        AlnFormat::Nexus => {
+            let datatype = match seqtype(msa) {
+                SeqType::DNA => "DNA",
+                SeqType::RNA => "RNA",
+                SeqType::Protein => "PROTEIN",
+                SeqType::Other => "STANDARD",
+            };
+            writeln!(
+                out,
+                "#NEXUS\nBEGIN DATA;\n  DIMENSIONS NTAX={} NCHAR={};\n  \
+                 FORMAT DATATYPE={} MISSING=? GAP=-;\nMATRIX",
+                msa.seqs.len(),
+                aln_len,
+                datatype
+            )?;
+            write_named(msa, &mut out, |n| {
+                if n.iter().all(|&c| c.is_ascii_alphanumeric() || c == b'_' || c == b'.') {
+                    n.to_vec()
+                } else {
+                    let mut q = vec![b'\''];
+                    q.extend(n.iter().filter(|&&c| c != b'\''));
+                    q.push(b'\'');
+                    q
+                }
+            })?;
+            out.write_all(b";\nEND;\n")?;
+        }
+        AlnFormat::Stockholm => {
+            out.write_all(b"# STOCKHOLM 1.0\n")?;
+            write_named(msa, &mut out, |n| n.iter().map(|&c| no_space(c)).collect())?;
+            out.write_all(b"//\n")?;
+        }
+    }
+    Ok(())
+}
+
+fn no_space(c: u8) -> u8 {
+    if c.is_ascii_whitespace() {
+        b'_'
+    } else {
+        c
+    }
+}
+
+// writes names (padded to equal width) and sequences
+fn write_named<W, F>(msa: &Msa, out: &mut W, fmt_name: F) -> CliResult<()>
+where
+    W: Write,
+    F: Fn(&[u8]) -> Vec<u8>,
+{
+    let names: Vec<_> = msa.names.iter().map(|n| fmt_name(n)).collect();
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0) + 2;
+    for (name, seq) in names.iter().zip(&msa.seqs) {
+        out.write_all(name)?;
+        for _ in name.len()..width {
+            out.write_all(b" ")?;
+        }
+        out.write_all(seq)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+fn seqtype(msa: &Msa) -> SeqType {
+    let seq: Vec<u8> = msa.seqs
+        .iter()
+        .flat_map(|s| s.iter().cloned())
+        .take(10000)
+        .collect();
+    guess_seqtype(&seq, None).map(|t| t.0).unwrap_or(SeqType::Other)
+}
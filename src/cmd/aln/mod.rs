@@ -0,0 +1,345 @@
+use std::io::Write;
+
+use cfg;
+use error::CliResult;
+use io::input;
+use lib::iupac;
+use lib::rng::Range;
+use lib::util::parse_range;
+use opt;
+
+use self::formats::AlnFormat;
+
+mod formats;
+
+static USAGE: &'static str = concat!(
+    "
+Utilities for multiple sequence alignments. The whole alignment is loaded into
+memory, and all sequences must have the same length. '-' and '.' are
+recognized as gaps.
+
+    check         Verify that all sequences have the same length and report
+                  the number of sequences and columns.
+    convert       Convert between alignment formats (see --aln-in/--aln-out)
+    degap         Remove columns consisting only of gaps (or with a gap
+                  frequency above --max-gaps)
+    cols          Extract one or more column ranges or single columns
+                  (e.g. '1..100,150,200..')
+    consensus     Calculate the consensus sequence, by default using IUPAC
+                  ambiguity codes (DNA/RNA only)
+    conservation  Per-column statistics (TSV): most frequent residue, its
+                  frequency, gap frequency and Shannon entropy (in bits)
+
+Usage:
+    st aln check [options] [<input>...]
+    st aln convert [options] [<input>...]
+    st aln degap [options] [<input>...]
+    st aln cols [options] <ranges> [<input>...]
+    st aln consensus [options] [<input>...]
+    st aln conservation [options] [<input>...]
+    st aln (-h | --help)
+
+Alignment format options:
+    --aln-in <fmt>      Input alignment format: fasta, phylip (relaxed, sequential
+                        or interleaved), nexus or stockholm [default: fasta]
+    --aln-out <fmt>     Output alignment format: fasta, phylip, nexus or
+                        stockholm [default: fasta]
+
+Command options:
+    --max-gaps <f>      degap: Remove columns with a gap frequency above <f>.
+                        consensus: Columns with a gap frequency above <f> are
+                        gaps in the consensus [default for consensus: 0.5]
+    -e, --exclude       cols: Remove the column ranges instead of extracting
+    --id <id>           consensus: ID of the consensus sequence
+                        [default: consensus]
+    --min-freq <f>      consensus: Minimum frequency of a base among the non-gap
+                        characters of a column to be included in the IUPAC code
+                        [default: 0.2]
+    --majority          consensus: Use the most frequent residue instead of
+                        IUPAC codes (also works with protein sequences)
+    --ungapped          consensus: Remove gaps from the consensus sequence
+",
+    common_opts!()
+);
+
+pub fn run() -> CliResult<()> {
+    let args = opt::Args::new(USAGE)?;
+    let cfg = cfg::Config::from_args(&args)?;
+
+    let in_format = AlnFormat::from_str(args.get_str("--aln-in"))?;
+    let out_format = AlnFormat::from_str(args.get_str("--aln-out"))?;
+    let max_gaps: Option<f64> = args.opt_value("--max-gaps")?;
+
+    let msa = read_msa(&cfg, in_format)?;
+
+    if args.get_bool("check") {
+        return cfg.io_writer(|writer, _| {
+            writeln!(writer, "{} sequences, {} columns", msa.seqs.len(), msa.len())?;
+            Ok(())
+        });
+    }
+
+    let out = if args.get_bool("convert") {
+        msa
+    } else if args.get_bool("degap") {
+        let n = msa.seqs.len() as f64;
+        let gaps = msa.gap_counts();
+        let keep: Vec<_> = gaps
+            .iter()
+            .map(|&g| match max_gaps {
+                Some(f) => g as f64 / n <= f,
+                None => g < msa.seqs.len(),
+            })
+            .collect();
+        msa.select_cols(&keep)
+    } else if args.get_bool("cols") {
+        let len = msa.len();
+        let exclude = args.get_bool("--exclude");
+        let mut keep = vec![exclude; len];
+        for rng in args.get_str("<ranges>").split(',') {
+            let (start, end) = if rng.contains("..") {
+                parse_range(rng)?
+            } else {
+                // single column
+                let col = rng.trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid column number: '{}'", rng))?;
+                (Some(col), Some(col))
+            };
+            let (start, end) = Range::new(start, end, len, false)?.get(false);
+            for k in &mut keep[start.min(end)..end] {
+                *k = !exclude;
+            }
+        }
+        msa.select_cols(&keep)
+    } else if args.get_bool("consensus") {
+        let opts = ConsensusOpts {
+            max_gaps: max_gaps.unwrap_or(0.5),
+            min_freq: args.value("--min-freq")?,
+            majority: args.get_bool("--majority"),
+            ungapped: args.get_bool("--ungapped"),
+        };
+        let cons = consensus(&msa, &opts)?;
+        let mut out = Msa::new();
+        out.push(args.get_str("--id").as_bytes().to_vec(), None, cons);
+        out
+    } else if args.get_bool("conservation") {
+        return cfg.io_writer(|writer, _| conservation(&msa, writer));
+    } else {
+        unreachable!();
+    };
+
+    cfg.io_writer(|writer, _| formats::write(&out, out_format, writer))
+}
+
+/// Multiple sequence alignment
+#[derive(Debug, Default)]
+pub struct Msa {
+    names: Vec<Vec<u8>>,
+    descs: Vec<Option<Vec<u8>>>,
+    seqs: Vec<Vec<u8>>,
+}
+
+impl Msa {
+    fn new() -> Msa {
+        Msa::default()
+    }
+
+    fn push(&mut self, name: Vec<u8>, desc: Option<Vec<u8>>, seq: Vec<u8>) {
+        self.names.push(name);
+        self.descs.push(desc);
+        self.seqs.push(seq);
+    }
+
+    /// Alignment length
+    fn len(&self) -> usize {
+        self.seqs.get(0).map(|s| s.len()).unwrap_or(0)
+    }
+
+    fn check(&self) -> CliResult<()> {
+        let len = self.len();
+        for (i, seq) in self.seqs.iter().enumerate().skip(1) {
+            if seq.len() != len {
+                return fail!(format!(
+                    "The sequences are not aligned: '{}' has a length of {}, but '{}' has {}.",
+                    String::from_utf8_lossy(&self.names[i]),
+                    seq.len(),
+                    String::from_utf8_lossy(&self.names[0]),
+                    len
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn gap_counts(&self) -> Vec<usize> {
+        let mut gaps = vec![0; self.len()];
+        for seq in &self.seqs {
+            for (g, &c) in gaps.iter_mut().zip(seq) {
+                if is_gap(c) {
+                    *g += 1;
+                }
+            }
+        }
+        gaps
+    }
+
+    fn select_cols(mut self, keep: &[bool]) -> Msa {
+        for seq in &mut self.seqs {
+            let mut i = 0;
+            seq.retain(|_| {
+                i += 1;
+                keep[i - 1]
+            });
+        }
+        self
+    }
+}
+
+#[inline]
+fn is_gap(c: u8) -> bool {
+    c == b'-' || c == b'.'
+}
+
+fn read_msa(cfg: &cfg::Config, format: AlnFormat) -> CliResult<Msa> {
+    let mut msa = Msa::new();
+    if format == AlnFormat::Fasta {
+        cfg.read_sequential(|rec| {
+            let mut seq = vec![];
+            rec.write_seq(&mut seq);
+            msa.push(
+                rec.id_bytes().to_vec(),
+                rec.desc_bytes().map(|d| d.to_vec()),
+                seq,
+            );
+            Ok(true)
+        })?;
+    } else {
+        input::io_readers(cfg.input_opts(), |_, rdr| formats::read(rdr, format, &mut msa))?;
+    }
+    msa.check()?;
+    Ok(msa)
+}
+
+struct ConsensusOpts {
+    max_gaps: f64,
+    min_freq: f64,
+    majority: bool,
+    ungapped: bool,
+}
+
+fn consensus(msa: &Msa, opts: &ConsensusOpts) -> CliResult<Vec<u8>> {
+    let n = msa.seqs.len() as f64;
+    let is_rna = msa.seqs.iter().any(|s| s.iter().any(|&c| c == b'U' || c == b'u'))
+        && !msa.seqs.iter().any(|s| s.iter().any(|&c| c == b'T' || c == b't'));
+    let mut out = Vec::with_capacity(msa.len());
+
+    for col in 0..msa.len() {
+        let gaps = msa.seqs.iter().filter(|s| is_gap(s[col])).count();
+        if gaps == msa.seqs.len() || gaps as f64 / n > opts.max_gaps {
+            if !opts.ungapped {
+                out.push(b'-');
+            }
+            continue;
+        }
+
+        let c = if opts.majority {
+            majority(msa.seqs.iter().map(|s| s[col])).0
+        } else {
+            // base frequencies, ambiguities are split between the bases
+            let mut freqs = [0f64; 4];
+            for seq in &msa.seqs {
+                let c = seq[col];
+                if is_gap(c) {
+                    continue;
+                }
+                let bases = iupac::bases(c).ok_or_else(|| {
+                    format!(
+                        "Invalid character for IUPAC consensus: '{}'. For other than DNA/RNA \
+                         sequences, use --majority.",
+                        c as char
+                    )
+                })?;
+                let w = 1. / bases.count_ones() as f64;
+                for (i, f) in freqs.iter_mut().enumerate() {
+                    if bases & (1 << i) != 0 {
+                        *f += w;
+                    }
+                }
+            }
+            let total: f64 = freqs.iter().sum();
+            let max = freqs.iter().cloned().fold(0., f64::max);
+            let mut set = 0;
+            for (i, &f) in freqs.iter().enumerate() {
+                if f / total >= opts.min_freq || f == max {
+                    set |= 1 << i;
+                }
+            }
+            let c = iupac::code(set);
+            if is_rna && c == b'T' {
+                b'U'
+            } else {
+                c
+            }
+        };
+        out.push(c);
+    }
+    Ok(out)
+}
+
+/// Returns the most frequent non-gap residue (uppercase) and its count.
+/// Ties are resolved by taking the residue coming first in the alphabet.
+fn majority<I: Iterator<Item = u8>>(col: I) -> (u8, usize) {
+    let counts = residue_counts(col);
+    let mut best = (b'-', 0);
+    for (c, &n) in counts.iter().enumerate() {
+        if n > best.1 {
+            best = (c as u8, n);
+        }
+    }
+    best
+}
+
+fn residue_counts<I: Iterator<Item = u8>>(col: I) -> [usize; 256] {
+    let mut counts = [0; 256];
+    for c in col {
+        if !is_gap(c) {
+            counts[c.to_ascii_uppercase() as usize] += 1;
+        }
+    }
+    counts
+}
+
+fn conservation(msa: &Msa, out: &mut Write) -> CliResult<()> {
+    let n = msa.seqs.len() as f64;
+    writeln!(out, "column\tresidue\tfreq\tgaps\tentropy")?;
+    for col in 0..msa.len() {
+        let counts = residue_counts(msa.seqs.iter().map(|s| s[col]));
+        let (residue, count) = majority(msa.seqs.iter().map(|s| s[col]));
+        let non_gap: usize = counts.iter().sum();
+        let entropy: f64 = counts
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| {
+                let p = c as f64 / non_gap as f64;
+                -p * p.log2()
+            })
+            .sum();
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            col + 1,
+            residue as char,
+            fmt_float(count as f64 / n),
+            fmt_float((msa.seqs.len() - non_gap) as f64 / n),
+            fmt_float(entropy.abs())
+        )?;
+    }
+    Ok(())
+}
+
+// four decimals without trailing zeros
+fn fmt_float(f: f64) -> String {
+    let s = format!("{:.4}", f);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
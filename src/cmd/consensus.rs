@@ -0,0 +1,379 @@
+use std::cmp::min;
+
+use fxhash::FxHashMap;
+
+use cfg;
+use error::CliResult;
+use io::output::writer::Writer;
+use io::OwnedRecord;
+use lib::iupac;
+use opt;
+use var::{self, varstring, VarHelp, VarProvider};
+
+static USAGE: &'static str = concat!(
+    "
+Builds consensus sequences from groups of sequences, e.g. reads of the same
+cluster or sample. All sequences of a group must have the same length or be
+aligned ('-' and '.' are recognized as gaps). Every base is weighted with
+the probability of being correct if quality scores are present.
+
+One record is returned per group. Its ID is the group key (or 'consensus'),
+and the number of sequences is added as attribute. With FASTQ output, the
+quality scores reflect the fraction of the (weighted) bases that agree with
+the consensus (capped at Phred 60).
+
+Usage:
+    st consensus [options] [-a <attr>...] [-l <list>...] [<input>...]
+    st consensus (-h | --help)
+    st consensus --help-vars
+
+Options:
+    -k, --key <key>     Build a consensus for each value of a key (variables
+                        allowed), e.g. '{a:cluster}'. By default, a single
+                        consensus is built from all sequences.
+    --iupac             Use IUPAC ambiguity codes for positions where
+                        several bases have a frequency of at least --min-freq
+                        (DNA/RNA only). The default is the most frequent base.
+    --min-freq <f>      Minimum frequency of a base to be included in the
+                        IUPAC code [default: 0.2]
+    --keep-gaps         Keep gaps in the consensus. By default, positions
+                        where a gap is the most frequent state are removed.
+    --no-qual           Ignore quality scores
+    --n-attr <name>     Name of the attribute with the number of sequences
+                        in the group [default: n]
+",
+    common_opts!()
+);
+
+// quality scores of the consensus
+const MAX_QUAL: f64 = 60.;
+
+pub fn run() -> CliResult<()> {
+    let args = opt::Args::new(USAGE)?;
+    let mut cfg = cfg::Config::from_args_with_help(&args, &ConsVarHelp)?;
+
+    let key = args.opt_str("--key");
+    let opts = ConsOpts {
+        iupac: args.get_bool("--iupac"),
+        min_freq: args.value("--min-freq")?,
+        keep_gaps: args.get_bool("--keep-gaps"),
+    };
+    let use_qual = !args.get_bool("--no-qual");
+    let n_attr = args.get_str("--n-attr");
+
+    cfg.add_attrs(&[(n_attr.to_string(), "{cons:n}".to_string())]);
+
+    cfg.writer_with(
+        |_| Ok(ConsVars::new()),
+        |writer, mut vars, cons_vars| {
+            let key = match key {
+                Some(k) => Some(vars.build(|b| varstring::VarString::var_or_composed(k, b))?),
+                None => None,
+            };
+
+            // groups in order of occurrence
+            let mut groups: Vec<Group> = vec![];
+            let mut group_idx: FxHashMap<Vec<u8>, usize> = FxHashMap::default();
+            let mut key_buf = vec![];
+            let mut seq = vec![];
+
+            cfg.read_sequential_var(&mut vars, |record, vars| {
+                key_buf.clear();
+                match key {
+                    Some(ref k) => k.compose(&mut key_buf, vars.symbols()),
+                    None => key_buf.extend_from_slice(b"consensus"),
+                }
+                let i = match group_idx.get(&key_buf) {
+                    Some(&i) => i,
+                    None => {
+                        groups.push(Group::new(key_buf.clone()));
+                        group_idx.insert(key_buf.clone(), groups.len() - 1);
+                        groups.len() - 1
+                    }
+                };
+                seq.clear();
+                record.write_seq(&mut seq);
+                let qual = if use_qual { record.qual() } else { None };
+                groups[i].add(&seq, qual, &vars.data().qual_converter, &opts)?;
+                Ok(true)
+            })?;
+
+            let mut record = OwnedRecord::default();
+            for group in groups {
+                let stats = group.consensus(&mut record, &vars.data().qual_converter, &opts);
+                vars.set_record(&record)?;
+                cons_vars.set_with(&stats, &mut vars.mut_data().symbols);
+                writer.write(&record, &vars)?;
+            }
+            Ok(())
+        },
+    )
+}
+
+struct ConsOpts {
+    iupac: bool,
+    min_freq: f64,
+    keep_gaps: bool,
+}
+
+#[derive(Debug)]
+struct Group {
+    key: Vec<u8>,
+    n: usize,
+    // (character, summed weight) for each position
+    cols: Vec<Vec<(u8, f64)>>,
+    has_qual: bool,
+    is_rna: bool,
+}
+
+impl Group {
+    fn new(key: Vec<u8>) -> Group {
+        Group {
+            key: key,
+            n: 0,
+            cols: vec![],
+            has_qual: false,
+            is_rna: false,
+        }
+    }
+
+    fn add(
+        &mut self,
+        seq: &[u8],
+        qual: Option<&[u8]>,
+        qual_converter: &::io::QualConverter,
+        opts: &ConsOpts,
+    ) -> CliResult<()> {
+        if self.n == 0 {
+            self.cols = vec![vec![]; seq.len()];
+            self.has_qual = qual.is_some();
+        } else if seq.len() != self.cols.len() {
+            return fail!(format!(
+                "Sequences of group '{}' have different lengths ({} and {}). \
+                 A consensus can only be built from sequences of equal length or aligned \
+                 sequences.",
+                String::from_utf8_lossy(&self.key),
+                self.cols.len(),
+                seq.len()
+            ));
+        }
+        self.n += 1;
+
+        for (i, (&c, col)) in seq.iter().zip(&mut self.cols).enumerate() {
+            let mut c = c.to_ascii_uppercase();
+            if c == b'.' {
+                c = b'-';
+            }
+            let w = match qual {
+                Some(q) if c != b'-' => 1. - qual_converter.get_prob(q[i])?,
+                _ => 1.,
+            };
+            if opts.iupac && c != b'-' {
+                // weight is distributed among the bases of ambiguity codes
+                let bases = iupac::bases(c).ok_or_else(|| {
+                    format!(
+                        "Invalid character for IUPAC consensus: '{}'. Only DNA/RNA is possible.",
+                        c as char
+                    )
+                })?;
+                if c == b'U' {
+                    self.is_rna = true;
+                }
+                let w = w / bases.count_ones() as f64;
+                for &b in &[iupac::A, iupac::C, iupac::G, iupac::T] {
+                    if bases & b != 0 {
+                        add_weight(col, iupac::code(b), w);
+                    }
+                }
+            } else {
+                add_weight(col, c, w);
+            }
+        }
+        Ok(())
+    }
+
+    fn consensus(
+        &self,
+        out: &mut OwnedRecord,
+        qual_converter: &::io::QualConverter,
+        opts: &ConsOpts,
+    ) -> ConsStats {
+        out.id.clear();
+        out.id.extend_from_slice(&self.key);
+        out.desc = None;
+        out.seq.clear();
+        let mut qual = vec![];
+        let mut stats = ConsStats {
+            n: self.n,
+            min_support: 1.,
+            mean_support: 0.,
+            ambig: 0,
+        };
+
+        for col in &self.cols {
+            let total: f64 = col.iter().map(|&(_, w)| w).sum();
+            // most frequent state; ties are resolved alphabetically, with bases
+            // preferred over gaps
+            let &(mut c, max) = col.iter()
+                .fold(None, |best: Option<&(u8, f64)>, s| match best {
+                    Some(b) if b.1 > s.1 || b.1 == s.1 && (b.0 == b'-', b.0) < (s.0 == b'-', s.0) => {
+                        Some(b)
+                    }
+                    _ => Some(s),
+                })
+                .unwrap();
+            let mut support = max;
+
+            if c != b'-' && opts.iupac {
+                let non_gap = total - weight(col, b'-');
+                let mut set = 0;
+                support = 0.;
+                for &(b, w) in col {
+                    if b != b'-' && (w / non_gap >= opts.min_freq || w == max) {
+                        set |= iupac::bases(b).unwrap();
+                        support += w;
+                    }
+                }
+                c = iupac::code(set);
+                if set.count_ones() > 1 {
+                    stats.ambig += 1;
+                }
+                if self.is_rna && c == b'T' {
+                    c = b'U';
+                }
+            }
+
+            let frac = if total > 0. { support / total } else { 1. };
+            if frac < stats.min_support {
+                stats.min_support = frac;
+            }
+            stats.mean_support += frac;
+
+            if c == b'-' && !opts.keep_gaps {
+                continue;
+            }
+            out.seq.push(c);
+            if self.has_qual {
+                let q = -10. * (1. - frac).max(1e-10).log10();
+                qual.push(qual_converter.from_phred(min(q.round() as u8, MAX_QUAL as u8)));
+            }
+        }
+        if !self.cols.is_empty() {
+            stats.mean_support /= self.cols.len() as f64;
+        }
+        out.qual = if self.has_qual { Some(qual) } else { None };
+        stats
+    }
+}
+
+fn add_weight(col: &mut Vec<(u8, f64)>, c: u8, w: f64) {
+    for s in col.iter_mut() {
+        if s.0 == c {
+            s.1 += w;
+            return;
+        }
+    }
+    col.push((c, w));
+}
+
+fn weight(col: &[(u8, f64)], c: u8) -> f64 {
+    col.iter().find(|s| s.0 == c).map(|s| s.1).unwrap_or(0.)
+}
+
+struct ConsStats {
+    n: usize,
+    min_support: f64,
+    mean_support: f64,
+    ambig: usize,
+}
+
+pub struct ConsVarHelp;
+
+impl VarHelp for ConsVarHelp {
+    fn name(&self) -> &'static str {
+        "Consensus variables"
+    }
+    fn usage(&self) -> &'static str {
+        "cons:<variable>"
+    }
+    fn vars(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            ("cons:n", "Number of sequences in the group"),
+            (
+                "cons:min_support",
+                "Minimum fraction of (quality weighted) bases agreeing with the \
+                 consensus at any position",
+            ),
+            ("cons:mean_support", "Mean fraction of agreeing bases over all positions"),
+            ("cons:ambig", "Number of ambiguous positions (with --iupac)"),
+        ])
+    }
+    fn examples(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            (
+                "Consensus sequences of clustered reads, with the minimum support",
+                "st consensus -k {a:cluster} -a support={cons:min_support} reads.fq > cons.fq",
+            ),
+            (
+                "USEARCH style size annotation",
+                "st consensus -k {a:otu} --attr-fmt usearch --n-attr size seqs.fa > cons.fa",
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ConsVar {
+    N,
+    MinSupport,
+    MeanSupport,
+    Ambig,
+}
+
+#[derive(Debug)]
+pub struct ConsVars {
+    vars: Vec<(ConsVar, usize)>,
+}
+
+impl ConsVars {
+    pub fn new() -> ConsVars {
+        ConsVars { vars: vec![] }
+    }
+
+    fn set_with(&self, stats: &ConsStats, symbols: &mut var::symbols::Table) {
+        for &(var, id) in &self.vars {
+            match var {
+                ConsVar::N => symbols.set_int(id, stats.n as i64),
+                ConsVar::MinSupport => symbols.set_float(id, stats.min_support),
+                ConsVar::MeanSupport => symbols.set_float(id, stats.mean_support),
+                ConsVar::Ambig => symbols.set_int(id, stats.ambig as i64),
+            }
+        }
+    }
+}
+
+impl VarProvider for ConsVars {
+    fn prefix(&self) -> Option<&str> {
+        Some("cons")
+    }
+
+    fn name(&self) -> &'static str {
+        "consensus"
+    }
+
+    fn register_var(&mut self, name: &str, id: usize, _: &mut var::VarStore) -> CliResult<bool> {
+        let var = match name {
+            "n" => ConsVar::N,
+            "min_support" => ConsVar::MinSupport,
+            "mean_support" => ConsVar::MeanSupport,
+            "ambig" => ConsVar::Ambig,
+            _ => return Ok(false),
+        };
+        self.vars.push((var, id));
+        Ok(true)
+    }
+
+    fn has_vars(&self) -> bool {
+        !self.vars.is_empty()
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 use std::fmt::Write;
 use std::mem;
+use std::sync::Mutex;
 
 use csv;
 
@@ -27,6 +28,7 @@ Options:
                         Example: 'n:10:{s:seqlen}'
     -n, --no-int        Don't print intervals when using the 'n:<interval>:<key> syntax',
                         instead only upper limits (e.g. '5' instead of '(1,5]')
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -37,11 +39,12 @@ pub fn run() -> CliResult<()> {
 
     let keys = args.get_vec("--key");
     let print_intervals = !args.get_bool("--no-int");
+    let num_threads = args.thread_num()?;
 
     if keys.is_empty() {
         count_simple(&cfg)
     } else {
-        count_categorized(&cfg, &keys, print_intervals)
+        count_categorized(&cfg, &keys, print_intervals, num_threads)
     }
 }
 
@@ -61,7 +64,12 @@ fn count_simple(cfg: &cfg::Config) -> CliResult<()> {
     Ok(())
 }
 
-fn count_categorized(cfg: &cfg::Config, keys: &[&str], print_intervals: bool) -> CliResult<()> {
+fn count_categorized(
+    cfg: &cfg::Config,
+    keys: &[&str],
+    print_intervals: bool,
+    num_threads: u32,
+) -> CliResult<()> {
     cfg.io_writer(|writer, mut vars| {
         // register variables & parse types
         let var_keys: Vec<_> = keys
@@ -74,53 +82,73 @@ fn count_categorized(cfg: &cfg::Config, keys: &[&str], print_intervals: bool) ->
             .collect::<CliResult<_>>()?;
         // count
         let mut counts = FxHashMap::default();
-
-        // vec of reusable strings for generating the key values
-        let mut values = vec![vec![]; var_keys.len()];
-        // reusable key that is only cloned when not present in the hash map
-        let mut key = vec![(Category::Text(vec![]), false); var_keys.len()];
-
-        cfg.read_sequential_var(&mut vars, |_, vars| {
-            for (
-                (&(ref key, ref interval), ref mut value),
-                &mut (ref mut cat, ref mut is_different),
-            ) in var_keys.iter().zip(&mut values).zip(&mut key)
-            {
-                if let Some(&(int, _)) = interval.as_ref() {
-                    if let Some(v) = key.get_float(vars.symbols())? {
-                        if !v.is_nan() {
-                            let v = v / int as f64;
-                            let f = v.floor();
-                            if relative_ne!(v, f) {
-                                *is_different = true;
+        // whether a numeric key column has values that are not
+        // multiples of the interval
+        let mut is_different = vec![false; var_keys.len()];
+
+        // every record set gets its own copy (VarString is not Sync)
+        let keys_copy = Mutex::new(var_keys.clone());
+        let n_keys = var_keys.len();
+
+        // The keys are composed in the worker threads, the main thread only
+        // counts. `values` is a vec of reusable strings for generating the key
+        // values, the key is only cloned when not present in the hash map.
+        cfg.var_parallel_edit(
+            &mut vars,
+            num_threads,
+            || Ok(keys_copy.lock().unwrap().clone()),
+            || {
+                (
+                    vec![Category::Text(vec![]); n_keys],
+                    vec![false; n_keys],
+                    vec![vec![]; n_keys],
+                )
+            },
+            |_, &mut (ref mut key, ref mut diff, ref mut values), var_keys, vars| {
+                for (((&(ref key, ref interval), ref mut value), cat), is_different) in
+                    var_keys.iter().zip(values).zip(key).zip(diff)
+                {
+                    *is_different = false;
+                    if let Some(&(int, _)) = interval.as_ref() {
+                        if let Some(v) = key.get_float(vars.symbols())? {
+                            if !v.is_nan() {
+                                let v = v / int as f64;
+                                let f = v.floor();
+                                if relative_ne!(v, f) {
+                                    *is_different = true;
+                                }
+                                *cat = Category::Num(f as i64);
+                            } else {
+                                *cat = Category::NaN;
                             }
-                            *cat = Category::Num(f as i64);
                         } else {
-                            *cat = Category::NaN;
+                            *cat = Category::NA;
                         }
                     } else {
-                        *cat = Category::NA;
-                    }
-                } else {
-                    value.clear();
-                    key.compose(value, vars.symbols());
-                    if let Category::Text(ref mut v) = *cat {
-                        mem::swap(v, value);
-                    } else {
-                        *cat = Category::Text(value.clone());
+                        value.clear();
+                        key.compose(value, vars.symbols());
+                        if let Category::Text(ref mut v) = *cat {
+                            mem::swap(v, value);
+                        } else {
+                            *cat = Category::Text(value.clone());
+                        }
                     }
                 }
-            }
-
-            // cannot use Entry API because this would require the key to be cloned
-            if let Some(v) = counts.get_mut(&key) {
-                *v += 1;
-                return Ok(true);
-            }
-            counts.insert(key.clone(), 1);
-
-            Ok(true)
-        })?;
+                Ok(())
+            },
+            |_, &mut (ref key, ref diff, _), _| {
+                for (d, &rec_d) in is_different.iter_mut().zip(diff) {
+                    *d |= rec_d;
+                }
+                // cannot use Entry API because this would require the key to be cloned
+                if let Some(v) = counts.get_mut(key) {
+                    *v += 1;
+                    return Ok(true);
+                }
+                counts.insert(key.clone(), 1);
+                Ok(true)
+            },
+        )?;
 
         // sort
         let mut sorted: Vec<_> = counts.into_iter().collect();
@@ -132,8 +160,11 @@ fn count_categorized(cfg: &cfg::Config, keys: &[&str], print_intervals: bool) ->
 
         let mut record = vec![String::new(); var_keys.len() + 1];
         for (ref keys, count) in sorted {
-            for ((ref mut field, &(ref c, is_different)), &(_, ref interval)) in
-                record.iter_mut().zip(keys).zip(&var_keys)
+            for (((ref mut field, c), &(_, ref interval)), &is_different) in record
+                .iter_mut()
+                .zip(keys)
+                .zip(&var_keys)
+                .zip(&is_different)
             {
                 field.clear();
                 match *c {
@@ -17,6 +17,7 @@ Usage:
 Options:
     -d, --desc          Delete description
     --attrs <names>     Delete attributes (comma delimited list)
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -27,6 +28,7 @@ pub fn run() -> CliResult<()> {
 
     let del_desc = args.get_bool("--desc");
     let attrs = args.opt_str("--attrs");
+    let num_threads = args.thread_num()?;
 
     cfg.writer(|writer, mut vars| {
         if let Some(attrs) = attrs {
@@ -38,15 +40,21 @@ pub fn run() -> CliResult<()> {
             })?;
         }
 
-        cfg.read_sequential_var(&mut vars, |record, vars| {
-            if del_desc {
-                let id = record.id_bytes();
-                let record = DefRecord::new(&record, id, None);
-                writer.write(&record, vars)?;
-            } else {
-                writer.write(&record, vars)?;
-            }
-            Ok(true)
-        })
-    })
+        cfg.var_parallel::<_, _, ()>(
+            &mut vars,
+            num_threads,
+            |_, _| Ok(()),
+            |record, _, vars| {
+                if del_desc {
+                    let id = record.id_bytes();
+                    let record = DefRecord::new(&record, id, None);
+                    writer.write(&record, vars)?;
+                } else {
+                    writer.write(&record, vars)?;
+                }
+                Ok(true)
+            },
+        )
+    })?;
+    Ok(())
 }
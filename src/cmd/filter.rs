@@ -19,6 +19,7 @@ Options:
     --dropped <file>    Output file for sequences that were removed by filtering.
                         The extension is autorecognized if possible, fallback
                         is the input format.
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -28,37 +29,44 @@ pub fn run() -> CliResult<()> {
     let cfg = cfg::Config::from_args(&args)?;
     let expr = args.get_str("<expression>");
     let dropped_file = args.opt_str("--dropped");
+    let num_threads = args.thread_num()?;
 
     cfg.writer(|writer, mut vars| {
         let expr_id = vars.build(|b| b.register_with_prefix(Some("expr_"), expr))?;
         let mut dropped_file =
             dropped_file.map_res(|s| cfg.other_writer(s, Some(&mut vars), None))?;
 
-        cfg.read_sequential_var(&mut vars, |record, vars| {
-            let result = vars
-                .symbols()
-                .get_float(expr_id)?
-                .expect("Bug: expression value not in symbol table!");
+        cfg.var_parallel::<_, _, ()>(
+            &mut vars,
+            num_threads,
+            |_, _| Ok(()),
+            |record, _, vars| {
+                let result = vars
+                    .symbols()
+                    .get_float(expr_id)?
+                    .expect("Bug: expression value not in symbol table!");
 
-            if result == 1. {
-                writer.write(&record, vars)?;
-            } else if result == 0. {
-                if let Some(w) = dropped_file.as_mut() {
-                    w.write(&record, vars)?;
+                if result == 1. {
+                    writer.write(&record, vars)?;
+                } else if result == 0. {
+                    if let Some(w) = dropped_file.as_mut() {
+                        w.write(&record, vars)?;
+                    }
+                } else if result == NAN {
+                    // cannot use match because of NAN
+                    return fail!(format!(
+                        "Undefined result of math expression for record '{}'",
+                        String::from_utf8_lossy(record.id_bytes())
+                    ));
+                } else {
+                    return fail!(format!(
+                        "Math expressions may only return false (0) or true (1), but the returned value is {}.",
+                        result
+                    ));
                 }
-            } else if result == NAN {
-                // cannot use match because of NAN
-                return fail!(format!(
-                    "Undefined result of math expression for record '{}'",
-                    String::from_utf8_lossy(record.id_bytes())
-                ));
-            } else {
-                return fail!(format!(
-                    "Math expressions may only return false (0) or true (1), but the returned value is {}.",
-                    result
-                ));
-            }
-            Ok(true)
-        })
-    })
+                Ok(true)
+            },
+        )
+    })?;
+    Ok(())
 }
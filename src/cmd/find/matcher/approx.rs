@@ -1,46 +1,74 @@
 extern crate pattern_matching;
-extern crate ref_slice;
 
 use super::*;
 use error::CliResult;
+use std::cmp::min;
 use std::collections::HashMap;
 
 use itertools::Itertools;
 
 use self::pattern_matching::myers::Myers;
+use self::pattern_matching::myers_long::MyersLong;
+pub use self::pattern_matching::myers::AlignmentOperation;
+
+/// Max. pattern length for the (faster) single-word Myers algorithm
+const MAX_SHORT_LEN: usize = 64;
+
+enum MyersImpl {
+    Short(Myers),
+    // block-based implementation for patterns longer than 64 symbols
+    Long(MyersLong),
+}
 
 pub struct MyersMatcher {
-    myers: Myers,
-    max_dist: u8,
+    myers: MyersImpl,
+    max_dist: u16,
     needs_start: bool,
+    needs_path: bool,
     sort_vec: Option<Vec<Match>>,
+    ops: Vec<AlignmentOperation>,
+}
+
+/// Returns the matching characters for each position of the pattern,
+/// taking ambiguities into account
+pub fn pattern_variants(pattern: &[u8], ambig_trans: Option<&HashMap<u8, Vec<u8>>>) -> Vec<Vec<u8>> {
+    pattern
+        .iter()
+        .map(|b| {
+            let mut v = vec![*b];
+            if let Some(eq) = ambig_trans.and_then(|t| t.get(b)) {
+                v.extend(eq.iter().filter(|&c| c != b));
+            }
+            v
+        })
+        .collect()
 }
 
 impl MyersMatcher {
+    /// Creates a new matcher given the matching characters for each
+    /// pattern position (see `pattern_variants`)
     pub fn new(
-        pattern: &[u8],
-        max_dist: u8,
+        variants: &[Vec<u8>],
+        max_dist: u16,
         needs_start: bool,
+        needs_path: bool,
         sorted: bool,
-        ambig_trans: Option<&HashMap<u8, Vec<u8>>>,
     ) -> CliResult<MyersMatcher> {
-        let myers = if let Some(t) = ambig_trans {
-            let variants = pattern.into_iter().map(|b| {
-                t.get(b)
-                    .map(|v| v.as_slice())
-                    .unwrap_or_else(|| ref_slice::ref_slice(b))
-                    .iter()
-                    .cloned()
-            });
-            Myers::from_variants(variants)
+        let len = variants.len();
+        let variants = variants.iter().map(|v| v.iter().cloned());
+        let myers = if len <= MAX_SHORT_LEN {
+            MyersImpl::Short(Myers::from_variants(variants))
         } else {
-            Myers::new(pattern)
+            MyersImpl::Long(MyersLong::from_variants(variants))
         };
 
         Ok(MyersMatcher {
             myers: myers,
-            max_dist: max_dist,
-            needs_start: needs_start,
+            // the distance cannot be larger than the pattern length
+            max_dist: min(max_dist, len as u16),
+            needs_start: needs_start || needs_path,
+            needs_path: needs_path,
+            ops: vec![],
             sort_vec: if sorted { Some(vec![]) } else { None },
         })
     }
@@ -48,30 +76,314 @@ impl MyersMatcher {
 
 impl Matcher for MyersMatcher {
     fn iter_matches(&mut self, text: &[u8], func: &mut FnMut(&Hit) -> bool) {
-        if self.needs_start {
-            // group hits by start position
-            let by_start = self
-                .myers
-                .find_all_pos(text, self.max_dist)
-                .group_by(|&(start, _, _)| start);
-
-            let iter = by_start
-                .into_iter()
-                .map(|(_, it)| {
-                    let mut out = None;
-                    let mut best_dist = ::std::u8::MAX;
-                    for m in it {
-                        if (m.2) < best_dist {
-                            best_dist = m.2;
-                            out = Some(m);
+        // same code for both implementations
+        macro_rules! iter_matches {
+            ($myers:expr) => {
+                if self.needs_path {
+                    // alignment path needed: keep the best hit for each start position
+                    let ops = &mut self.ops;
+                    let mut matches = $myers.find_all_pos(text, self.max_dist as _);
+                    let mut best: Option<Match> = None;
+                    let iter = ::std::iter::from_fn(|| {
+                        while let Some((start, end, dist)) = matches.next_path(ops) {
+                            let dist = dist as u16;
+                            let mut out = None;
+                            if best.as_ref().map(|b| b.start != start) == Some(true) {
+                                out = best.take();
+                            }
+                            if best.as_ref().map(|b| dist < b.dist) != Some(false) {
+                                best = Some(Match::from_ops(start, end, dist, ops));
+                            }
+                            if out.is_some() {
+                                return out;
+                            }
                         }
+                        best.take()
+                    });
+
+                    opt_sorted(
+                        iter,
+                        self.sort_vec.as_mut(),
+                        |m| m.dist,
+                        |m| {
+                            let h = SimpleHit(m);
+                            func(&h)
+                        },
+                    );
+                } else if self.needs_start {
+                    // group hits by start position
+                    let by_start = $myers
+                        .find_all_pos(text, self.max_dist as _)
+                        .group_by(|&(start, _, _)| start);
+
+                    let iter = by_start
+                        .into_iter()
+                        .map(|(_, it)| {
+                            let mut out: Option<(usize, usize, _)> = None;
+                            for m in it {
+                                if out.map_or(true, |o| m.2 < o.2) {
+                                    out = Some(m);
+                                }
+                            }
+                            out.unwrap()
+                        })
+                        .map(|(start, end, dist)| Match::new(start, end, dist as u16, 0, 0, 0));
+
+                    opt_sorted(
+                        iter,
+                        self.sort_vec.as_mut(),
+                        |m| m.dist,
+                        |m| {
+                            let h = SimpleHit(m);
+                            func(&h)
+                        },
+                    );
+                } else {
+                    // only end position needed
+                    let iter = $myers
+                        .find_all_end(text, self.max_dist as _)
+                        .map(|(end, dist)| Match::new(0, end + 1, dist as u16, 0, 0, 0));
+
+                    opt_sorted(
+                        iter,
+                        self.sort_vec.as_mut(),
+                        |m| m.dist,
+                        |m| {
+                            let h = SimpleHit(m);
+                            func(&h)
+                        },
+                    );
+                }
+            };
+        }
+
+        match self.myers {
+            MyersImpl::Short(ref mut myers) => iter_matches!(myers),
+            MyersImpl::Long(ref mut myers) => iter_matches!(myers),
+        }
+    }
+}
+
+/// Costs for approximate matching with `WeightedMatcher`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Costs {
+    pub subst: u16,
+    pub ins: u16,
+    pub del: u16,
+    /// max. number of pattern characters overhanging the text ends
+    /// without penalty
+    pub free_ends: usize,
+}
+
+impl Default for Costs {
+    fn default() -> Costs {
+        Costs {
+            subst: 1,
+            ins: 1,
+            del: 1,
+            free_ends: 0,
+        }
+    }
+}
+
+/// Approximate matching with a weighted edit distance using dynamic programming
+/// (Sellers algorithm). Slower than `MyersMatcher`, but allows for different
+/// costs of substitutions, insertions and deletions, and for patterns
+/// overhanging the text ends (free end gaps).
+pub struct WeightedMatcher {
+    // for each pattern position: table of matching characters
+    eq: Vec<[bool; 256]>,
+    costs: Costs,
+    max_dist: u16,
+    needs_start: bool,
+    needs_path: bool,
+    sort_vec: Option<Vec<Match>>,
+    col: Vec<u16>,
+    prev_col: Vec<u16>,
+    // (end, pattern length without overhang, dist)
+    ends: Vec<(usize, usize, u16)>,
+    matrix: Vec<u16>,
+    ops: Vec<AlignmentOperation>,
+}
+
+impl WeightedMatcher {
+    pub fn new(
+        variants: &[Vec<u8>],
+        max_dist: u16,
+        costs: Costs,
+        needs_start: bool,
+        needs_path: bool,
+        sorted: bool,
+    ) -> CliResult<WeightedMatcher> {
+        debug_assert!(costs.subst > 0 && costs.ins > 0 && costs.del > 0);
+        let eq = variants
+            .iter()
+            .map(|v| {
+                let mut eq = [false; 256];
+                for &b in v {
+                    eq[b as usize] = true;
+                }
+                eq
+            })
+            .collect();
+
+        Ok(WeightedMatcher {
+            eq: eq,
+            costs: costs,
+            max_dist: max_dist,
+            needs_start: needs_start || needs_path,
+            needs_path: needs_path,
+            sort_vec: if sorted { Some(vec![]) } else { None },
+            col: vec![],
+            prev_col: vec![],
+            ends: vec![],
+            matrix: vec![],
+            ops: vec![],
+        })
+    }
+
+    // initial column: the pattern start may overhang the text start
+    fn init_col(&self, col: &mut Vec<u16>) {
+        let free = self.costs.free_ends;
+        col.clear();
+        col.extend((0..self.eq.len() + 1).map(|i| {
+            (i.saturating_sub(free) as u16).saturating_mul(self.costs.ins)
+        }));
+    }
+
+    // calculates the next column given a text character
+    #[inline]
+    fn step(&self, prev: &[u16], col: &mut [u16], c: u8) {
+        let costs = self.costs;
+        col[0] = 0;
+        for i in 1..col.len() {
+            let s = if self.eq[i - 1][c as usize] { 0 } else { costs.subst };
+            let diag = prev[i - 1].saturating_add(s);
+            let left = prev[i].saturating_add(costs.del);
+            let up = col[i - 1].saturating_add(costs.ins);
+            col[i] = min(diag, min(left, up));
+        }
+    }
+
+    /// Finds all end positions of hits, given as (end, aligned pattern length, distance).
+    /// The aligned pattern length is only smaller than the pattern length if
+    /// the pattern end overhangs the text end.
+    fn find_ends(&mut self, text: &[u8]) {
+        let m = self.eq.len();
+        let mut col = ::std::mem::replace(&mut self.col, vec![]);
+        let mut prev = ::std::mem::replace(&mut self.prev_col, vec![]);
+        self.init_col(&mut prev);
+        col.clear();
+        col.resize(m + 1, 0);
+        self.ends.clear();
+        for (j, &c) in text.iter().enumerate() {
+            self.step(&prev, &mut col, c);
+            let mut best = (m, col[m]);
+            if j + 1 == text.len() {
+                // the pattern end may overhang the text end
+                for i in m.saturating_sub(self.costs.free_ends)..m {
+                    if col[i] < best.1 {
+                        best = (i, col[i]);
                     }
-                    out.unwrap()
-                })
-                .map(|(start, end, dist)| Match::new(start, end, u16::from(dist), 0, 0, 0));
+                }
+            }
+            if best.1 <= self.max_dist {
+                self.ends.push((j + 1, best.0, best.1));
+            }
+            ::std::mem::swap(&mut prev, &mut col);
+        }
+        self.col = col;
+        self.prev_col = prev;
+    }
+
+    /// Aligns the pattern (of which the first `pattern_len` characters are aligned)
+    /// to the text ending at `end` and returns the start position. The alignment
+    /// path is written to `self.ops`.
+    fn traceback(&mut self, text: &[u8], end: usize, pattern_len: usize) -> usize {
+        let m = pattern_len;
+        let max_len = m + (self.max_dist / self.costs.del) as usize;
+        let start = end.saturating_sub(max_len);
+        let window = &text[start..end];
+        let n = window.len();
+        let rows = m + 1;
 
+        // fill the matrix (column-wise)
+        let mut matrix = ::std::mem::replace(&mut self.matrix, vec![]);
+        matrix.clear();
+        matrix.resize(rows * (n + 1), 0);
+        {
+            let free = if start == 0 { self.costs.free_ends } else { 0 };
+            for i in 0..rows {
+                matrix[i] = (i.saturating_sub(free) as u16).saturating_mul(self.costs.ins);
+            }
+            for (j, &c) in window.iter().enumerate() {
+                let (prev, col) = matrix[j * rows..(j + 2) * rows].split_at_mut(rows);
+                self.step(prev, col, c);
+            }
+        }
+
+        // traceback
+        self.ops.clear();
+        // overhanging pattern end
+        for _ in pattern_len..self.eq.len() {
+            self.ops.push(AlignmentOperation::Ins);
+        }
+        let (mut i, mut j) = (m, n);
+        let d = |i: usize, j: usize| matrix[j * rows + i];
+        while i > 0 {
+            if j == 0 {
+                // overhanging pattern start (or start of the window)
+                self.ops.push(AlignmentOperation::Ins);
+                i -= 1;
+                continue;
+            }
+            let cur = d(i, j);
+            let is_eq = self.eq[i - 1][window[j - 1] as usize];
+            let s = if is_eq { 0 } else { self.costs.subst };
+            if d(i - 1, j - 1).saturating_add(s) == cur {
+                self.ops.push(if is_eq {
+                    AlignmentOperation::Match
+                } else {
+                    AlignmentOperation::Subst
+                });
+                i -= 1;
+                j -= 1;
+            } else if d(i, j - 1).saturating_add(self.costs.del) == cur {
+                self.ops.push(AlignmentOperation::Del);
+                j -= 1;
+            } else {
+                self.ops.push(AlignmentOperation::Ins);
+                i -= 1;
+            }
+        }
+        self.ops.reverse();
+        self.matrix = matrix;
+        start + j
+    }
+}
+
+impl Matcher for WeightedMatcher {
+    fn iter_matches(&mut self, text: &[u8], func: &mut FnMut(&Hit) -> bool) {
+        self.find_ends(text);
+        let ends = ::std::mem::replace(&mut self.ends, vec![]);
+
+        if self.needs_start {
+            // keep the best hit for each start position
+            let mut hits: Vec<Match> = vec![];
+            for &(end, pattern_len, dist) in &ends {
+                let start = self.traceback(text, end, pattern_len);
+                if let Some(last) = hits.last_mut() {
+                    if last.start == start {
+                        if dist < last.dist {
+                            *last = self.make_match(start, end, dist);
+                        }
+                        continue;
+                    }
+                }
+                hits.push(self.make_match(start, end, dist));
+            }
             opt_sorted(
-                iter,
+                hits.into_iter(),
                 self.sort_vec.as_mut(),
                 |m| m.dist,
                 |m| {
@@ -80,12 +392,9 @@ impl Matcher for MyersMatcher {
                 },
             );
         } else {
-            // only end position needed
-            let iter = self
-                .myers
-                .find_all_end(text, self.max_dist)
-                .map(|(end, dist)| Match::new(0, end + 1, u16::from(dist), 0, 0, 0));
-
+            let iter = ends
+                .iter()
+                .map(|&(end, _, dist)| Match::new(0, end, dist, 0, 0, 0));
             opt_sorted(
                 iter,
                 self.sort_vec.as_mut(),
@@ -96,32 +405,16 @@ impl Matcher for MyersMatcher {
                 },
             );
         }
+        self.ends = ends;
     }
 }
 
-#[inline(always)]
-fn opt_sorted<T, U, I, K, F>(iter: I, sort_vec: Option<&mut Vec<T>>, ord_key: K, mut func: F)
-where
-    I: Iterator<Item = T>,
-    K: Fn(&T) -> U,
-    U: Ord,
-    T: Clone,
-    F: FnMut(T) -> bool,
-{
-    if let Some(v) = sort_vec {
-        v.clear();
-        v.extend(iter);
-        v.sort_by_key(ord_key);
-        for item in v {
-            if !func(item.clone()) {
-                break;
-            }
-        }
-    } else {
-        for item in iter {
-            if !func(item) {
-                break;
-            }
+impl WeightedMatcher {
+    fn make_match(&self, start: usize, end: usize, dist: u16) -> Match {
+        if self.needs_path {
+            Match::from_ops(start, end, dist, &self.ops)
+        } else {
+            Match::new(start, end, dist, 0, 0, 0)
         }
     }
 }
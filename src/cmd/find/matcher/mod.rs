@@ -2,11 +2,17 @@ use std::fmt::Debug;
 
 mod approx;
 mod exact;
+mod multi;
+mod pwm;
 mod regex;
+mod score;
 
 pub use self::approx::*;
 pub use self::exact::*;
+pub use self::multi::*;
+pub use self::pwm::*;
 pub use self::regex::*;
+pub use self::score::*;
 
 pub trait Matcher {
     fn iter_matches(&mut self, text: &[u8], func: &mut FnMut(&Hit) -> bool);
@@ -42,7 +48,7 @@ impl Hit for SimpleHit {
 }
 
 /// contains 0-based coordinates and distance
-#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Match {
     pub start: usize,
     pub end: usize,
@@ -50,6 +56,16 @@ pub struct Match {
     pub subst: u16,
     pub ins: u16,
     pub del: u16,
+    /// Alignment path of the pattern (from start to end), only present
+    /// for approximate matches if the traceback was requested.
+    /// `Ins` means that a pattern character is missing in the text,
+    /// `Del` means that a text character is missing in the pattern.
+    pub ops: Vec<AlignmentOperation>,
+    /// Alignment score (with substitution matrices) or log-odds score
+    /// (with position weight matrices)
+    pub score: Option<f64>,
+    /// P-value of the score (only with position weight matrices)
+    pub pvalue: Option<f64>,
 }
 
 impl Match {
@@ -61,9 +77,43 @@ impl Match {
             subst: subst,
             ins: ins,
             del: del,
+            ops: vec![],
+            score: None,
+            pvalue: None,
         }
     }
 
+    /// Creates a match from an alignment path, counting the number of
+    /// substitutions, insertions and deletions
+    pub fn from_ops(start: usize, end: usize, dist: u16, ops: &[AlignmentOperation]) -> Match {
+        let mut m = Match::new(start, end, dist, 0, 0, 0);
+        for op in ops {
+            match *op {
+                AlignmentOperation::Subst => m.subst += 1,
+                AlignmentOperation::Ins => m.ins += 1,
+                AlignmentOperation::Del => m.del += 1,
+                AlignmentOperation::Match => {}
+            }
+        }
+        m.ops.extend_from_slice(ops);
+        m
+    }
+
+    /// Iterates over the alignment operations. If there is no alignment path
+    /// (exact or regex matching), the whole match is assumed to be identical.
+    pub fn ops_iter<'a>(&'a self) -> Box<Iterator<Item = AlignmentOperation> + 'a> {
+        if self.ops.is_empty() {
+            Box::new(::std::iter::repeat(AlignmentOperation::Match).take(self.end - self.start))
+        } else {
+            Box::new(self.ops.iter().cloned())
+        }
+    }
+
+    /// Reverses the alignment path (used after reverse complementing the text)
+    pub fn reverse_ops(&mut self) {
+        self.ops.reverse();
+    }
+
     pub fn neg_start1(&self, seq_len: usize) -> i64 {
         self.start as i64 - seq_len as i64
     }
@@ -72,3 +122,31 @@ impl Match {
         self.end as i64 - seq_len as i64 - 1
     }
 }
+
+/// Calls `func` for each item, optionally sorting the items by `ord_key` first
+#[inline(always)]
+fn opt_sorted<T, U, I, K, F>(iter: I, sort_vec: Option<&mut Vec<T>>, ord_key: K, mut func: F)
+where
+    I: Iterator<Item = T>,
+    K: Fn(&T) -> U,
+    U: Ord,
+    T: Clone,
+    F: FnMut(T) -> bool,
+{
+    if let Some(v) = sort_vec {
+        v.clear();
+        v.extend(iter);
+        v.sort_by_key(ord_key);
+        for item in v {
+            if !func(item.clone()) {
+                break;
+            }
+        }
+    } else {
+        for item in iter {
+            if !func(item) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+extern crate aho_corasick;
+
+use std::collections::HashMap;
+
+use self::aho_corasick::AhoCorasick;
+use error::CliResult;
+
+/// Minimum length of a seed. Patterns that would require shorter seeds are
+/// always searched.
+const MIN_SEED_LEN: usize = 5;
+
+/// Pre-filter for searching many patterns at once. All patterns are split into
+/// seeds, which are searched in a single pass using the Aho-Corasick algorithm.
+/// Only patterns with at least one seed present in the text can match and need
+/// to be searched by their individual matcher.
+///
+/// With exact matching, the seed is the whole pattern. With approximate matching
+/// and up to k edits, a pattern is split into k + 1 non-overlapping seeds, of which
+/// at least one has to occur without errors (pigeonhole principle).
+pub struct MultiFilter {
+    ac: AhoCorasick,
+    // pattern index for each seed
+    seed_patterns: Vec<usize>,
+    // patterns without seeds, which are always searched
+    always: Vec<usize>,
+    candidates: Vec<bool>,
+}
+
+impl MultiFilter {
+    /// Creates a new filter from patterns of the form (pattern, max. number of edits,
+    /// ambiguity map). Returns `None` if no pattern could be split into seeds.
+    pub fn new<'a, I>(patterns: I) -> CliResult<Option<MultiFilter>>
+    where
+        I: IntoIterator<Item = (&'a [u8], usize, Option<&'a HashMap<u8, Vec<u8>>>)>,
+    {
+        let mut seeds = vec![];
+        let mut seed_patterns = vec![];
+        let mut always = vec![];
+        let mut n = 0;
+
+        for (i, (pattern, max_edits, ambig)) in patterns.into_iter().enumerate() {
+            n += 1;
+            let n_seeds = max_edits + 1;
+            let seed_len = pattern.len() / n_seeds;
+            // seeds must not contain ambiguous characters
+            let is_ambig = |s: &[u8]| ambig.map(|a| s.iter().any(|b| a.contains_key(b))) == Some(true);
+            let pattern_seeds: Vec<_> = (0..n_seeds)
+                .map(|j| &pattern[j * seed_len..(j + 1) * seed_len])
+                .collect();
+            if seed_len < MIN_SEED_LEN && seed_len < pattern.len()
+                || pattern_seeds.iter().any(|s| s.is_empty() || is_ambig(s))
+            {
+                always.push(i);
+                continue;
+            }
+            for s in pattern_seeds {
+                seeds.push(s);
+                seed_patterns.push(i);
+            }
+        }
+
+        if seeds.is_empty() {
+            return Ok(None);
+        }
+
+        let ac = AhoCorasick::new(&seeds)
+            .map_err(|e| format!("Could not build the multi-pattern filter: {}", e))?;
+
+        Ok(Some(MultiFilter {
+            ac: ac,
+            seed_patterns: seed_patterns,
+            always: always,
+            candidates: vec![false; n],
+        }))
+    }
+
+    /// Searches the text for all seeds and returns a slice indicating for each
+    /// pattern whether it possibly matches.
+    pub fn candidates(&mut self, text: &[u8]) -> &[bool] {
+        for c in &mut self.candidates {
+            *c = false;
+        }
+        for &i in &self.always {
+            self.candidates[i] = true;
+        }
+        for m in self.ac.find_overlapping_iter(text) {
+            self.candidates[self.seed_patterns[m.pattern().as_usize()]] = true;
+        }
+        &self.candidates
+    }
+}
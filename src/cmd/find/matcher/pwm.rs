@@ -0,0 +1,532 @@
+use std::cmp::min;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::*;
+use error::CliResult;
+
+/// Total pseudocount added to each matrix column (distributed according to
+/// the background frequencies)
+const PSEUDOCOUNT: f64 = 0.8;
+
+/// Number of sites assumed for matrices that contain frequencies instead of counts
+const DEFAULT_NSITES: f64 = 20.;
+
+/// Background frequency of each nucleotide
+const BACKGROUND: f64 = 0.25;
+
+/// Scores are multiplied by this factor and rounded for calculating
+/// the score distribution (p-values)
+const PVALUE_SCALE: f64 = 100.;
+
+/// Minimum score of a hit, either an absolute log-odds score or a fraction
+/// of the score range of the matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PwmThreshold {
+    Abs(f64),
+    Rel(f64),
+}
+
+impl PwmThreshold {
+    /// Parses '<score>' or '<percent>%'
+    pub fn parse(s: &str) -> CliResult<PwmThreshold> {
+        let s = s.trim();
+        if s.ends_with('%') {
+            let p: f64 = s[..s.len() - 1]
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid relative score threshold: {}", s))?;
+            if p < 0. || p > 100. {
+                return fail!(format!("Relative score thresholds must be between 0 and 100%: {}", s));
+            }
+            Ok(PwmThreshold::Rel(p / 100.))
+        } else {
+            let v = s
+                .parse()
+                .map_err(|_| format!("Invalid score threshold: {}", s))?;
+            Ok(PwmThreshold::Abs(v))
+        }
+    }
+}
+
+/// Position weight matrix with log2-odds scores for A, C, G and T at each position
+#[derive(Clone, Debug)]
+pub struct Pwm {
+    pub name: String,
+    scores: Vec<[f64; 4]>,
+    // scores scaled by PVALUE_SCALE and rounded
+    int_scores: Vec<[i64; 4]>,
+    // lowest possible integer score
+    int_min: i64,
+    // P(score >= int_min + i) given the background frequencies
+    tail: Arc<Vec<f64>>,
+}
+
+impl Pwm {
+    /// Creates a matrix from counts (or frequencies) of A, C, G and T
+    /// at each position
+    pub fn from_counts(name: &str, counts: &[[f64; 4]]) -> CliResult<Pwm> {
+        if counts.is_empty() {
+            return fail!(format!("The matrix '{}' is empty.", name));
+        }
+        if counts.iter().flat_map(|c| c.iter()).any(|&c| c < 0. || !c.is_finite()) {
+            return fail!(format!("The matrix '{}' contains invalid counts.", name));
+        }
+        let scores: Vec<_> = counts
+            .iter()
+            .map(|col| {
+                let mut n: f64 = col.iter().sum();
+                let mut col = *col;
+                if n <= 1.01 {
+                    // frequencies
+                    for c in &mut col {
+                        *c *= DEFAULT_NSITES;
+                    }
+                    n *= DEFAULT_NSITES;
+                }
+                let mut out = [0.; 4];
+                for (o, c) in out.iter_mut().zip(&col) {
+                    let p = (c + PSEUDOCOUNT * BACKGROUND) / (n + PSEUDOCOUNT);
+                    *o = (p / BACKGROUND).log2();
+                }
+                out
+            })
+            .collect();
+        Ok(Self::from_scores(name, scores))
+    }
+
+    fn from_scores(name: &str, scores: Vec<[f64; 4]>) -> Pwm {
+        let int_scores: Vec<_> = scores
+            .iter()
+            .map(|col| {
+                let mut out = [0; 4];
+                for (o, s) in out.iter_mut().zip(col) {
+                    *o = (s * PVALUE_SCALE).round() as i64;
+                }
+                out
+            })
+            .collect();
+
+        // score distribution given the background
+        let int_min: i64 = int_scores.iter().map(|c| *c.iter().min().unwrap()).sum();
+        let int_max: i64 = int_scores.iter().map(|c| *c.iter().max().unwrap()).sum();
+        let mut dist = vec![0.; (int_max - int_min) as usize + 1];
+        let mut new_dist = dist.clone();
+        dist[0] = 1.;
+        let mut cur_max = 0;
+        for col in &int_scores {
+            let col_min = *col.iter().min().unwrap();
+            let col_max = *col.iter().max().unwrap();
+            for v in &mut new_dist {
+                *v = 0.;
+            }
+            for (i, &p) in dist[..cur_max + 1].iter().enumerate() {
+                if p > 0. {
+                    for &s in col {
+                        new_dist[i + (s - col_min) as usize] += p * BACKGROUND;
+                    }
+                }
+            }
+            cur_max += (col_max - col_min) as usize;
+            ::std::mem::swap(&mut dist, &mut new_dist);
+        }
+        let mut tail = dist;
+        for i in (0..tail.len() - 1).rev() {
+            tail[i] += tail[i + 1];
+        }
+
+        Pwm {
+            name: name.to_string(),
+            scores: scores,
+            int_scores: int_scores,
+            int_min: int_min,
+            tail: Arc::new(tail),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn max_score(&self) -> f64 {
+        self.scores
+            .iter()
+            .map(|c| c.iter().cloned().fold(::std::f64::MIN, f64::max))
+            .sum()
+    }
+
+    pub fn min_score(&self) -> f64 {
+        self.scores
+            .iter()
+            .map(|c| c.iter().cloned().fold(::std::f64::MAX, f64::min))
+            .sum()
+    }
+
+    /// Returns the absolute minimum score
+    pub fn threshold(&self, t: PwmThreshold) -> f64 {
+        match t {
+            PwmThreshold::Abs(s) => s,
+            PwmThreshold::Rel(r) => {
+                let min = self.min_score();
+                min + r * (self.max_score() - min)
+            }
+        }
+    }
+
+    /// Returns the consensus sequence
+    pub fn consensus(&self) -> String {
+        self.scores
+            .iter()
+            .map(|col| {
+                let i = (0..4)
+                    .max_by(|&a, &b| col[a].partial_cmp(&col[b]).unwrap())
+                    .unwrap();
+                b"ACGT"[i] as char
+            })
+            .collect()
+    }
+
+    /// Returns the matrix for the reverse strand
+    pub fn revcomp(&self) -> Pwm {
+        let rev = |v: &[f64; 4]| [v[3], v[2], v[1], v[0]];
+        let rev_int = |v: &[i64; 4]| [v[3], v[2], v[1], v[0]];
+        Pwm {
+            name: self.name.clone(),
+            scores: self.scores.iter().rev().map(rev).collect(),
+            int_scores: self.int_scores.iter().rev().map(rev_int).collect(),
+            int_min: self.int_min,
+            tail: self.tail.clone(),
+        }
+    }
+
+    /// Scores a window of the text, returns (score, p-value). Characters other
+    /// than A, C, G, T (U) have a score of 0.
+    #[inline]
+    fn score(&self, window: &[u8]) -> (f64, f64) {
+        let mut score = 0.;
+        let mut int_score = 0;
+        for ((col, int_col), &c) in self.scores.iter().zip(&self.int_scores).zip(window) {
+            if let Some(i) = base_index(c) {
+                score += col[i];
+                int_score += int_col[i];
+            }
+        }
+        let i = min(
+            int_score.saturating_sub(self.int_min).max(0) as usize,
+            self.tail.len() - 1,
+        );
+        (score, self.tail[i])
+    }
+
+    /// Compares a window of the text with the consensus sequence: bases
+    /// with the highest score in a column are a `Match`, all others a `Subst`
+    fn consensus_ops(&self, window: &[u8], ops: &mut Vec<AlignmentOperation>) {
+        ops.clear();
+        for (col, &c) in self.scores.iter().zip(window) {
+            let max = col.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let op = match base_index(c) {
+                Some(i) if col[i] == max => AlignmentOperation::Match,
+                _ => AlignmentOperation::Subst,
+            };
+            ops.push(op);
+        }
+    }
+}
+
+#[inline]
+fn base_index(c: u8) -> Option<usize> {
+    match c {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' | b'U' | b'u' => Some(3),
+        _ => None,
+    }
+}
+
+/// Reads all matrices from a file in the JASPAR, MEME or TRANSFAC format
+/// (recognized automatically)
+pub fn read_pwm_file<P: AsRef<Path>>(path: P) -> CliResult<Vec<Pwm>> {
+    let path = path.as_ref();
+    let f = File::open(path)
+        .map_err(|e| format!("Could not open matrix file '{}': {}", path.display(), e))?;
+    let lines = BufReader::new(f).lines().collect::<Result<Vec<_>, _>>()?;
+    let first = lines
+        .iter()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty())
+        .unwrap_or("");
+    let pwms = if first.starts_with('>') {
+        parse_jaspar(&lines)?
+    } else if lines.iter().any(|l| l.starts_with("MOTIF")) {
+        parse_meme(&lines)?
+    } else if lines
+        .iter()
+        .any(|l| l.starts_with("P0") || l.starts_with("PO"))
+    {
+        parse_transfac(&lines)?
+    } else {
+        return fail!(format!(
+            "Unknown matrix format in '{}'. Only JASPAR, MEME and TRANSFAC are recognized.",
+            path.display()
+        ));
+    };
+    if pwms.is_empty() {
+        return fail!(format!("No matrix found in '{}'.", path.display()));
+    }
+    Ok(pwms)
+}
+
+fn parse_num(s: &str) -> CliResult<f64> {
+    s.parse()
+        .map_err(|_| format!("Invalid number in matrix: '{}'", s).into())
+}
+
+/// JASPAR format: '>ID name' followed by four rows (A, C, G, T), optionally
+/// starting with the base and with the counts in brackets
+fn parse_jaspar(lines: &[String]) -> CliResult<Vec<Pwm>> {
+    let mut out = vec![];
+    let mut name: Option<&str> = None;
+    let mut rows: Vec<Vec<f64>> = vec![];
+    let mut finish = |name: Option<&str>, rows: &mut Vec<Vec<f64>>| -> CliResult<()> {
+        if let Some(name) = name {
+            if rows.len() != 4 || rows.iter().any(|r| r.len() != rows[0].len()) {
+                return fail!(format!("Invalid JASPAR matrix: '{}'", name));
+            }
+            let counts: Vec<_> = (0..rows[0].len())
+                .map(|i| [rows[0][i], rows[1][i], rows[2][i], rows[3][i]])
+                .collect();
+            out.push(Pwm::from_counts(name, &counts)?);
+        }
+        rows.clear();
+        Ok(())
+    };
+    for line in lines {
+        let line = line.trim();
+        if line.starts_with('>') {
+            finish(name, &mut rows)?;
+            name = Some(line[1..].split_whitespace().next().unwrap_or(""));
+        } else if !line.is_empty() {
+            let row = line
+                .trim_start_matches(|c| "ACGTacgt".contains(c))
+                .split(|c: char| c.is_whitespace() || c == '[' || c == ']')
+                .filter(|s| !s.is_empty())
+                .map(parse_num)
+                .collect::<CliResult<Vec<_>>>()?;
+            rows.push(row);
+        }
+    }
+    finish(name, &mut rows)?;
+    Ok(out)
+}
+
+/// MEME format: 'MOTIF id [alt. name]' followed by a 'letter-probability matrix'
+fn parse_meme(lines: &[String]) -> CliResult<Vec<Pwm>> {
+    let mut out = vec![];
+    let mut lines = lines.iter().map(|l| l.trim());
+    let mut name = None;
+    while let Some(line) = lines.next() {
+        if line.starts_with("MOTIF") {
+            name = line.split_whitespace().nth(1).map(|n| n.to_string());
+        } else if line.starts_with("letter-probability matrix") {
+            let name = name
+                .take()
+                .ok_or("MEME format: 'letter-probability matrix' without preceding MOTIF line")?;
+            // key= value pairs
+            let attrs: Vec<_> = line
+                .splitn(2, ':')
+                .nth(1)
+                .unwrap_or("")
+                .replace("= ", "=")
+                .split_whitespace()
+                .filter_map(|kv| {
+                    let mut kv = kv.splitn(2, '=');
+                    Some((kv.next()?.to_string(), kv.next()?.to_string()))
+                })
+                .collect();
+            let get = |key: &str| attrs.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| v);
+            let w: usize = match get("w").and_then(|w| w.parse().ok()) {
+                Some(w) => w,
+                None => return fail!(format!("MEME format: motif width missing for '{}'", name)),
+            };
+            if get("alength").map(|a| a.as_str() != "4") == Some(true) {
+                return fail!(format!("MEME format: only DNA motifs are supported ('{}')", name));
+            }
+            let nsites = get("nsites").and_then(|n| n.parse().ok()).unwrap_or(DEFAULT_NSITES);
+            let mut counts = vec![];
+            while counts.len() < w {
+                let row = match lines.next() {
+                    Some(l) => l,
+                    None => return fail!(format!("MEME format: matrix '{}' is incomplete", name)),
+                };
+                if row.is_empty() {
+                    continue;
+                }
+                let v = row
+                    .split_whitespace()
+                    .map(parse_num)
+                    .collect::<CliResult<Vec<_>>>()?;
+                if v.len() != 4 {
+                    return fail!(format!("MEME format: invalid matrix row in '{}'", name));
+                }
+                counts.push([v[0] * nsites, v[1] * nsites, v[2] * nsites, v[3] * nsites]);
+            }
+            out.push(Pwm::from_counts(&name, &counts)?);
+        }
+    }
+    Ok(out)
+}
+
+/// TRANSFAC format: ID line, matrix starting with 'P0' (or 'PO'), entries
+/// separated by '//'
+fn parse_transfac(lines: &[String]) -> CliResult<Vec<Pwm>> {
+    let mut out = vec![];
+    let mut name = None;
+    let mut counts = vec![];
+    let mut in_matrix = false;
+    for line in lines {
+        let mut fields = line.split_whitespace();
+        let key = fields.next().unwrap_or("");
+        if key == "//" {
+            if !counts.is_empty() {
+                let n = name.take().unwrap_or_else(|| format!("matrix{}", out.len() + 1));
+                out.push(Pwm::from_counts(&n, &counts)?);
+                counts.clear();
+            }
+            in_matrix = false;
+        } else if key == "P0" || key == "PO" {
+            let bases: Vec<_> = fields.collect();
+            if bases != ["A", "C", "G", "T"] {
+                return fail!("TRANSFAC format: only matrices with the columns A, C, G, T are supported");
+            }
+            in_matrix = true;
+        } else if in_matrix && key.chars().all(|c| c.is_ascii_digit()) && !key.is_empty() {
+            let v = fields
+                .take(4)
+                .map(parse_num)
+                .collect::<CliResult<Vec<_>>>()?;
+            if v.len() != 4 {
+                return fail!("TRANSFAC format: invalid matrix row");
+            }
+            counts.push([v[0], v[1], v[2], v[3]]);
+        } else {
+            in_matrix = false;
+            if key == "ID" || (key == "AC" && name.is_none()) {
+                name = fields.next().map(|n| n.to_string());
+            }
+        }
+    }
+    if !counts.is_empty() {
+        let n = name.unwrap_or_else(|| format!("matrix{}", out.len() + 1));
+        out.push(Pwm::from_counts(&n, &counts)?);
+    }
+    Ok(out)
+}
+
+/// Scores every window of the text with a position weight matrix and reports
+/// all windows with a score above the threshold
+pub struct PwmMatcher {
+    pwm: Pwm,
+    min_score: f64,
+    max_score: f64,
+    sorted: bool,
+    hits: Vec<Match>,
+    ops: Vec<AlignmentOperation>,
+}
+
+impl PwmMatcher {
+    pub fn new(pwm: Pwm, threshold: PwmThreshold, sorted: bool) -> PwmMatcher {
+        PwmMatcher {
+            min_score: pwm.threshold(threshold),
+            max_score: pwm.max_score(),
+            pwm: pwm,
+            sorted: sorted,
+            hits: vec![],
+            ops: vec![],
+        }
+    }
+}
+
+impl Matcher for PwmMatcher {
+    fn iter_matches(&mut self, text: &[u8], func: &mut FnMut(&Hit) -> bool) {
+        let w = self.pwm.len();
+        self.hits.clear();
+        if text.len() >= w {
+            for start in 0..text.len() - w + 1 {
+                let window = &text[start..start + w];
+                let (score, pvalue) = self.pwm.score(window);
+                if score >= self.min_score {
+                    let dist = (self.max_score - score).round().max(0.) as u16;
+                    // the alignment to the consensus sequence
+                    self.pwm.consensus_ops(window, &mut self.ops);
+                    let mut m = Match::from_ops(start, start + w, dist, &self.ops);
+                    m.score = Some(score);
+                    m.pvalue = Some(pvalue);
+                    self.hits.push(m);
+                }
+            }
+        }
+        if self.sorted {
+            // best score first
+            self.hits
+                .sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        }
+        for m in self.hits.drain(..) {
+            if !func(&SimpleHit(m)) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn formats() {
+        let jaspar = ">MA0004.1 Arnt\nA  [ 4 19  0  0  0  0 ]\nC  [16  0 20  0  0  0 ]\n\
+                      G  [ 0  1  0 20  0 20 ]\nT  [ 0  0  0  0 20  0 ]\n";
+        let meme = "MEME version 4\n\nALPHABET= ACGT\n\nMOTIF MA0004.1 Arnt\n\
+                    letter-probability matrix: alength= 4 w= 6 nsites= 20 E= 0\n\
+                    0.2 0.8 0 0\n0.95 0 0.05 0\n0 1 0 0\n0 0 1 0\n0 0 0 1\n0 0 1 0\n";
+        let transfac = "AC  MA0004.1\nXX\nID  Arnt\nXX\nP0      A      C      G      T\n\
+                        01      4     16      0      0      C\n02     19      0      1      0      A\n\
+                        03      0     20      0      0      C\n04      0      0     20      0      G\n\
+                        05      0      0      0     20      T\n06      0      0     20      0      G\n\
+                        XX\n//\n";
+        let j = parse_jaspar(&lines(jaspar)).unwrap();
+        let m = parse_meme(&lines(meme)).unwrap();
+        let t = parse_transfac(&lines(transfac)).unwrap();
+        assert_eq!(&j[0].name, "MA0004.1");
+        assert_eq!(&m[0].name, "MA0004.1");
+        assert_eq!(&t[0].name, "Arnt");
+        for pwm in &[&j[0], &m[0], &t[0]] {
+            assert_eq!(&pwm.consensus(), "CACGTG");
+            assert_eq!(pwm.len(), 6);
+        }
+        for (a, b) in j[0].scores.iter().zip(&t[0].scores) {
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn pvalue() {
+        // a single column: p = 0.25 for the best base
+        let pwm = Pwm::from_counts("p", &[[10., 0., 0., 0.]]).unwrap();
+        assert_eq!(pwm.score(b"A").1, 0.25);
+        assert_eq!(pwm.score(b"C").1, 1.);
+        let pwm = Pwm::from_counts("p", &[[10., 0., 0., 0.], [0., 10., 5., 0.]]).unwrap();
+        assert!((pwm.score(b"AC").1 - 1. / 16.).abs() < 1e-12);
+        assert!((pwm.score(b"AG").1 - 2. / 16.).abs() < 1e-12);
+        let rc = pwm.revcomp();
+        assert_eq!(&rc.consensus(), "GT");
+        assert_eq!(rc.score(b"GT"), pwm.score(b"AC"));
+    }
+}
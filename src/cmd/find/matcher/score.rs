@@ -0,0 +1,384 @@
+use std::cmp::{max, min};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+use bio::scores::{blosum62, pam250};
+
+use super::*;
+use error::CliResult;
+
+// residues contained in the built-in matrices
+static RESIDUES: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ*";
+
+// "minus infinity" that does not overflow if gap penalties are added
+const NEG_INF: i32 = ::std::i32::MIN / 2;
+
+/// Substitution matrix with scores for all byte pairs. Lowercase letters are
+/// scored like uppercase letters, characters not present in the matrix
+/// obtain the lowest score of the matrix.
+pub struct ScoreMatrix {
+    scores: Vec<i32>,
+}
+
+impl ScoreMatrix {
+    /// Returns a built-in matrix (BLOSUM62 or PAM250) or reads a matrix file
+    /// in the NCBI format.
+    pub fn from_name_or_path(s: &str) -> CliResult<ScoreMatrix> {
+        match &*s.to_ascii_lowercase() {
+            "blosum62" => Ok(Self::from_fn(blosum62)),
+            "pam250" => Ok(Self::from_fn(pam250)),
+            _ => Self::from_file(s),
+        }
+    }
+
+    fn from_fn(f: fn(u8, u8) -> i32) -> ScoreMatrix {
+        let entries = RESIDUES
+            .iter()
+            .flat_map(|&a| RESIDUES.iter().map(move |&b| (a, b, f(a, b))));
+        Self::from_entries(entries)
+    }
+
+    /// Reads a matrix in the NCBI format: the first line (after comments starting
+    /// with '#') contains the residues of the columns, followed by one row per
+    /// residue, starting with the residue itself.
+    fn from_file(path: &str) -> CliResult<ScoreMatrix> {
+        let f = File::open(path).map_err(|e| {
+            format!(
+                "Unknown substitution matrix: '{}'. Valid built-in matrices are \
+                 'blosum62' and 'pam250', otherwise a matrix file is expected ({})",
+                path, e
+            )
+        })?;
+        let mut columns: Option<Vec<u8>> = None;
+        let mut entries = vec![];
+        for line in BufReader::new(f).lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let cols = match columns {
+                Some(ref c) => c,
+                None => {
+                    columns = Some(fields.map(|f| parse_residue(f, path)).collect::<CliResult<_>>()?);
+                    continue;
+                }
+            };
+            let row = parse_residue(fields.next().unwrap(), path)?;
+            let mut n = 0;
+            for (&col, score) in cols.iter().zip(&mut fields) {
+                let score = score.parse().map_err(|_| {
+                    format!("Invalid score in substitution matrix '{}': '{}'", path, score)
+                })?;
+                entries.push((row, col, score));
+                n += 1;
+            }
+            if n != cols.len() || fields.next().is_some() {
+                return fail!(format!(
+                    "The number of scores in the row of '{}' does not match the number of \
+                     columns in substitution matrix '{}'",
+                    row as char, path
+                ));
+            }
+        }
+        if entries.is_empty() {
+            return fail!(format!("Substitution matrix '{}' is empty", path));
+        }
+        Ok(Self::from_entries(entries))
+    }
+
+    fn from_entries<I: IntoIterator<Item = (u8, u8, i32)>>(entries: I) -> ScoreMatrix {
+        let entries: Vec<_> = entries.into_iter().collect();
+        let min_score = entries.iter().map(|e| e.2).min().unwrap_or(0);
+        let mut scores = vec![min_score; 256 * 256];
+        for (a, b, s) in entries {
+            for &a in &[a.to_ascii_uppercase(), a.to_ascii_lowercase()] {
+                for &b in &[b.to_ascii_uppercase(), b.to_ascii_lowercase()] {
+                    scores[a as usize * 256 + b as usize] = s;
+                }
+            }
+        }
+        ScoreMatrix { scores: scores }
+    }
+
+    #[inline]
+    pub fn score(&self, a: u8, b: u8) -> i32 {
+        self.scores[a as usize * 256 + b as usize]
+    }
+
+    /// Highest possible score of a residue
+    fn max_score(&self, a: u8) -> i32 {
+        self.scores[a as usize * 256..(a as usize + 1) * 256]
+            .iter()
+            .cloned()
+            .max()
+            .unwrap()
+    }
+}
+
+fn parse_residue(s: &str, path: &str) -> CliResult<u8> {
+    if s.len() != 1 {
+        return fail!(format!(
+            "Invalid residue in substitution matrix '{}': '{}'",
+            path, s
+        ));
+    }
+    Ok(s.as_bytes()[0])
+}
+
+/// Options for scoring matrix based matching
+#[derive(Clone)]
+pub struct ScoreOpts {
+    pub matrix: Arc<ScoreMatrix>,
+    pub min_score: i32,
+    /// gap opening and extension scores (<= 0 and < 0). A gap of length n has the
+    /// score gap_open + n * gap_ext.
+    pub gap_open: i32,
+    pub gap_ext: i32,
+}
+
+/// Approximate matching based on the alignment score of the pattern with the text
+/// (semi-global alignment with affine gap penalties). All hits with a score of
+/// at least `min_score` are reported. The distance of a hit is the difference
+/// between the highest possible score of the pattern and the actual score.
+pub struct ScoreMatcher {
+    pattern: Vec<u8>,
+    opts: ScoreOpts,
+    max_score: i32,
+    // maximum length of a hit in the text
+    max_len: usize,
+    needs_start: bool,
+    needs_path: bool,
+    sort_vec: Option<Vec<Match>>,
+    // DP columns
+    h: Vec<i32>,
+    e: Vec<i32>,
+    // (end, score)
+    ends: Vec<(usize, i32)>,
+    // matrices for traceback
+    h_mat: Vec<i32>,
+    e_mat: Vec<i32>,
+    f_mat: Vec<i32>,
+    ops: Vec<AlignmentOperation>,
+}
+
+impl ScoreMatcher {
+    pub fn new(
+        pattern: &[u8],
+        opts: ScoreOpts,
+        needs_start: bool,
+        needs_path: bool,
+        sorted: bool,
+    ) -> ScoreMatcher {
+        debug_assert!(opts.gap_open <= 0 && opts.gap_ext < 0);
+        let max_score = pattern.iter().map(|&a| opts.matrix.max_score(a)).sum();
+        // max. number of text characters missing in the pattern
+        let max_del = max(0, max_score - opts.min_score + opts.gap_open) / -opts.gap_ext;
+        ScoreMatcher {
+            pattern: pattern.to_owned(),
+            max_score: max_score,
+            max_len: pattern.len() + max_del as usize,
+            opts: opts,
+            needs_start: needs_start || needs_path,
+            needs_path: needs_path,
+            sort_vec: if sorted { Some(vec![]) } else { None },
+            h: vec![],
+            e: vec![],
+            ends: vec![],
+            h_mat: vec![],
+            e_mat: vec![],
+            f_mat: vec![],
+            ops: vec![],
+        }
+    }
+
+    // score of a gap of length n in the first column
+    #[inline]
+    fn init_score(&self, n: usize) -> i32 {
+        if n == 0 {
+            0
+        } else {
+            self.opts.gap_open + n as i32 * self.opts.gap_ext
+        }
+    }
+
+    // calculates the next column of H and E (and F if given) for the text character `c`
+    #[inline]
+    fn step(&self, h: &mut [i32], e: &mut [i32], mut f: Option<&mut [i32]>, c: u8) {
+        let open = self.opts.gap_open + self.opts.gap_ext;
+        let ext = self.opts.gap_ext;
+        // H[i-1][j-1]
+        let mut diag = h[0];
+        let mut f_cur = NEG_INF;
+        h[0] = 0;
+        for i in 1..h.len() {
+            let e_new = max(h[i] + open, e[i] + ext);
+            f_cur = max(h[i - 1] + open, f_cur + ext);
+            let h_new = max(diag + self.opts.matrix.score(self.pattern[i - 1], c), max(e_new, f_cur));
+            diag = h[i];
+            h[i] = h_new;
+            e[i] = e_new;
+            if let Some(f) = f.as_mut() {
+                f[i] = f_cur;
+            }
+        }
+    }
+
+    fn find_ends(&mut self, text: &[u8]) {
+        let m = self.pattern.len();
+        let mut h = ::std::mem::replace(&mut self.h, vec![]);
+        let mut e = ::std::mem::replace(&mut self.e, vec![]);
+        h.clear();
+        h.extend((0..m + 1).map(|i| self.init_score(i)));
+        e.clear();
+        e.resize(m + 1, NEG_INF);
+        self.ends.clear();
+        for (j, &c) in text.iter().enumerate() {
+            self.step(&mut h, &mut e, None, c);
+            if h[m] >= self.opts.min_score {
+                self.ends.push((j + 1, h[m]));
+            }
+        }
+        self.h = h;
+        self.e = e;
+    }
+
+    /// Aligns the pattern to the text ending at `end` and returns the start
+    /// position. The alignment path is written to `self.ops`.
+    fn traceback(&mut self, text: &[u8], end: usize) -> usize {
+        let rows = self.pattern.len() + 1;
+        let start = end.saturating_sub(self.max_len);
+        let window = &text[start..end];
+        let cols = window.len() + 1;
+
+        // fill the matrices (column-wise)
+        let mut h = ::std::mem::replace(&mut self.h_mat, vec![]);
+        let mut e = ::std::mem::replace(&mut self.e_mat, vec![]);
+        let mut f = ::std::mem::replace(&mut self.f_mat, vec![]);
+        for mat in &mut [&mut h, &mut e, &mut f] {
+            mat.clear();
+            mat.resize(rows * cols, NEG_INF);
+        }
+        for i in 0..rows {
+            h[i] = self.init_score(i);
+            if i > 0 {
+                f[i] = h[i];
+            }
+        }
+        for (j, &c) in window.iter().enumerate() {
+            let (h_prev, h_col) = h[j * rows..(j + 2) * rows].split_at_mut(rows);
+            h_col.copy_from_slice(h_prev);
+            let (e_prev, e_col) = e[j * rows..(j + 2) * rows].split_at_mut(rows);
+            e_col.copy_from_slice(e_prev);
+            self.step(h_col, e_col, Some(&mut f[(j + 1) * rows..(j + 2) * rows]), c);
+        }
+
+        // traceback
+        #[derive(PartialEq)]
+        enum State {
+            H,
+            E,
+            F,
+        }
+        let open = self.opts.gap_open + self.opts.gap_ext;
+        let ext = self.opts.gap_ext;
+        let idx = |i: usize, j: usize| j * rows + i;
+        let (mut i, mut j) = (rows - 1, cols - 1);
+        let mut state = State::H;
+        self.ops.clear();
+        while i > 0 {
+            match state {
+                State::H => {
+                    let cur = h[idx(i, j)];
+                    if j > 0 {
+                        let (p, t) = (self.pattern[i - 1], window[j - 1]);
+                        if h[idx(i - 1, j - 1)] + self.opts.matrix.score(p, t) == cur {
+                            self.ops.push(if p.eq_ignore_ascii_case(&t) {
+                                AlignmentOperation::Match
+                            } else {
+                                AlignmentOperation::Subst
+                            });
+                            i -= 1;
+                            j -= 1;
+                            continue;
+                        }
+                    }
+                    state = if j > 0 && e[idx(i, j)] == cur { State::E } else { State::F };
+                }
+                State::E => {
+                    // text character missing in the pattern
+                    self.ops.push(AlignmentOperation::Del);
+                    if h[idx(i, j - 1)] + open == e[idx(i, j)] {
+                        state = State::H;
+                    }
+                    j -= 1;
+                }
+                State::F => {
+                    // pattern character missing in the text
+                    self.ops.push(AlignmentOperation::Ins);
+                    if h[idx(i - 1, j)] + open == f[idx(i, j)] {
+                        state = State::H;
+                    } else {
+                        debug_assert!(f[idx(i - 1, j)] + ext == f[idx(i, j)]);
+                    }
+                    i -= 1;
+                }
+            }
+        }
+        self.ops.reverse();
+        self.h_mat = h;
+        self.e_mat = e;
+        self.f_mat = f;
+        start + j
+    }
+
+    fn make_match(&self, start: usize, end: usize, score: i32) -> Match {
+        let dist = min(max(self.max_score - score, 0), ::std::u16::MAX as i32) as u16;
+        let mut m = if self.needs_path {
+            Match::from_ops(start, end, dist, &self.ops)
+        } else {
+            Match::new(start, end, dist, 0, 0, 0)
+        };
+        m.score = Some(f64::from(score));
+        m
+    }
+}
+
+impl Matcher for ScoreMatcher {
+    fn iter_matches(&mut self, text: &[u8], func: &mut FnMut(&Hit) -> bool) {
+        self.find_ends(text);
+        let ends = ::std::mem::replace(&mut self.ends, vec![]);
+
+        let mut hits: Vec<Match> = vec![];
+        for &(end, score) in &ends {
+            if self.needs_start {
+                // keep the best hit for each start position
+                let start = self.traceback(text, end);
+                if let Some(last) = hits.last_mut() {
+                    if last.start == start {
+                        if Some(f64::from(score)) > last.score {
+                            *last = self.make_match(start, end, score);
+                        }
+                        continue;
+                    }
+                }
+                hits.push(self.make_match(start, end, score));
+            } else {
+                hits.push(self.make_match(0, end, score));
+            }
+        }
+        self.ends = ends;
+
+        opt_sorted(
+            hits.into_iter(),
+            self.sort_vec.as_mut(),
+            |m| m.dist,
+            |m| {
+                let h = SimpleHit(m);
+                func(&h)
+            },
+        );
+    }
+}
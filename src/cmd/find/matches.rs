@@ -4,24 +4,32 @@ use std::slice::Iter;
 use itertools::{Itertools, Step};
 use vec_map::VecMap;
 
-use super::matcher::{Match, Matcher};
+use super::matcher::{Match, Matcher, MultiFilter};
 use super::*;
 
 // Sent around between threads and holds the matches found by `Matcher`
 #[derive(Debug)]
 pub struct Matches {
     matcher_names: Vec<String>,
+    // (pattern, reverse complemented pattern (if searching both strands))
+    patterns: Vec<(Vec<u8>, Vec<u8>)>,
+    // matches were converted to the reverse complemented text
+    reoriented: bool,
     pos: SearchPositions,
     bounds: Option<(isize, isize)>,
     max_shift: Option<Shift>,
     multiple_matchers: bool,
-    calc_bounds: Option<(usize, usize)>,
-    // offset introduced by narrowing down search range (calc_bounds)
-    offset: usize,
+    // reverse complemented patterns are searched as well
+    // (matchers are expected in the order fwd, rev, fwd, rev...)
+    both_strands: bool,
     // vector of matches for each pattern
     // Vec<Option<Match>> is a flat 2D matrix with dimension has_matches x num_match_groups
     matches: Vec<Vec<Option<Match>>>,
     has_matches: bool,
+    // strand of the reported matches for each pattern (true = reverse)
+    reverse: Vec<bool>,
+    // matches on the other strand (with --both-strands)
+    other_strand: Vec<Vec<Option<Match>>>,
     // dist, index, has_matches
     dist_order: Vec<(u16, usize, bool)>,
     // optional search range
@@ -30,75 +38,105 @@ pub struct Matches {
 impl Matches {
     pub fn new(
         matcher_names: &[String],
+        patterns: Vec<(Vec<u8>, Vec<u8>)>,
         pos: SearchPositions,
         bounds: Option<(isize, isize)>,
         max_shift: Option<Shift>,
+        both_strands: bool,
     ) -> Matches {
         let n = matcher_names.len();
         Matches {
             matcher_names: matcher_names.to_owned(),
+            patterns: patterns,
+            reoriented: false,
             pos: pos,
             max_shift: max_shift,
             multiple_matchers: n > 1,
+            both_strands: both_strands,
             dist_order: vec![(0, 0, false); n],
             matches: vec![vec![]; n],
             has_matches: false,
+            reverse: vec![false; n],
+            other_strand: vec![vec![]; n],
             bounds: bounds,
-            calc_bounds: None,
-            offset: 0,
         }
     }
 
-    pub fn find<M: Matcher>(&mut self, text: &[u8], matchers: &mut [M]) {
-        let mut text = text;
-
-        if let Some((start, end)) = self.bounds {
+    /// Searches the text with all matchers. If a `filter` is supplied, only matchers of
+    /// patterns that are candidates according to the filter are run.
+    pub fn find<M: Matcher>(
+        &mut self,
+        text: &[u8],
+        matchers: &mut [M],
+        filter: Option<&mut MultiFilter>,
+    ) {
+        let len = text.len();
+        let (start, end) = if let Some((start, end)) = self.bounds {
             // restrict search range
-            let (s, e) = Range::from_rng1(start, end, text.len()).get(false);
-            self.calc_bounds = Some((s, e));
-            self.offset = s;
-            text = &text[self.offset..e];
-        }
-
-        if !self.multiple_matchers {
-            self.has_matches = self.pos.collect_matches(
-                text,
-                &mut matchers[0],
-                &mut self.matches[0],
-                self.max_shift.as_ref(),
-                self.offset,
-            );
+            Range::from_rng1(start, end, len).get(false)
         } else {
-            for (
-                i,
-                (
-                    (ref mut matcher, ref mut matches),
-                    &mut (ref mut best_dist, ref mut idx, ref mut has_matches),
-                ),
-            ) in matchers
-                .into_iter()
-                .zip(self.matches.iter_mut())
-                .zip(self.dist_order.iter_mut())
-                .enumerate()
-            {
-                *has_matches = self.pos.collect_matches(
-                    text,
-                    matcher,
-                    matches,
+            (0, len)
+        };
+        let step = if self.both_strands { 2 } else { 1 };
+        self.reoriented = false;
+        // The whole text is searched by the filter, which may result in more
+        // candidates than necessary if the search range is restricted.
+        let candidates = filter.map(|f| f.candidates(text));
+        let is_candidate = |j: usize| candidates.map(|c| c[j]).unwrap_or(true);
+
+        for (i, matchers) in matchers.chunks_mut(step).enumerate() {
+            let mut has_matches = if is_candidate(i * step) {
+                self.pos.collect_matches(
+                    &text[start..end],
+                    &mut matchers[0],
+                    &mut self.matches[i],
                     self.max_shift.as_ref(),
-                    self.offset,
-                );
-                // take distance of first match (assumed to be sorted if necessary)
-                *best_dist = matches
-                    .get(0)
-                    .and_then(|m| m.as_ref().map(|m| m.dist))
-                    .unwrap_or(::std::u16::MAX);
-                *idx = i;
+                    start,
+                )
+            } else {
+                self.matches[i].clear();
+                false
+            };
+            self.reverse[i] = false;
+
+            if self.both_strands {
+                // The reverse complemented pattern is searched in the forward sequence,
+                // but the search range and max. shift are relative to the reverse strand.
+                let (rev_start, rev_end) = (len - end, len - start);
+                let rev_shift = self.max_shift.as_ref().map(Shift::mirror);
+                let has_rev = if is_candidate(i * step + 1) {
+                    self.pos.collect_matches(
+                        &text[rev_start..rev_end],
+                        &mut matchers[1],
+                        &mut self.other_strand[i],
+                        rev_shift.as_ref(),
+                        rev_start,
+                    )
+                } else {
+                    self.other_strand[i].clear();
+                    false
+                };
+                // use the strand with the best hit (forward if equal)
+                if has_rev
+                    && (!has_matches
+                        || best_dist(&self.other_strand[i]) < best_dist(&self.matches[i]))
+                {
+                    ::std::mem::swap(&mut self.matches[i], &mut self.other_strand[i]);
+                    self.reverse[i] = true;
+                    has_matches = true;
+                }
+            }
+
+            if !self.multiple_matchers {
+                self.has_matches = has_matches;
+                return;
             }
-            // sort -> best matches first
-            self.dist_order.sort_by_key(|&(dist, _, _)| dist);
-            self.has_matches = self.dist_order[0].2;
+            // take distance of first match (assumed to be sorted if necessary)
+            self.dist_order[i] = (best_dist(&self.matches[i]), i, has_matches);
         }
+        // sort -> best matches first
+        self.dist_order.sort_by_key(|&(dist, _, _)| dist);
+        self.has_matches = self.dist_order[0].2;
     }
 
     pub fn has_matches(&self) -> bool {
@@ -121,6 +159,70 @@ impl Matches {
         self.pos.get_match(pos, group, self._matches(pattern_rank))
     }
 
+    /// Returns true if the matches of the given pattern were found on the reverse strand,
+    /// or None if there was no match
+    pub fn is_reverse(&self, pattern_rank: usize) -> Option<bool> {
+        if self.multiple_matchers {
+            self.dist_order
+                .get(pattern_rank)
+                .and_then(|&(_, i, has_matches)| if has_matches { Some(self.reverse[i]) } else { None })
+        } else if self.has_matches && pattern_rank == 0 {
+            Some(self.reverse[0])
+        } else {
+            None
+        }
+    }
+
+    /// Converts all match coordinates to coordinates on the reverse complemented
+    /// sequence (of length `len`)
+    pub fn reverse_coords(&mut self, len: usize) {
+        for matches in self.matches.iter_mut().chain(&mut self.other_strand) {
+            for m in matches.iter_mut().filter_map(|m| m.as_mut()) {
+                let start = len - m.end;
+                m.end = len - m.start;
+                m.start = start;
+                m.reverse_ops();
+            }
+        }
+        self.reoriented = !self.reoriented;
+    }
+
+    /// Returns the pattern (or its reverse complement) in the orientation in which
+    /// it matched the text
+    pub fn pattern(&self, pattern_rank: usize) -> Option<&[u8]> {
+        let i = if self.multiple_matchers {
+            self.dist_order.get(pattern_rank)?.1
+        } else {
+            0
+        };
+        let (ref fwd, ref rev) = self.patterns[i];
+        if self.reverse[i] != self.reoriented {
+            Some(rev)
+        } else {
+            Some(fwd)
+        }
+    }
+
+    /// Calls `func(pattern_name, match, is_reverse)` for all matches (group 0)
+    /// of all patterns on both strands
+    pub fn for_each_hit<F>(&self, mut func: F) -> CliResult<()>
+    where
+        F: FnMut(&str, &Match, bool) -> CliResult<()>,
+    {
+        for (i, name) in self.matcher_names.iter().enumerate() {
+            let strands = [
+                (&self.matches[i], self.reverse[i]),
+                (&self.other_strand[i], !self.reverse[i]),
+            ];
+            for &(matches, reverse) in &strands {
+                for m in self.pos.matches_iter(0, matches).filter_map(|m| m) {
+                    func(name, m, reverse)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn pattern_name(&self, pattern_rank: usize) -> Option<&str> {
         if self.multiple_matchers {
             return self
@@ -276,7 +378,22 @@ pub enum Shift {
     End(usize),
 }
 
+fn best_dist(matches: &[Option<Match>]) -> u16 {
+    matches
+        .get(0)
+        .and_then(|m| m.as_ref().map(|m| m.dist))
+        .unwrap_or(::std::u16::MAX)
+}
+
 impl Shift {
+    /// returns the shift relative to the opposite end
+    pub fn mirror(&self) -> Shift {
+        match *self {
+            Shift::Start(n) => Shift::End(n),
+            Shift::End(n) => Shift::Start(n),
+        }
+    }
+
     pub fn in_range(&self, rng: (usize, usize), len: usize) -> bool {
         match *self {
             Shift::Start(n) => rng.0 <= n,
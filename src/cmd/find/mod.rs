@@ -1,13 +1,18 @@
+use std::cmp::min;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::io::Write;
 use std::str;
+use std::sync::Arc;
 
 use itertools::Itertools;
 
 use cfg;
 use error::CliResult;
 use io::output::writer::Writer;
-use io::{RecordEditor, SeqAttr};
+use io::output;
+use io::{Compression, Record, RecordEditor, SeqAttr, SeqQualRecord};
+use lib::iupac;
 use lib::rng::Range;
 use lib::seqtype::{guess_seqtype, SeqType};
 use lib::util::{parse_range, replace_iter};
@@ -16,31 +21,83 @@ use var::{varstring, VarHelp, VarProvider};
 
 use self::matcher::*;
 use self::matches::*;
+use self::motif::*;
 use self::vars::*;
 
 mod matcher;
 mod matches;
+mod motif;
 mod vars;
 
 static USAGE: &'static str = concat!(
     "
 Fast searching for one or more patterns in sequences or ids/descriptions,
-with optional multithreading.
+with optional multithreading. With -f/-e, all pattern sets (<pattern> and -p)
+need to match.
 
 Usage:
-  st find [options] [-a <attr>...][-l <list>...] <pattern> [<input>...]
+  st find [options] [-a <attr>...] [-l <list>...] [-p <p>...] <pattern> [<input>...]
   st find (-h | --help)
   st find --help-vars
 
 Search Options:
     <pattern>           Pattern string or 'file:<patterns.fasta>'
+    -p, --pattern <p>   Additional pattern set (pattern string or file), which
+                        is searched independently. Its variables are accessed
+                        with the prefix 'f2:', 'f3:', etc. The set may be followed
+                        by options: 'file:rev.fa;rng=-30..;dist=2'. Valid
+                        options are rng, dist, max-shift-l and max-shift-r.
+                        The distance defaults to -d/--dist, search ranges/shifts
+                        are not inherited.
     -r, --regex         Treat the pattern(s) as regular expressions.
+    --prosite           Treat the pattern(s) as PROSITE patterns, e.g.
+                        'C-x(2,4)-C-x(3)-[LIVMFYWC]'. Patterns with a fixed
+                        length can be searched with -d/--dist.
+    --iupac             Treat the pattern(s) as IUPAC codes with optional
+                        repeat counts, e.g. 'N{3}ACGT' or 'ACN{2,5}T'. Patterns
+                        with a fixed length can be searched with mismatches
+                        (-d/--dist) and on both strands.
+    --pwm               Treat the pattern(s) as files with position weight
+                        matrices (JASPAR, MEME or TRANSFAC format). Every
+                        window of the sequence is scored on both strands, the
+                        minimum log-odds score is set with --min-score.
     -d, --dist <dist>   Approximative string matching with maximum edit distance
                         of <dist> [default: 0]
+    --subst-cost <n>    Cost of a substitution in approximate matching
+                        [default: 1]
+    --ins-cost <n>      Cost of an insertion, i.e. a pattern character missing
+                        in the sequence [default: 1]
+    --del-cost <n>      Cost of a deletion, i.e. a sequence character missing
+                        in the pattern [default: 1]
+    --free-ends <n>     Allow up to <n> pattern characters to overhang the
+                        start or end of the sequence without penalty (e.g. for
+                        primers at the read ends). The overhang is reported
+                        as insertions. [default: 0]
+    --matrix <m>        Approximate matching based on the alignment score with
+                        a substitution matrix instead of the edit distance
+                        (e.g. for proteins). Built-in are 'blosum62' and
+                        'pam250', other matrices can be read from files
+                        in the NCBI format. Requires --min-score.
+    --min-score <s>     Minimum alignment score of a hit with --matrix, or
+                        minimum log-odds score with --pwm. With PWMs, the score
+                        can also be a percentage of the score range of the
+                        matrix (default with --pwm: 80%).
+    --gap-open <s>      Gap opening score with --matrix (<= 0) [default: -11]
+    --gap-ext <s>       Gap extension score with --matrix (< 0). A gap of length
+                        n has the score <gap-open> + n * <gap-ext> [default: -1]
     --in-order          Report hits in the order of their occurrence instead
                         of sorting by distance (with -d > 0)
+    --both-strands      Search the reverse complement of the pattern(s) as well
+                        (DNA/RNA only). The strand with the best hit is reported
+                        ({f:strand}). Coordinates always refer to the forward
+                        strand, but --rng and --max-shift-l/r are applied to the
+                        reverse strand when searching reverse complemented
+                        patterns.
+    --reorient          Reverse complement sequences whose best hit was found
+                        on the reverse strand (implies --both-strands). All
+                        coordinates then refer to the reoriented sequence.
     --seqtype <type>    Sequence type {dna/rna/protein/other}
-    -t, --threads <N>   Number of threads to use [default: 1]
+    -t, --threads <N>   Number of threads [default: 1]
     --ambig <yn>        Override choice of whether DNA ambiguity codes (IUPAC)
                         are recognized or not {yes/no}.
     --algo <algorithm>  Override decision of algorithm for testing
@@ -65,6 +122,17 @@ Actions:
                         The extension is autorecognized if possible, fallback
                         is the input format.
     --rep <with>        Replace by a composable string
+    --hits <file>       Write all hits of all patterns (on both strands if
+                        searching both) to a tab-delimited file with the
+                        columns id, pattern, start, end, strand, dist, subst,
+                        ins, del. With the extension .bed, the output is in the
+                        BED format (id, 0-based start, end, pattern, dist,
+                        strand).
+    --extract           Only keep the part of the sequence located between
+                        the first hit of the first and the last pattern set
+                        (e.g. the amplicon between two primers). Sequences with
+                        missing or overlapping hits are not modified (use -f
+                        to remove them).
 ",
     common_opts!()
 );
@@ -100,11 +168,16 @@ lazy_static! {
 
 use self::Algorithm::*;
 
+/// Minimum number of patterns in a set, from which on the patterns are
+/// pre-filtered using `MultiFilter`
+const MULTI_FILTER_MIN_PATTERNS: usize = 10;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum Algorithm {
     Exact,
     Regex,
     Myers,
+    PwmScan,
 }
 
 impl Algorithm {
@@ -121,18 +194,123 @@ impl Algorithm {
 struct MatchOpts {
     has_groups: bool,
     needs_alignment: bool,
+    needs_path: bool,
     sorted: bool,
     max_dist: u16,
+    costs: Costs,
+    scoring: Option<ScoreOpts>,
+    pwm_threshold: PwmThreshold,
     seqtype: SeqType,
 }
 
+/// A set of patterns, which is searched independently of other sets
+struct PatternSet {
+    patterns: Vec<(String, String)>,
+    range: Option<(isize, isize)>,
+    max_shift: Option<Shift>,
+    dist: u16,
+    regex: bool,
+    // parsed PROSITE / IUPAC motifs (if any)
+    motifs: Vec<Motif>,
+    // position weight matrices (if any)
+    pwms: Vec<Pwm>,
+}
+
+impl PatternSet {
+    /// Parses a pattern set in the form `pattern[;key=value;...]`.
+    /// Unspecified options are taken from `default`.
+    fn from_spec(spec: &str, default: &PatternSet, pwm: bool) -> CliResult<PatternSet> {
+        let mut parts = spec.split(';');
+        let (patterns, pwms) = read_patterns(parts.next().unwrap(), pwm)?;
+        let mut set = PatternSet {
+            patterns: patterns,
+            range: default.range,
+            max_shift: default.max_shift.clone(),
+            dist: default.dist,
+            regex: default.regex,
+            motifs: vec![],
+            pwms: pwms,
+        };
+        for opt in parts {
+            let kv: Vec<_> = opt.splitn(2, '=').collect();
+            if kv.len() != 2 {
+                return fail!(format!("Invalid pattern set option: '{}'. Expecting key=value.", opt));
+            }
+            let (key, value) = (kv[0].trim(), kv[1].trim());
+            match key {
+                "rng" => set.range = Some(parse_search_range(value)?),
+                "dist" | "d" => {
+                    set.dist = value
+                        .parse()
+                        .map_err(|_| format!("Invalid distance: {}", value))?
+                }
+                "max-shift-l" => set.max_shift = Some(parse_shift(value, true)?),
+                "max-shift-r" => set.max_shift = Some(parse_shift(value, false)?),
+                _ => {
+                    return fail!(format!(
+                        "Unknown pattern set option: '{}'. Valid are 'rng', 'dist', \
+                         'max-shift-l' and 'max-shift-r'.",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(set)
+    }
+
+    /// Parses the patterns as PROSITE / IUPAC motifs. IUPAC motifs with a fixed
+    /// length are expanded to plain patterns, other motifs are converted to
+    /// regular expressions. Fixed-length PROSITE motifs are searched by matching
+    /// each position against a set of residues if a distance is given.
+    fn compile_motifs(&mut self, syntax: MotifSyntax) -> CliResult<()> {
+        let motifs = self
+            .patterns
+            .iter()
+            .map(|&(_, ref p)| Motif::parse(p, syntax))
+            .collect::<CliResult<Vec<_>>>()?;
+        let fixed = motifs.iter().all(|m| m.is_fixed());
+        if fixed && syntax == MotifSyntax::Iupac {
+            for (&mut (_, ref mut p), m) in self.patterns.iter_mut().zip(&motifs) {
+                *p = m.repr();
+            }
+        } else if fixed && self.dist > 0 {
+            for (&mut (_, ref mut p), m) in self.patterns.iter_mut().zip(&motifs) {
+                *p = m.repr();
+            }
+            self.motifs = motifs;
+        } else {
+            if self.dist > 0 {
+                return fail!(
+                    "Motifs with variable length or anchors cannot be searched with -d/--dist."
+                );
+            }
+            let ambig: Option<&HashMap<u8, Vec<u8>>> = match syntax {
+                MotifSyntax::Iupac if self.patterns.iter().any(|&(_, ref p)| p.contains('U')) => {
+                    Some(&AMBIG_RNA)
+                }
+                MotifSyntax::Iupac => Some(&AMBIG_DNA),
+                MotifSyntax::Prosite => None,
+            };
+            for (&mut (_, ref mut p), m) in self.patterns.iter_mut().zip(&motifs) {
+                *p = m.to_regex(ambig);
+            }
+            self.regex = true;
+            self.motifs = motifs;
+        }
+        Ok(())
+    }
+}
+
 pub fn run() -> CliResult<()> {
     let args = opt::Args::new(USAGE)?;
     let cfg = cfg::Config::from_args_with_help(&args, &FindVarHelp)?;
 
-    let dist: u16 = args.value("--dist")?;
     let sorted = !args.get_bool("--in-order");
     let regex = args.get_bool("--regex");
+    let reorient = args.get_bool("--reorient");
+    let extract = args.get_bool("--extract");
+    let pwm = args.get_bool("--pwm");
+    let both_strands = args.get_bool("--both-strands") || reorient || pwm;
     let ambig = args.yes_no("--ambig")?;
     let verbose = args.get_bool("--verbose");
 
@@ -152,66 +330,207 @@ pub fn run() -> CliResult<()> {
         None
     };
 
+    let motif_syntax = if args.get_bool("--prosite") {
+        Some(MotifSyntax::Prosite)
+    } else if args.get_bool("--iupac") {
+        Some(MotifSyntax::Iupac)
+    } else {
+        None
+    };
+    if motif_syntax.is_some() && regex {
+        return fail!("Motif syntaxes (--prosite / --iupac) cannot be combined with -r/--regex.");
+    }
+    if pwm && (regex || motif_syntax.is_some()) {
+        return fail!("--pwm cannot be combined with -r/--regex, --prosite or --iupac.");
+    }
+    if both_strands && motif_syntax == Some(MotifSyntax::Prosite) {
+        return fail!("PROSITE patterns cannot be searched on both strands.");
+    }
+    if reorient && attr != SeqAttr::Seq {
+        return fail!("--reorient is only possible when searching in sequences.");
+    }
+    if extract && attr != SeqAttr::Seq {
+        return fail!("--extract is only possible when searching in sequences.");
+    }
+
     let num_threads = args.thread_num()?;
 
-    let pattern = args.get_str("<pattern>");
-    let patterns = if !pattern.starts_with("file:") {
-        vec![("pattern".to_string(), pattern.to_string())]
-    } else {
-        read_pattern_file(&pattern[5..])?
+    let (patterns, pwms) = read_patterns(args.get_str("<pattern>"), pwm)?;
+    let first_set = PatternSet {
+        patterns: patterns,
+        range: args.opt_str("--rng").map(parse_search_range).map_or(Ok(None), |r| r.map(Some))?,
+        max_shift: if let Some(n) = args.opt_str("--max-shift-l") {
+            Some(parse_shift(n, true)?)
+        } else if let Some(n) = args.opt_str("--max-shift-r") {
+            Some(parse_shift(n, false)?)
+        } else {
+            None
+        },
+        dist: args.value("--dist")?,
+        regex: regex,
+        motifs: vec![],
+        pwms: pwms,
     };
 
-    let typehint = args.opt_str("--seqtype").map(|s| s.to_ascii_lowercase());
+    // additional pattern sets: the search range and max. shift are not inherited
+    let set_default = PatternSet {
+        patterns: vec![],
+        range: None,
+        max_shift: None,
+        dist: first_set.dist,
+        regex: regex,
+        motifs: vec![],
+        pwms: vec![],
+    };
+    let mut sets = vec![];
+    for spec in args.get_vec("--pattern") {
+        sets.push(PatternSet::from_spec(spec, &set_default, pwm)?);
+    }
+    sets.insert(0, first_set);
+    let num_sets = sets.len();
 
-    //let replace_num = args.get_str("--match-num");
-    let replacement = args.opt_str("--rep");
+    if let Some(syntax) = motif_syntax {
+        for set in &mut sets {
+            set.compile_motifs(syntax)?;
+        }
+    }
+    if both_strands && sets.iter().any(|s| s.regex) {
+        return fail!("Regular expressions cannot be searched on both strands.");
+    }
 
-    let range = if let Some(r) = args.opt_str("--rng") {
-        let (start, end) = parse_range(r)?;
-        Some((start.unwrap_or(1), end.unwrap_or(-1)))
-    } else {
-        None
+    if extract && num_sets < 2 {
+        return fail!("--extract requires at least two pattern sets (-p/--pattern).");
+    }
+
+    let costs = Costs {
+        subst: args.value("--subst-cost")?,
+        ins: args.value("--ins-cost")?,
+        del: args.value("--del-cost")?,
+        free_ends: args.value("--free-ends")?,
     };
+    if costs.subst == 0 || costs.ins == 0 || costs.del == 0 {
+        return fail!("Substitution, insertion and deletion costs must be > 0");
+    }
+    if costs != Costs::default() && sets.iter().any(|s| s.regex) {
+        return fail!("Costs and free end gaps cannot be used with regular expressions.");
+    }
 
-    let max_shift = if let Some(n) = args.opt_str("--max-shift-l") {
-        Some(Shift::Start(n.parse().map_err(|_| {
-            format!("Invalid max. left shift value: {}", n)
-        })?))
-    } else if let Some(n) = args.opt_str("--max-shift-r") {
-        Some(Shift::End(n.parse().map_err(|_| {
-            format!("Invalid max. right shift value: {}", n)
-        })?))
+    if pwm
+        && (costs != Costs::default()
+            || args.opt_str("--matrix").is_some()
+            || sets.iter().any(|s| s.dist > 0))
+    {
+        return fail!("--pwm cannot be combined with --matrix, -d/--dist or edit costs.");
+    }
+    let pwm_threshold = if pwm {
+        PwmThreshold::parse(args.opt_str("--min-score").unwrap_or("80%"))?
     } else {
-        None
+        PwmThreshold::Rel(0.)
     };
 
+    let scoring = match args.opt_str("--matrix") {
+        Some(m) => {
+            let min_score = match args.opt_value("--min-score")? {
+                Some(s) => s,
+                None => return fail!("--matrix requires a minimum score (--min-score)."),
+            };
+            let gap_open = args.value("--gap-open")?;
+            let gap_ext = args.value("--gap-ext")?;
+            if gap_open > 0 || gap_ext >= 0 {
+                return fail!("The gap opening score must be <= 0, the extension score < 0.");
+            }
+            if regex
+                || motif_syntax.is_some()
+                || costs != Costs::default()
+                || sets.iter().any(|s| s.dist > 0)
+            {
+                return fail!(
+                    "--matrix cannot be combined with regular expressions, motifs, -d/--dist \
+                     or edit costs."
+                );
+            }
+            Some(ScoreOpts {
+                matrix: Arc::new(ScoreMatrix::from_name_or_path(m)?),
+                min_score: min_score,
+                gap_open: gap_open,
+                gap_ext: gap_ext,
+            })
+        }
+        None => None,
+    };
+
+    let mut typehint = args.opt_str("--seqtype").map(|s| s.to_ascii_lowercase());
+    if typehint.is_none() && motif_syntax == Some(MotifSyntax::Prosite) {
+        typehint = Some("protein".to_string());
+    }
+
+    //let replace_num = args.get_str("--match-num");
+    let replacement = args.opt_str("--rep");
+    if extract && replacement.is_some() {
+        return fail!("--extract and --rep cannot be combined.");
+    }
+
     let dropped_file = args.opt_str("--dropped").map(|s| s.to_string());
+    let hits_file = args.opt_str("--hits");
 
     // override algorithm for testing
     let algo_override = Algorithm::from_str(args.get_str("--algo"));
 
     ///// option parsing end
 
-    // determine sequence type for each pattern
+    // determine sequence type and algorithm for each pattern
     let typehint = typehint.as_ref().map(|s| s.as_str());
-    let (seqtype, algorithms) = analyse_patterns(
-        &patterns,
-        algo_override,
-        typehint,
-        ambig,
-        regex,
-        dist,
-        verbose,
-    )?;
+    let set_info = sets
+        .iter()
+        .map(|set| {
+            if !set.pwms.is_empty() {
+                report!(verbose, "Position weight matrices: {}", set.pwms.len());
+                return Ok((SeqType::DNA, vec![(PwmScan, false); set.pwms.len()], false));
+            }
+            // motifs are analysed based on their sequence representation
+            let motif_seqs: Vec<_>;
+            let patterns = if set.motifs.is_empty() {
+                &set.patterns
+            } else {
+                motif_seqs = set
+                    .patterns
+                    .iter()
+                    .zip(&set.motifs)
+                    .map(|(&(ref name, _), m)| (name.clone(), m.repr()))
+                    .collect();
+                &motif_seqs
+            };
+            let (seqtype, algorithms, multi) = analyse_patterns(
+                patterns,
+                algo_override,
+                typehint,
+                ambig,
+                set.regex,
+                set.dist,
+                costs != Costs::default() || scoring.is_some(),
+                verbose,
+            )?;
+            if both_strands && seqtype != SeqType::DNA && seqtype != SeqType::RNA {
+                return fail!(format!(
+                    "Searching both strands is only possible with DNA/RNA patterns, but the \
+                     sequence type is {:?}. Use --seqtype to override the type recognition.",
+                    seqtype
+                ));
+            }
+            Ok((seqtype, algorithms, multi && set.motifs.is_empty()))
+        })
+        .collect::<CliResult<Vec<_>>>()?;
+
+    let is_rna = set_info[0].0 == SeqType::RNA;
 
     // run
     cfg.writer_with(
-        |_| Ok(FindVars::new()),
+        |_| Ok(FindVars::new(num_sets)),
         |writer, mut vars, mut match_vars| {
             let replacement = if let Some(r) = replacement {
                 // make sure all hits for group 0 are collected (group 0 is always searched)
                 // API is somehow awkward
-                match_vars.register_all(0);
+                match_vars.register_all(0, 0);
                 let s = vars.build_with(Some(&mut match_vars), |b| {
                     varstring::VarString::var_or_composed(r, b)
                 })?;
@@ -220,33 +539,64 @@ pub fn run() -> CliResult<()> {
                 None
             };
 
-            if filter.is_none() && !match_vars.has_vars() && replacement.is_none() {
+            if filter.is_none()
+                && !match_vars.has_vars()
+                && replacement.is_none()
+                && !extract
+                && hits_file.is_none()
+            {
                 return fail!(
-                    "Match command does nothing. Use -f/-e for filtering, --repl for replacing or \
-                     -a for writing attributes."
+                    "Match command does nothing. Use -f/-e for filtering, --repl for replacing, \
+                     -a for writing attributes or --hits for writing all hits to a file."
                 );
             }
 
-            let needs_alignment =
-                match_vars.bounds_needed().0 || match_vars.bounds_needed().1 || max_shift.is_some();
+            if hits_file.is_some() {
+                // all hits of all pattern sets are needed
+                for i in 0..num_sets {
+                    match_vars.register_all(0, i);
+                }
+            }
 
-            report!(
-                verbose,
-                "Sort by distance: {:?}. Find full position: {:?}",
-                sorted,
-                needs_alignment
-            );
+            if extract {
+                // the first hit of the first and the last set is needed
+                match_vars.register_pos(0, 0, 0);
+                match_vars.register_pos(0, 0, num_sets - 1);
+            }
 
-            let opts = MatchOpts {
-                has_groups: match_vars.positions().has_groups(),
-                needs_alignment: needs_alignment,
-                sorted: sorted,
-                max_dist: dist,
-                seqtype: seqtype,
-            };
+            let opts: Vec<_> = sets
+                .iter()
+                .zip(&set_info)
+                .enumerate()
+                .map(|(i, (set, &(seqtype, _, _)))| {
+                    let bounds = match_vars.bounds_needed(i);
+                    let needs_alignment = bounds.0
+                        || bounds.1
+                        || set.max_shift.is_some()
+                        || extract
+                        || hits_file.is_some();
+                    report!(
+                        verbose,
+                        "Pattern set {}: sort by distance: {:?}. Find full position: {:?}",
+                        i + 1,
+                        sorted,
+                        needs_alignment
+                    );
+                    MatchOpts {
+                        has_groups: match_vars.positions(i).has_groups(),
+                        needs_alignment: needs_alignment,
+                        needs_path: match_vars.path_needed(i) || hits_file.is_some(),
+                        sorted: sorted,
+                        max_dist: set.dist,
+                        costs: costs,
+                        scoring: scoring.clone(),
+                        pwm_threshold: pwm_threshold,
+                        seqtype: seqtype,
+                    }
+                })
+                .collect();
 
             let mut replacement_text = vec![];
-            let (pattern_names, patterns): (Vec<_>, Vec<_>) = patterns.into_iter().unzip();
 
             let mut dropped_file = if let Some(f) = dropped_file.as_ref() {
                 Some(cfg.other_writer(f, Some(&mut vars), Some(&mut match_vars))?)
@@ -254,38 +604,136 @@ pub fn run() -> CliResult<()> {
                 None
             };
 
-            let pos = match_vars.positions().clone();
+            let mut hits_writer = match hits_file {
+                Some(f) => Some(HitsWriter::new(f)?),
+                None => None,
+            };
+
+            let positions: Vec<_> = (0..num_sets)
+                .map(|i| match_vars.positions(i).clone())
+                .collect();
 
             cfg.var_parallel_init(
                 &mut vars,
                 num_threads,
                 || {
-                    // initiate matchers (one per record set)
-                    algorithms
-                        .iter()
-                        .zip(&patterns)
-                        .map(|(&(algo, is_ambig), patt)| get_matcher(patt, algo, is_ambig, &opts))
+                    // initiate matchers (one vector per pattern set and record set)
+                    // with --both-strands, there are two matchers per pattern (fwd, rev)
+                    sets.iter()
+                        .zip(&set_info)
+                        .zip(&opts)
+                        .map(|((set, &(_, ref algorithms, multi)), opts)| {
+                            let mut matchers = vec![];
+                            // (pattern, max. edits, ambiguities) for the multi-pattern filter
+                            let mut filter_patterns = vec![];
+                            for (i, (&(algo, is_ambig), &(_, ref patt))) in
+                                algorithms.iter().zip(&set.patterns).enumerate()
+                            {
+                                let motif = set.motifs.get(i);
+                                let pwm = set.pwms.get(i);
+                                matchers.push(get_matcher(patt, motif, pwm, algo, is_ambig, opts)?);
+                                let max_edits = max_edits(algo, opts);
+                                let ambig_map = get_ambig_map(is_ambig, opts.seqtype);
+                                filter_patterns.push((patt.as_bytes().to_owned(), max_edits, ambig_map));
+                                if both_strands {
+                                    let mut rc = vec![];
+                                    iupac::revcomp(
+                                        patt.as_bytes(),
+                                        opts.seqtype == SeqType::RNA,
+                                        &mut rc,
+                                    );
+                                    let rc = String::from_utf8(rc).unwrap();
+                                    let rev_pwm = pwm.map(|p| p.revcomp());
+                                    matchers.push(get_matcher(
+                                        &rc,
+                                        None,
+                                        rev_pwm.as_ref(),
+                                        algo,
+                                        is_ambig,
+                                        opts,
+                                    )?);
+                                    filter_patterns.push((rc.into_bytes(), max_edits, ambig_map));
+                                }
+                            }
+                            let filter = if multi
+                                && opts.costs.free_ends == 0
+                                && opts.scoring.is_none()
+                            {
+                                MultiFilter::new(
+                                    filter_patterns.iter().map(|&(ref p, k, a)| (p.as_slice(), k, a)),
+                                )?
+                            } else {
+                                None
+                            };
+                            Ok((matchers, filter))
+                        })
                         .collect::<CliResult<Vec<_>>>()
                 },
                 || {
                     // initialize per-sequence record data
                     let editor = Box::new(RecordEditor::default());
-                    let matches = Box::new(Matches::new(
-                        &pattern_names,
-                        pos.clone(),
-                        range,
-                        max_shift.clone(),
-                    ));
-                    (editor, matches)
+                    let matches: Vec<_> = sets
+                        .iter()
+                        .zip(&positions)
+                        .map(|(set, pos)| {
+                            let names: Vec<_> =
+                                set.patterns.iter().map(|&(ref n, _)| n.clone()).collect();
+                            let seqs = set
+                                .patterns
+                                .iter()
+                                .map(|&(_, ref p)| {
+                                    let mut rc = vec![];
+                                    if both_strands {
+                                        iupac::revcomp(p.as_bytes(), is_rna, &mut rc);
+                                    }
+                                    (p.as_bytes().to_owned(), rc)
+                                })
+                                .collect();
+                            Matches::new(
+                                &names,
+                                seqs,
+                                pos.clone(),
+                                set.range,
+                                set.max_shift.clone(),
+                                both_strands,
+                            )
+                        })
+                        .collect();
+                    (editor, matches, Box::new((vec![], vec![])))
                 },
-                |record, &mut (ref mut editor, ref mut matches), ref mut matchers| {
+                |record, &mut (ref mut editor, ref mut matches, _), ref mut matchers| {
                     // searching in worker threads
                     let text = editor.get(attr, &record, false);
-                    matches.find(text, matchers);
+                    for (m, &mut (ref mut matchers, ref mut filter)) in
+                        matches.iter_mut().zip(matchers.iter_mut())
+                    {
+                        m.find(text, matchers, filter.as_mut());
+                    }
                     Ok(())
                 },
-                |record, &mut (ref mut editor, ref matches), vars| {
+                |record, &mut (ref mut editor, ref mut matches, ref mut buf), vars| {
                     // records returned to main thread
+                    let (ref mut new_seq, ref mut new_qual) = **buf;
+                    let rc_rec;
+                    let record = if reorient && matches[0].is_reverse(0) == Some(true) {
+                        new_seq.clear();
+                        for s in record.seq_segments().rev() {
+                            new_seq.extend(s.iter().rev().map(|&c| iupac::complement(c, is_rna)));
+                        }
+                        let qual = record.qual().map(|q| {
+                            new_qual.clear();
+                            new_qual.extend(q.iter().rev());
+                            new_qual.as_slice()
+                        });
+                        for m in matches.iter_mut() {
+                            m.reverse_coords(new_seq.len());
+                        }
+                        rc_rec = SeqQualRecord::new(record, new_seq, qual);
+                        vars.set_seq(&rc_rec)?;
+                        &rc_rec as &Record
+                    } else {
+                        record
+                    };
                     if let Some(rep) = replacement.as_ref() {
                         editor.edit_with_val(attr, &record, true, |text, out| {
                             match_vars.set_with(
@@ -298,7 +746,7 @@ pub fn run() -> CliResult<()> {
                             replacement_text.clear();
                             rep.compose(&mut replacement_text, vars.symbols());
 
-                            let pos = matches
+                            let pos = matches[0]
                                 .matches_iter(0, 0)
                                 .filter_map(|m| m)
                                 .map(|m| (m.start, m.end));
@@ -311,9 +759,13 @@ pub fn run() -> CliResult<()> {
                         match_vars.set_with(record, matches, &mut vars.mut_data().symbols, text)?;
                     }
 
-                    // keep / exclude
+                    if let Some(ref mut w) = hits_writer {
+                        w.write(record.id_bytes(), matches)?;
+                    }
+
+                    // keep / exclude (all pattern sets have to match)
                     if let Some(keep) = filter {
-                        if matches.has_matches() ^ keep {
+                        if matches.iter().all(|m| m.has_matches()) ^ keep {
                             if let Some(ref mut f) = dropped_file {
                                 f.write(&record, vars)?;
                             }
@@ -321,17 +773,105 @@ pub fn run() -> CliResult<()> {
                         }
                     }
 
+                    if extract {
+                        // region between the first and the last pattern set
+                        let first = matches[0].get_match(0, 0, 0).map(|m| m.end);
+                        let last = matches[num_sets - 1].get_match(0, 0, 0).map(|m| m.start);
+                        if let (Some(start), Some(end)) = (first, last) {
+                            if start <= end {
+                                let text = editor.get(attr, &record, true);
+                                let seq = text[start..end].to_owned();
+                                let qual = record.qual().map(|q| &q[start..end]);
+                                writer.write(&SeqQualRecord::new(record, &seq, qual), vars)?;
+                                return Ok(true);
+                            }
+                        }
+                    }
+
                     // write non-excluded to output
                     writer.write(&editor.rec(&record), vars)?;
                     Ok(true)
                 },
             )?;
+            if let Some(w) = hits_writer {
+                w.finish()?;
+            }
             Ok(())
         },
     )?;
     Ok(())
 }
 
+/// Writes all hits to a TSV or BED file, sorted by position
+/// for each record
+struct HitsWriter {
+    writer: Box<output::WriteFinish>,
+    bed: bool,
+    // (start, end, pattern set, pattern name, match, reverse)
+    hits: Vec<(usize, usize, usize, String, Match, bool)>,
+}
+
+impl HitsWriter {
+    fn new(path: &str) -> CliResult<HitsWriter> {
+        let (ext, compr) = opt::ext_compr(&path);
+        let bed = ext.map(|e| e.eq_ignore_ascii_case("bed")) == Some(true);
+        let writer = output::io_writer_from_kind(&output::OutputKind::File(path.into()))?;
+        let mut writer = output::compr_writer(writer, compr.unwrap_or(Compression::None), None, 1)?;
+        if !bed {
+            writeln!(writer, "id\tpattern\tstart\tend\tstrand\tdist\tsubst\tins\tdel")?;
+        }
+        Ok(HitsWriter {
+            writer: writer,
+            bed: bed,
+            hits: vec![],
+        })
+    }
+
+    fn write(&mut self, id: &[u8], matches: &[Matches]) -> CliResult<()> {
+        self.hits.clear();
+        for (set, m) in matches.iter().enumerate() {
+            let hits = &mut self.hits;
+            m.for_each_hit(|name, m, reverse| {
+                hits.push((m.start, m.end, set, name.to_string(), m.clone(), reverse));
+                Ok(())
+            })?;
+        }
+        self.hits.sort_by(|a, b| (a.0, a.1, a.2).cmp(&(b.0, b.1, b.2)));
+
+        let id = String::from_utf8_lossy(id);
+        for &(_, _, _, ref name, ref m, reverse) in &self.hits {
+            let strand = if reverse { '-' } else { '+' };
+            if self.bed {
+                writeln!(
+                    self.writer,
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    id, m.start, m.end, name, m.dist, strand
+                )?;
+            } else {
+                writeln!(
+                    self.writer,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    id,
+                    name,
+                    m.start + 1,
+                    m.end,
+                    strand,
+                    m.dist,
+                    m.subst,
+                    m.ins,
+                    m.del
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> CliResult<()> {
+        self.writer.finish()?.flush()?;
+        Ok(())
+    }
+}
+
 fn analyse_patterns<S>(
     patterns: &[(S, S)],
     algo_override: Option<Algorithm>,
@@ -339,8 +879,9 @@ fn analyse_patterns<S>(
     ambig_override: Option<bool>,
     regex: bool,
     dist: u16,
+    weighted: bool,
     verbose: bool,
-) -> CliResult<(SeqType, Vec<(Algorithm, bool)>)>
+) -> CliResult<(SeqType, Vec<(Algorithm, bool)>, bool)>
 where
     S: AsRef<str> + Display,
 {
@@ -377,7 +918,7 @@ where
             // decide which algorithm should be used
             let mut algorithm = if regex {
                 Regex
-            } else if dist > 0 || is_ambig {
+            } else if dist > 0 || is_ambig || weighted {
                 Myers
             } else {
                 Exact
@@ -446,12 +987,18 @@ where
       ));
     }
 
+    // pre-filter patterns if there are many of them
+    let multi = out.len() >= MULTI_FILTER_MIN_PATTERNS && out.iter().all(|&(a, _)| a != Regex);
+    report!(verbose, "Multi-pattern pre-filter: {:?}", multi);
+
     let t = unique_seqtypes.into_iter().next().unwrap();
-    Ok((t, out))
+    Ok((t, out, multi))
 }
 
 fn get_matcher<'a>(
     pattern: &str,
+    motif: Option<&Motif>,
+    pwm: Option<&Pwm>,
     algorithm: Algorithm,
     ambig: bool,
     o: &MatchOpts,
@@ -462,25 +1009,97 @@ fn get_matcher<'a>(
         Regex =>
           // TODO: string regexes for ID/desc
           Box::new(BytesRegexMatcher::new(pattern, o.has_groups)?),
+        PwmScan => {
+            let pwm = pwm.expect("no position weight matrix supplied").clone();
+            Box::new(PwmMatcher::new(pwm, o.pwm_threshold, o.sorted))
+        }
         Myers => {
-            let ambig_map =
-                if ambig {
-                    match o.seqtype {
-                        SeqType::DNA => Some(&AMBIG_DNA as &HashMap<_, _>),
-                        SeqType::RNA => Some(&AMBIG_RNA as &HashMap<_, _>),
-                        SeqType::Protein => Some(&AMBIG_PROTEIN as &HashMap<_, _>),
-                        SeqType::Other => None,
-                    }
-                } else { None };
-            Box::new(MyersMatcher::new(
-              pattern.as_bytes(), o.max_dist as u8,
-              o.needs_alignment, o.sorted,
-              ambig_map
-            )?)
+            let ambig_map = get_ambig_map(ambig, o.seqtype);
+            // fixed-length motifs: sets of matching characters for each position
+            let variants = match motif {
+                Some(m) => m.variants(ambig_map),
+                None => pattern_variants(pattern.as_bytes(), ambig_map),
+            };
+            if let Some(ref s) = o.scoring {
+                Box::new(ScoreMatcher::new(
+                  pattern.as_bytes(), s.clone(),
+                  o.needs_alignment, o.needs_path, o.sorted,
+                ))
+            } else if o.costs != Costs::default() {
+                Box::new(WeightedMatcher::new(
+                  &variants, o.max_dist, o.costs,
+                  o.needs_alignment, o.needs_path, o.sorted,
+                )?)
+            } else {
+                Box::new(MyersMatcher::new(
+                  &variants, o.max_dist,
+                  o.needs_alignment, o.needs_path, o.sorted,
+                )?)
+            }
         }
     })
 }
 
+fn get_ambig_map(ambig: bool, seqtype: SeqType) -> Option<&'static HashMap<u8, Vec<u8>>> {
+    if !ambig {
+        return None;
+    }
+    match seqtype {
+        SeqType::DNA => Some(&AMBIG_DNA),
+        SeqType::RNA => Some(&AMBIG_RNA),
+        SeqType::Protein => Some(&AMBIG_PROTEIN),
+        SeqType::Other => None,
+    }
+}
+
+/// Returns the maximum number of edit operations in a hit
+fn max_edits(algorithm: Algorithm, o: &MatchOpts) -> usize {
+    match algorithm {
+        Myers => {
+            let c = o.costs;
+            (o.max_dist / min(c.subst, min(c.ins, c.del))) as usize
+        }
+        _ => 0,
+    }
+}
+
+/// Reads patterns from a string or file. If `pwm` is true, the pattern is a file
+/// with position weight matrices, and the consensus sequences are returned
+/// as patterns.
+fn read_patterns(pattern: &str, pwm: bool) -> CliResult<(Vec<(String, String)>, Vec<Pwm>)> {
+    if pwm {
+        let path = if pattern.starts_with("file:") { &pattern[5..] } else { pattern };
+        let pwms = read_pwm_file(path)?;
+        let patterns = pwms.iter().map(|p| (p.name.clone(), p.consensus())).collect();
+        return Ok((patterns, pwms));
+    }
+    Ok((read_pattern_strings(pattern)?, vec![]))
+}
+
+fn read_pattern_strings(pattern: &str) -> CliResult<Vec<(String, String)>> {
+    if pattern.starts_with("file:") {
+        read_pattern_file(&pattern[5..])
+    } else {
+        Ok(vec![("pattern".to_string(), pattern.to_string())])
+    }
+}
+
+fn parse_search_range(rng: &str) -> CliResult<(isize, isize)> {
+    let (start, end) = parse_range(rng)?;
+    Ok((start.unwrap_or(1), end.unwrap_or(-1)))
+}
+
+fn parse_shift(n: &str, left: bool) -> CliResult<Shift> {
+    let n = n.parse().map_err(|_| {
+        format!(
+            "Invalid max. {} shift value: {}",
+            if left { "left" } else { "right" },
+            n
+        )
+    })?;
+    Ok(if left { Shift::Start(n) } else { Shift::End(n) })
+}
+
 fn read_pattern_file(path: &str) -> CliResult<Vec<(String, String)>> {
     use seq_io::fasta::*;
     let mut reader = Reader::from_path(path)?;
@@ -0,0 +1,316 @@
+//! Parsing of PROSITE patterns and of the IUPAC notation with repeat counts
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use regex;
+
+use error::CliResult;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MotifSyntax {
+    /// PROSITE patterns, e.g. C-x(2,4)-C-x(3)-[LIVMFYWC]
+    Prosite,
+    /// IUPAC codes with optional repeat counts, e.g. N{3}ACGT
+    Iupac,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Class {
+    Any,
+    OneOf(Vec<u8>),
+    NoneOf(Vec<u8>),
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    class: Class,
+    min: usize,
+    max: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Motif {
+    elements: Vec<Element>,
+    // N-terminal / C-terminal anchors (PROSITE only)
+    start_anchor: bool,
+    end_anchor: bool,
+}
+
+impl Motif {
+    pub fn parse(pattern: &str, syntax: MotifSyntax) -> CliResult<Motif> {
+        match syntax {
+            MotifSyntax::Prosite => Self::parse_prosite(pattern),
+            MotifSyntax::Iupac => Self::parse_iupac(pattern),
+        }
+    }
+
+    fn parse_prosite(pattern: &str) -> CliResult<Motif> {
+        let err = |msg: &str| -> CliResult<Motif> {
+            fail!(format!("Invalid PROSITE pattern '{}': {}", pattern, msg))
+        };
+        let mut p = pattern.trim();
+        if p.ends_with('.') {
+            p = &p[..p.len() - 1];
+        }
+        let mut motif = Motif {
+            elements: vec![],
+            start_anchor: false,
+            end_anchor: false,
+        };
+        if p.starts_with('<') {
+            motif.start_anchor = true;
+            p = &p[1..];
+        }
+        if p.ends_with('>') {
+            motif.end_anchor = true;
+            p = &p[..p.len() - 1];
+        }
+        for elem in p.split('-') {
+            let elem = elem.trim();
+            // repeat count
+            let (spec, min, max) = match elem.find('(') {
+                Some(i) => {
+                    if !elem.ends_with(')') {
+                        return err(&format!("missing ')' in '{}'", elem));
+                    }
+                    let (min, max) = match parse_repeat(&elem[i + 1..elem.len() - 1]) {
+                        Some(r) => r,
+                        None => return err(&format!("invalid repeat count in '{}'", elem)),
+                    };
+                    (&elem[..i], min, max)
+                }
+                None => (elem, 1, 1),
+            };
+            let class = if spec == "x" || spec == "X" {
+                Class::Any
+            } else if spec.starts_with('[') && spec.ends_with(']') && spec.len() > 2 {
+                Class::OneOf(residues(&spec[1..spec.len() - 1]))
+            } else if spec.starts_with('{') && spec.ends_with('}') && spec.len() > 2 {
+                Class::NoneOf(residues(&spec[1..spec.len() - 1]))
+            } else if spec.len() == 1 {
+                Class::OneOf(residues(spec))
+            } else {
+                return err(&format!("invalid element '{}'", elem));
+            };
+            if let Class::OneOf(ref r) | Class::NoneOf(ref r) = class {
+                if r.iter().any(|c| !c.is_ascii_alphabetic()) {
+                    return err(&format!(
+                        "invalid element '{}' (only amino acid codes are allowed)",
+                        elem
+                    ));
+                }
+            }
+            motif.elements.push(Element {
+                class: class,
+                min: min,
+                max: max,
+            });
+        }
+        Ok(motif)
+    }
+
+    fn parse_iupac(pattern: &str) -> CliResult<Motif> {
+        let pattern = pattern.trim();
+        let mut motif = Motif {
+            elements: vec![],
+            start_anchor: false,
+            end_anchor: false,
+        };
+        let mut chars = pattern.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == '{' {
+                let end = match pattern[i..].find('}') {
+                    Some(e) => i + e,
+                    None => return fail!(format!("Missing '}}' in pattern '{}'", pattern)),
+                };
+                let (min, max) = parse_repeat(&pattern[i + 1..end]).ok_or_else(|| {
+                    format!("Invalid repeat count in pattern '{}'", pattern)
+                })?;
+                let prev = motif.elements.last_mut().ok_or_else(|| {
+                    format!("Repeat count without preceding character in pattern '{}'", pattern)
+                })?;
+                if prev.min != 1 || prev.max != 1 {
+                    return fail!(format!("Invalid repeat count in pattern '{}'", pattern));
+                }
+                prev.min = min;
+                prev.max = max;
+                while chars.next().map(|(j, _)| j < end) == Some(true) {}
+            } else if c.is_ascii_alphabetic() {
+                motif.elements.push(Element {
+                    class: Class::OneOf(vec![c.to_ascii_uppercase() as u8]),
+                    min: 1,
+                    max: 1,
+                });
+            } else {
+                return fail!(format!(
+                    "Invalid character '{}' in IUPAC pattern '{}'",
+                    c, pattern
+                ));
+            }
+        }
+        Ok(motif)
+    }
+
+    /// Returns true if the motif always has the same length and is not anchored,
+    /// which is required for approximate matching.
+    pub fn is_fixed(&self) -> bool {
+        !self.start_anchor && !self.end_anchor && self.elements.iter().all(|e| e.min == e.max)
+    }
+
+    /// Returns a sequence with one character per motif position (the minimum number
+    /// of repeats is used). Positions matching several residues are represented
+    /// by 'X' (PROSITE) or the ambiguity code (IUPAC).
+    pub fn repr(&self) -> String {
+        let mut out = String::new();
+        for e in &self.elements {
+            let c = match e.class {
+                Class::OneOf(ref r) if r.len() == 1 => r[0] as char,
+                _ => 'X',
+            };
+            for _ in 0..e.min {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    /// Converts the motif to a regular expression. Characters contained in `ambig`
+    /// are converted to character classes containing all matching characters.
+    pub fn to_regex(&self, ambig: Option<&HashMap<u8, Vec<u8>>>) -> String {
+        let mut out = String::new();
+        if self.start_anchor {
+            out.push('^');
+        }
+        for e in &self.elements {
+            match e.class {
+                Class::Any => out.push('.'),
+                Class::OneOf(ref r) => {
+                    let r = expand(r, ambig);
+                    if r.len() == 1 {
+                        out.push_str(&regex::escape(&(r[0] as char).to_string()));
+                    } else {
+                        write_class(&r, false, &mut out);
+                    }
+                }
+                Class::NoneOf(ref r) => write_class(&expand(r, ambig), true, &mut out),
+            }
+            if e.min != 1 || e.max != 1 {
+                if e.min == e.max {
+                    write!(out, "{{{}}}", e.min).unwrap();
+                } else {
+                    write!(out, "{{{},{}}}", e.min, e.max).unwrap();
+                }
+            }
+        }
+        if self.end_anchor {
+            out.push('$');
+        }
+        out
+    }
+
+    /// Returns the matching characters for every position of a motif with fixed
+    /// length (see `is_fixed()`)
+    pub fn variants(&self, ambig: Option<&HashMap<u8, Vec<u8>>>) -> Vec<Vec<u8>> {
+        debug_assert!(self.is_fixed());
+        let mut out = vec![];
+        for e in &self.elements {
+            let v = match e.class {
+                Class::Any => (0..256).map(|b| b as u8).collect(),
+                Class::OneOf(ref r) => expand(r, ambig),
+                Class::NoneOf(ref r) => {
+                    let r = expand(r, ambig);
+                    (0..256)
+                        .map(|b| b as u8)
+                        .filter(|b| !r.contains(b))
+                        .collect()
+                }
+            };
+            for _ in 0..e.min {
+                out.push(v.clone());
+            }
+        }
+        out
+    }
+}
+
+fn residues(s: &str) -> Vec<u8> {
+    s.bytes().map(|b| b.to_ascii_uppercase()).collect()
+}
+
+// parses 'n' or 'n,m'
+fn parse_repeat(s: &str) -> Option<(usize, usize)> {
+    let mut parts = s.splitn(2, ',');
+    let min = parts.next()?.trim().parse().ok()?;
+    let max = match parts.next() {
+        Some(m) => m.trim().parse().ok()?,
+        None => min,
+    };
+    if max < min || max == 0 {
+        return None;
+    }
+    Some((min, max))
+}
+
+fn expand(residues: &[u8], ambig: Option<&HashMap<u8, Vec<u8>>>) -> Vec<u8> {
+    let mut out = vec![];
+    for r in residues {
+        match ambig.and_then(|a| a.get(r)) {
+            Some(v) => out.extend_from_slice(v),
+            None => out.push(*r),
+        }
+    }
+    out.sort();
+    out.dedup();
+    out
+}
+
+fn write_class(residues: &[u8], negate: bool, out: &mut String) {
+    out.push('[');
+    if negate {
+        out.push('^');
+    }
+    for &r in residues {
+        out.push_str(&regex::escape(&(r as char).to_string()));
+    }
+    out.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prosite() {
+        let m = Motif::parse("C-x(2,4)-C-x(3)-[LIVMFYWC].", MotifSyntax::Prosite).unwrap();
+        assert_eq!(&m.to_regex(None), "C.{2,4}C.{3}[CFILMVWY]");
+        assert_eq!(&m.repr(), "CXXCXXXX");
+        assert!(!m.is_fixed());
+        let m = Motif::parse("<M-{PG}-x-K>", MotifSyntax::Prosite).unwrap();
+        assert_eq!(&m.to_regex(None), "^M[^GP].K$");
+        let m = Motif::parse("A-{PG}-x(2)", MotifSyntax::Prosite).unwrap();
+        assert!(m.is_fixed());
+        let v = m.variants(None);
+        assert_eq!(v.len(), 4);
+        assert_eq!(&v[0], b"A");
+        assert!(!v[1].contains(&b'P') && !v[1].contains(&b'G') && v[1].contains(&b'A'));
+        assert_eq!(v[2].len(), 256);
+        assert!(Motif::parse("C-x(2,4", MotifSyntax::Prosite).is_err());
+        assert!(Motif::parse("C-xx", MotifSyntax::Prosite).is_err());
+    }
+
+    #[test]
+    fn iupac() {
+        let ambig = hashmap!{ b'N' => b"ACGTN".to_vec(), b'R' => b"AGR".to_vec() };
+        let m = Motif::parse("N{3}ACRT", MotifSyntax::Iupac).unwrap();
+        assert!(m.is_fixed());
+        assert_eq!(&m.repr(), "NNNACRT");
+        assert_eq!(&m.to_regex(Some(&ambig)), "[ACGNT]{3}AC[AGR]T");
+        let m = Motif::parse("ACn{2,4}T", MotifSyntax::Iupac).unwrap();
+        assert!(!m.is_fixed());
+        assert_eq!(&m.repr(), "ACNNT");
+        assert!(Motif::parse("{3}A", MotifSyntax::Iupac).is_err());
+        assert!(Motif::parse("A{3", MotifSyntax::Iupac).is_err());
+        assert!(Motif::parse("A{3}{2}", MotifSyntax::Iupac).is_err());
+    }
+}
@@ -1,9 +1,13 @@
 use std::io::Write;
 
+use itertools::Itertools;
+
 use error::CliResult;
 use io::Record;
 use var;
 
+use super::matcher::{AlignmentOperation, Match};
+
 use super::*;
 
 pub struct FindVarHelp;
@@ -13,7 +17,14 @@ impl VarHelp for FindVarHelp {
         "Pattern finding variables"
     }
     fn usage(&self) -> &'static str {
-        "f:<variable>[.pattern_rank][:match_num][:group]"
+        "f[set]:<variable>[.pattern_rank][:match_num][:group]"
+    }
+    fn desc(&self) -> Option<&'static str> {
+        Some(
+            "With multiple pattern sets (-p), the variables of each set are accessed \
+             using a numbered prefix: f1:<variable>, f2:<variable>, etc. \
+             The 'f:' prefix always refers to the first set.",
+        )
     }
     fn vars(&self) -> Option<&'static [(&'static str, &'static str)]> {
         Some(&[
@@ -21,7 +32,9 @@ impl VarHelp for FindVarHelp {
             ("f:start", "Start of the match."),
             ("f:end",   "End of the match."),
             ("f:dist", "Distance of the matched sequence compared to the pattern. Normally, this is \
-              the edit distance, unless --gapw is used"),
+              the edit distance, unless other costs are set with --subst-cost, --ins-cost \
+              or --del-cost. With --matrix and --pwm, this is the difference between the highest \
+              possible score of the pattern and the score of the hit (rounded with --pwm)"),
             ("f:neg_start", "Start of the match relative to sequence end (negative number)"),
             ("f:neg_end",   "End of the match relative to sequence end (negative number)"),
             ("f:range",  "Range of the match in the form start-end"),
@@ -32,10 +45,32 @@ impl VarHelp for FindVarHelp {
             "Range of the match (dot delimiter) relative to the sequence end (-<start>..-<end>)"),
             ("f:name",
             "Name of the best matching pattern if there are multiple (read from pattern file)"),
+            ("f:subst", "Number of substitutions in the alignment of the pattern with the text"),
+            ("f:ins", "Number of insertions in the pattern (pattern characters missing in the text)"),
+            ("f:del", "Number of deletions in the pattern (text characters missing in the pattern)"),
+            ("f:cigar",
+            "CIGAR string of the pattern aligned to the matched text \
+            (=: match, X: substitution, I: insertion, D: deletion)"),
+            ("f:aln_pattern", "Pattern aligned to the matched text, with gaps (-)"),
+            ("f:aln_text", "Matched text aligned to the pattern, with gaps (-)"),
+            ("f:score",
+            "Alignment score of the hit (with --matrix) or log-odds score (with --pwm)"),
+            ("f:pvalue",
+            "P-value of the log-odds score of the hit given a uniform background (only with --pwm)"),
+            ("f:ident",
+            "Identity: number of identical positions divided by the number of alignment \
+            columns (including gaps)"),
+            ("f:strand",
+            "Strand of the match ('+' or '-'), only differs from '+' with --both-strands"),
         ])
     }
     fn examples(&self) -> Option<&'static [(&'static str, &'static str)]> {
-        Some(&[])
+        Some(&[(
+            "Locating a forward primer at the start and a reverse primer at the end of \
+             the reads",
+            "st find --rng ..30 file:fwd.fa -p 'file:rev.fa;rng=-30..' -d 2 \
+             -a fwd={f1:name} -a rev={f2:name} -a amplicon={f1:end}..{f2:start} reads.fq",
+        )])
     }
 }
 
@@ -52,25 +87,64 @@ pub enum Var {
     Dist,
     Match,
     Name,
+    Strand,
+    Subst,
+    Ins,
+    Del,
+    Cigar,
+    AlnPattern,
+    AlnText,
+    Score,
+    Pvalue,
+    Ident,
+}
+
+impl Var {
+    // requires the alignment path
+    fn needs_path(&self) -> bool {
+        match *self {
+            Subst | Ins | Del | Cigar | AlnPattern | AlnText | Ident => true,
+            _ => false,
+        }
+    }
 }
 
 use self::Var::*;
 
 #[derive(Debug)]
 pub struct FindVars {
-    // (var, var_id, position, pattern_rank)
-    // position: Some((pos, group)), or None for all hits
-    vars: Vec<(Var, usize, Option<usize>, usize, usize)>,
-    pos: SearchPositions,
-    bounds_needed: (bool, bool),
+    // (var, var_id, position, group, pattern_rank, pattern_set)
+    // position: Some(pos), or None for all hits
+    vars: Vec<(Var, usize, Option<usize>, usize, usize, usize)>,
+    // positions and bounds needed for each pattern set
+    pos: Vec<SearchPositions>,
+    bounds_needed: Vec<(bool, bool)>,
+    path_needed: Vec<bool>,
 }
 
 impl FindVars {
-    pub fn new() -> FindVars {
+    pub fn new(num_sets: usize) -> FindVars {
         FindVars {
             vars: vec![],
-            pos: SearchPositions::new(),
-            bounds_needed: (false, false),
+            pos: vec![SearchPositions::new(); num_sets],
+            bounds_needed: vec![(false, false); num_sets],
+            path_needed: vec![false; num_sets],
+        }
+    }
+
+    // returns the index of the pattern set given a variable prefix
+    // (f, f1, f2...), or None if the prefix is not valid
+    fn parse_set(&self, prefix: Option<&str>) -> Option<usize> {
+        let p = prefix?;
+        if !p.starts_with('f') {
+            return None;
+        }
+        if p == "f" {
+            return Some(0);
+        }
+        match p[1..].parse::<usize>() {
+            Ok(i) if i > 0 && i <= self.pos.len() => Some(i - 1),
+            _ => None,
         }
     }
 
@@ -157,43 +231,60 @@ impl FindVars {
         pos: Option<usize>,
         group: usize,
         rank: usize,
+        set: usize,
     ) -> CliResult<()> {
-        self.vars.push((var, var_id, pos, group, rank));
+        self.vars.push((var, var_id, pos, group, rank, set));
 
         if let Some(p) = pos {
-            self.pos.register_pos(p, group);
+            self.pos[set].register_pos(p, group);
         } else {
-            self.pos.register_all(group);
+            self.pos[set].register_all(group);
         }
 
         Ok(())
     }
 
-    pub fn register_all(&mut self, group: usize) {
-        self.pos.register_all(group);
+    pub fn register_pos(&mut self, pos: usize, group: usize, set: usize) {
+        self.pos[set].register_pos(pos, group);
+    }
+
+    pub fn register_all(&mut self, group: usize, set: usize) {
+        self.pos[set].register_all(group);
+    }
+
+    pub fn positions(&self, set: usize) -> &SearchPositions {
+        &self.pos[set]
     }
 
-    pub fn positions(&self) -> &SearchPositions {
-        &self.pos
+    pub fn bounds_needed(&self, set: usize) -> (bool, bool) {
+        self.bounds_needed[set]
     }
 
-    pub fn bounds_needed(&self) -> (bool, bool) {
-        self.bounds_needed
+    pub fn path_needed(&self, set: usize) -> bool {
+        self.path_needed[set]
     }
 
     pub fn set_with(
         &mut self,
         rec: &Record,
-        matches: &Matches,
+        matches: &[Matches],
         symbols: &mut var::symbols::Table,
         text: &[u8],
     ) -> CliResult<()> {
-        for &(ref var, var_id, ref position, group, pattern_rank) in &self.vars {
+        for &(ref var, var_id, ref position, group, pattern_rank, set) in &self.vars {
+            let matches = &matches[set];
             if *var == Name {
                 let name = matches.pattern_name(pattern_rank).unwrap_or("");
                 symbols.set_text(var_id, name.as_bytes());
                 continue;
             }
+            if *var == Strand {
+                match matches.is_reverse(pattern_rank) {
+                    Some(rev) => symbols.set_text(var_id, if rev { b"-" } else { b"+" }),
+                    None => symbols.set_none(var_id),
+                }
+                continue;
+            }
 
             if let Some(pos) = position.as_ref() {
                 // specific hits requested
@@ -219,6 +310,26 @@ impl FindVars {
                             m.neg_end1(rec.seq_len())
                         )?,
                         Match => symbols.set_text(var_id, &text[m.start..m.end]),
+                        Subst => symbols.set_int(var_id, i64::from(m.subst)),
+                        Ins => symbols.set_int(var_id, i64::from(m.ins)),
+                        Del => symbols.set_int(var_id, i64::from(m.del)),
+                        Cigar => write_cigar(m, symbols.mut_text(var_id))?,
+                        Score => match m.score {
+                            Some(s) => symbols.set_float(var_id, s),
+                            None => symbols.set_none(var_id),
+                        },
+                        Pvalue => match m.pvalue {
+                            Some(p) => symbols.set_float(var_id, p),
+                            None => symbols.set_none(var_id),
+                        },
+                        Ident => symbols.set_float(var_id, identity(m)),
+                        AlnPattern | AlnText => write_aln(
+                            m,
+                            matches.pattern(pattern_rank).unwrap(),
+                            text,
+                            *var == AlnPattern,
+                            symbols.mut_text(var_id),
+                        ),
                         _ => unreachable!(),
                     }
                     continue;
@@ -246,6 +357,28 @@ impl FindVars {
                                 m.neg_end1(rec.seq_len())
                             )?,
                             Match => out.extend_from_slice(&text[m.start..m.end]),
+                            Subst => write!(out, "{}", m.subst)?,
+                            Ins => write!(out, "{}", m.ins)?,
+                            Del => write!(out, "{}", m.del)?,
+                            Cigar => write_cigar(m, out)?,
+                            Score => {
+                                if let Some(s) = m.score {
+                                    write!(out, "{}", s)?;
+                                }
+                            }
+                            Pvalue => {
+                                if let Some(p) = m.pvalue {
+                                    write!(out, "{}", p)?;
+                                }
+                            }
+                            Ident => write!(out, "{}", identity(m))?,
+                            AlnPattern | AlnText => write_aln(
+                                m,
+                                matches.pattern(pattern_rank).unwrap(),
+                                text,
+                                *var == AlnPattern,
+                                out,
+                            ),
                             _ => unreachable!(),
                         }
                         out.push(b',');
@@ -276,8 +409,23 @@ impl VarProvider for FindVars {
         &mut self,
         code: &str,
         var_id: usize,
+        store: &mut var::VarStore,
+    ) -> CliResult<bool> {
+        self.register_prefixed(Some("f"), code, var_id, store)
+    }
+
+    fn accepts_prefix(&self, prefix: Option<&str>) -> bool {
+        self.parse_set(prefix).is_some()
+    }
+
+    fn register_prefixed(
+        &mut self,
+        prefix: Option<&str>,
+        code: &str,
+        var_id: usize,
         _: &mut var::VarStore,
     ) -> CliResult<bool> {
+        let set = self.parse_set(prefix).unwrap();
         let (name, pos, group, rank) = self.parse_code(code)?;
 
         let var = match name {
@@ -291,17 +439,32 @@ impl VarProvider for FindVars {
             "dist" => Dist,
             "match" => Match,
             "name" => Name,
+            "strand" => Strand,
+            "subst" => Subst,
+            "ins" => Ins,
+            "del" => Del,
+            "cigar" => Cigar,
+            "aln_pattern" => AlnPattern,
+            "aln_text" => AlnText,
+            "score" => Score,
+            "pvalue" => Pvalue,
+            "ident" => Ident,
             _ => return Ok(false),
         };
 
-        if var != End && var != Dist && var != Name {
-            self.bounds_needed.0 = true;
+        if var.needs_path() {
+            self.path_needed[set] = true;
         }
-        if var != Start && var != Dist && var != Name {
-            self.bounds_needed.1 = true;
+
+        let no_pos = var == Dist || var == Score || var == Pvalue || var == Name || var == Strand;
+        if var != End && !no_pos {
+            self.bounds_needed[set].0 = true;
+        }
+        if var != Start && !no_pos {
+            self.bounds_needed[set].1 = true;
         }
 
-        self.register_match(var, var_id, pos, group, rank)?;
+        self.register_match(var, var_id, pos, group, rank, set)?;
         Ok(true)
     }
 
@@ -309,3 +472,42 @@ impl VarProvider for FindVars {
         !self.vars.is_empty()
     }
 }
+
+fn write_cigar(m: &Match, out: &mut Vec<u8>) -> CliResult<()> {
+    for (op, group) in &m.ops_iter().group_by(|op| *op) {
+        let c = match op {
+            AlignmentOperation::Match => '=',
+            AlignmentOperation::Subst => 'X',
+            AlignmentOperation::Ins => 'I',
+            AlignmentOperation::Del => 'D',
+        };
+        write!(out, "{}{}", group.count(), c)?;
+    }
+    Ok(())
+}
+
+/// Fraction of identical positions in the alignment
+fn identity(m: &Match) -> f64 {
+    let (n, ident) = m.ops_iter().fold((0, 0), |(n, ident), op| {
+        (n + 1, ident + (op == AlignmentOperation::Match) as usize)
+    });
+    if n == 0 {
+        return 0.;
+    }
+    ident as f64 / n as f64
+}
+
+// writes either the aligned pattern or text
+fn write_aln(m: &Match, pattern: &[u8], text: &[u8], write_pattern: bool, out: &mut Vec<u8>) {
+    let mut pattern = pattern.iter();
+    let mut text = text[m.start..m.end].iter();
+    for op in m.ops_iter() {
+        let (p, t) = match op {
+            AlignmentOperation::Match | AlignmentOperation::Subst => (pattern.next(), text.next()),
+            AlignmentOperation::Ins => (pattern.next(), None),
+            AlignmentOperation::Del => (None, text.next()),
+        };
+        let c = if write_pattern { p } else { t };
+        out.push(*c.unwrap_or(&b'-'));
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::mem;
+
+use csv;
+use fxhash::FxHashMap;
+
+use cfg;
+use error::CliResult;
+use lib::kmers::{decode_packed, packed_kmers, LongKmers, MAX_PACKED_K};
+use opt;
+use var::varstring;
+
+static USAGE: &'static str = concat!(
+    "
+Counts the k-mers of DNA/RNA sequences and returns the k-mers with their
+counts, sorted by decreasing count. Alternatively, the k-mer frequency
+histogram is returned. Only k-mers consisting of A, C, G and T/U are
+counted (case insensitive). By default, canonical k-mers are counted,
+meaning that a k-mer and its reverse complement are counted together.
+
+Usage:
+    st kmers [options] [-l <list>...] [<input>...]
+    st kmers (-h | --help)
+    st kmers --help-vars
+
+Options:
+    -K, --kmer-len <k>  K-mer length. K-mers of up to 31 bases are packed into
+                        integers, longer ones require more memory. [default: 21]
+    -s, --strand        Count strand-specific instead of canonical k-mers.
+    -k, --key <key>     Count k-mers separately for each value of a key
+                        (variables allowed). The key is written to the first
+                        column.
+    -f, --per-file      Count separately for each input file (same as
+                        '-k {path}').
+    -n, --top <N>       Only return the <N> most frequent k-mers (of each group).
+    -H, --hist          Return the frequency histogram (frequency, number of
+                        distinct k-mers with this frequency) instead of the
+                        k-mers.
+    -t, --threads <N>   Number of threads [default: 1]
+",
+    common_opts!()
+);
+
+pub fn run() -> CliResult<()> {
+    let args = opt::Args::new(USAGE)?;
+    let cfg = cfg::Config::from_args(&args)?;
+    let num_threads = args.thread_num()?;
+
+    let k: usize = args.value("--kmer-len")?;
+    if k == 0 {
+        return fail!("The k-mer length must be > 0");
+    }
+    let canonical = !args.get_bool("--strand");
+    let key = if args.get_bool("--per-file") {
+        Some("{path}")
+    } else {
+        args.opt_str("--key")
+    };
+    let out = Output {
+        top: args.opt_value("--top")?,
+        hist: args.get_bool("--hist"),
+    };
+
+    cfg.io_writer(|writer, mut vars| {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .from_writer(writer);
+
+        if let Some(key) = key {
+            let key = vars.build(|b| varstring::VarString::var_or_composed(key, b))?;
+
+            let mut groups: FxHashMap<Vec<u8>, Counts> = FxHashMap::default();
+            let mut key_buf = vec![];
+
+            // k-mers are extracted in the worker threads,
+            // and then counted in the main thread together with the key
+            cfg.var_parallel_init::<_, _, _, _, _, KmerBuf>(
+                &mut vars,
+                num_threads,
+                || Ok(()),
+                Default::default,
+                |record, buf, _| {
+                    buf.seq.clear();
+                    record.write_seq(&mut buf.seq);
+                    buf.extract(k, canonical);
+                    Ok(())
+                },
+                |_, buf, vars| {
+                    key_buf.clear();
+                    key.compose(&mut key_buf, vars.symbols());
+                    if !groups.contains_key(&key_buf) {
+                        groups.insert(key_buf.clone(), Counts::new(k));
+                    }
+                    groups.get_mut(&key_buf).unwrap().add_buf(buf, k);
+                    Ok(true)
+                },
+            )?;
+
+            let mut groups: Vec<_> = groups.into_iter().collect();
+            groups.sort_by(|&(ref k1, _), &(ref k2, _)| k1.cmp(k2));
+            for (key, counts) in groups {
+                out.write(&mut csv_writer, Some(&key), counts, k)?;
+            }
+        } else {
+            // With several threads, every record set is counted separately
+            // in a worker thread, and the counts are merged into the total
+            // in the main thread. Otherwise, k-mers are counted directly.
+            let mut counts = Counts::new(k);
+
+            cfg.var_parallel_local::<_, _, _, _, _, ()>(
+                &mut vars,
+                num_threads,
+                || Ok(LocalCounts::new(k)),
+                Default::default,
+                |record, _, local| {
+                    local.buf.seq.clear();
+                    record.write_seq(&mut local.buf.seq);
+                    local.buf.extract(k, canonical);
+                    if num_threads > 1 {
+                        local.counts.add_buf(&local.buf, k);
+                    }
+                    Ok(())
+                },
+                |_, _, local, _| {
+                    if num_threads > 1 {
+                        // only the first record of a set finds the counts
+                        if !local.counts.is_empty() {
+                            counts.merge(&mut local.counts);
+                        }
+                    } else {
+                        counts.add_buf(&local.buf, k);
+                    }
+                    Ok(true)
+                },
+            )?;
+
+            out.write(&mut csv_writer, None, counts, k)?;
+        }
+        Ok(())
+    })
+}
+
+#[derive(Debug, Default)]
+struct KmerBuf {
+    seq: Vec<u8>,
+    packed: Vec<u64>,
+    // k-mers > MAX_PACKED_K, concatenated
+    long: Vec<u8>,
+    long_kmers: LongKmers,
+}
+
+impl KmerBuf {
+    fn extract(&mut self, k: usize, canonical: bool) {
+        if k <= MAX_PACKED_K {
+            let packed = &mut self.packed;
+            packed.clear();
+            packed_kmers(&self.seq, k, canonical, |kmer| packed.push(kmer));
+        } else {
+            let long = &mut self.long;
+            long.clear();
+            self.long_kmers
+                .kmers(&self.seq, k, canonical, |kmer| long.extend_from_slice(kmer));
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Counts {
+    Packed(FxHashMap<u64, u64>),
+    Long(FxHashMap<Vec<u8>, u64>),
+}
+
+impl Counts {
+    fn new(k: usize) -> Counts {
+        if k <= MAX_PACKED_K {
+            Counts::Packed(FxHashMap::default())
+        } else {
+            Counts::Long(FxHashMap::default())
+        }
+    }
+
+    fn add_buf(&mut self, buf: &KmerBuf, k: usize) {
+        match *self {
+            Counts::Packed(ref mut map) => for &kmer in &buf.packed {
+                *map.entry(kmer).or_insert(0) += 1;
+            },
+            Counts::Long(ref mut map) => for kmer in buf.long.chunks(k) {
+                // avoid allocating a new key for every k-mer
+                if let Some(c) = map.get_mut(kmer) {
+                    *c += 1;
+                    continue;
+                }
+                map.insert(kmer.to_vec(), 1);
+            },
+        }
+    }
+
+    /// Adds the counts of `other`, which is empty afterwards.
+    fn merge(&mut self, other: &mut Counts) {
+        match (self, other) {
+            (&mut Counts::Packed(ref mut map), &mut Counts::Packed(ref mut other)) => {
+                merge_maps(map, other)
+            }
+            (&mut Counts::Long(ref mut map), &mut Counts::Long(ref mut other)) => {
+                merge_maps(map, other)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match *self {
+            Counts::Packed(ref map) => map.is_empty(),
+            Counts::Long(ref map) => map.is_empty(),
+        }
+    }
+
+    // returns (k-mer, count) sorted by decreasing count
+    fn into_sorted(self, k: usize) -> Vec<(Vec<u8>, u64)> {
+        let mut out: Vec<_> = match self {
+            Counts::Packed(map) => map
+                .into_iter()
+                .map(|(kmer, count)| {
+                    let mut s = Vec::with_capacity(k);
+                    decode_packed(kmer, k, &mut s);
+                    (s, count)
+                })
+                .collect(),
+            Counts::Long(map) => map.into_iter().collect(),
+        };
+        out.sort_by(|&(ref k1, c1), &(ref k2, c2)| c2.cmp(&c1).then_with(|| k1.cmp(k2)));
+        out
+    }
+
+    fn histogram(&self) -> BTreeMap<u64, u64> {
+        let mut hist = BTreeMap::new();
+        {
+            let mut add = |count: u64| *hist.entry(count).or_insert(0) += 1;
+            match *self {
+                Counts::Packed(ref map) => map.values().for_each(|&c| add(c)),
+                Counts::Long(ref map) => map.values().for_each(|&c| add(c)),
+            }
+        }
+        hist
+    }
+}
+
+fn merge_maps<K: Hash + Eq>(map: &mut FxHashMap<K, u64>, other: &mut FxHashMap<K, u64>) {
+    if map.len() < other.len() {
+        mem::swap(map, other);
+    }
+    for (kmer, count) in other.drain() {
+        *map.entry(kmer).or_insert(0) += count;
+    }
+}
+
+/// Counts of a record set, which are merged into the total
+/// count by the main thread
+struct LocalCounts {
+    counts: Counts,
+    buf: KmerBuf,
+}
+
+impl LocalCounts {
+    fn new(k: usize) -> LocalCounts {
+        LocalCounts {
+            counts: Counts::new(k),
+            buf: KmerBuf::default(),
+        }
+    }
+}
+
+struct Output {
+    top: Option<usize>,
+    hist: bool,
+}
+
+impl Output {
+    fn write<W: ::std::io::Write>(
+        &self,
+        writer: &mut csv::Writer<W>,
+        key: Option<&[u8]>,
+        counts: Counts,
+        k: usize,
+    ) -> CliResult<()> {
+        let mut row: Vec<Vec<u8>> = key.map(|k| vec![k.to_vec()]).unwrap_or_else(Vec::new);
+        let n = row.len();
+        if self.hist {
+            for (freq, num) in counts.histogram() {
+                row.truncate(n);
+                row.push(freq.to_string().into_bytes());
+                row.push(num.to_string().into_bytes());
+                writer.write_record(&row)?;
+            }
+        } else {
+            let sorted = counts.into_sorted(k);
+            let top = self.top.unwrap_or(sorted.len());
+            for (kmer, count) in sorted.into_iter().take(top) {
+                row.truncate(n);
+                row.push(kmer);
+                row.push(count.to_string().into_bytes());
+                writer.write_record(&row)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -11,6 +11,9 @@ Usage:
     st lower [options][-a <attr>...] [-l <list>...] [<input>...]
     st lower (-h | --help)
     st lower --help-vars
+
+Options:
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -18,20 +21,28 @@ Usage:
 pub fn run() -> CliResult<()> {
     let args = opt::Args::new(USAGE)?;
     let cfg = cfg::Config::from_args(&args)?;
+    let num_threads = args.thread_num()?;
 
     cfg.writer(|writer, mut vars| {
-        let mut seq = vec![];
-        cfg.read_sequential_var(&mut vars, |record, vars| {
-            seq.clear();
-            for s in record.seq_segments() {
-                seq.extend(s.iter().cloned().map(|ref mut b| {
-                    b.make_ascii_lowercase();
-                    *b
-                }));
-            }
-            let ucase_rec = SeqQualRecord::new(&record, &seq, None);
-            writer.write(&ucase_rec, vars)?;
-            Ok(true)
-        })
-    })
+        cfg.var_parallel::<_, _, Vec<u8>>(
+            &mut vars,
+            num_threads,
+            |record, seq| {
+                seq.clear();
+                for s in record.seq_segments() {
+                    seq.extend(s.iter().cloned().map(|ref mut b| {
+                        b.make_ascii_lowercase();
+                        *b
+                    }));
+                }
+                Ok(())
+            },
+            |record, seq, vars| {
+                let lcase_rec = SeqQualRecord::new(&record, seq, None);
+                writer.write(&lcase_rec, vars)?;
+                Ok(true)
+            },
+        )
+    })?;
+    Ok(())
 }
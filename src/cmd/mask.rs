@@ -1,3 +1,5 @@
+use std::sync::Mutex;
+
 use error::CliResult;
 use io::SeqQualRecord;
 use lib::rng::*;
@@ -26,6 +28,7 @@ Options:
     -e, --exclude       Exclusive range: excludes start and end positions
                         from the masked sequence.
     -0                  Interpret range as 0-based, with the end not included.
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -39,42 +42,51 @@ pub fn run() -> CliResult<()> {
     let rng0 = args.get_bool("-0");
     let exclusive = args.get_bool("--exclude");
     let unmask = args.get_bool("--unmask");
+    let num_threads = args.thread_num()?;
 
     cfg.writer(|writer, mut vars| {
-        let mut ranges = VarRanges::from_str(ranges, &mut vars)?;
-        let mut seq = vec![];
+        // every record set gets its own copy (VarRanges is not Sync)
+        let ranges = Mutex::new(VarRanges::from_str(ranges, &mut vars)?);
 
-        cfg.read_sequential_var(&mut vars, |record, vars| {
-            let seqlen = record.seq_len();
+        cfg.var_parallel_edit::<_, _, _, _, _, Vec<u8>>(
+            &mut vars,
+            num_threads,
+            || Ok(ranges.lock().unwrap().clone()),
+            Default::default,
+            |record, seq, ranges, vars| {
+                let seqlen = record.seq_len();
 
-            seq.clear();
-            for s in record.seq_segments() {
-                seq.extend_from_slice(s);
-            }
+                seq.clear();
+                for s in record.seq_segments() {
+                    seq.extend_from_slice(s);
+                }
 
-            let calc_ranges = ranges.get(seqlen, rng0, exclusive, vars.symbols())?;
+                let calc_ranges = ranges.get(seqlen, rng0, exclusive, vars.symbols())?;
 
-            if let Some(h) = hard_mask {
-                for &(start, end) in calc_ranges {
-                    for c in &mut seq[start..end] {
-                        *c = h;
+                if let Some(h) = hard_mask {
+                    for &(start, end) in calc_ranges {
+                        for c in &mut seq[start..end] {
+                            *c = h;
+                        }
                     }
-                }
-            } else {
-                for &(start, end) in calc_ranges {
-                    for c in &mut seq[start..end] {
-                        if unmask {
-                            c.make_ascii_uppercase()
-                        } else {
-                            c.make_ascii_lowercase()
-                        };
+                } else {
+                    for &(start, end) in calc_ranges {
+                        for c in &mut seq[start..end] {
+                            if unmask {
+                                c.make_ascii_uppercase()
+                            } else {
+                                c.make_ascii_lowercase()
+                            };
+                        }
                     }
                 }
-            }
-
-            writer.write(&SeqQualRecord::new(&record, &seq, None), vars)?;
-
-            Ok(true)
-        })
-    })
+                Ok(())
+            },
+            |record, seq, vars| {
+                writer.write(&SeqQualRecord::new(&record, seq, None), vars)?;
+                Ok(true)
+            },
+        )
+    })?;
+    Ok(())
 }
@@ -1,3 +1,6 @@
+pub mod align;
+pub mod aln;
+pub mod consensus;
 pub mod count;
 pub mod pass;
 
@@ -12,10 +15,12 @@ pub mod del;
 pub mod filter;
 pub mod find;
 pub mod interleave;
+pub mod kmers;
 pub mod lower;
 pub mod mask;
 pub mod replace;
 pub mod revcomp;
+pub mod screen;
 pub mod set;
 pub mod split;
 pub mod stat;
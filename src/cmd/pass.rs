@@ -11,6 +11,9 @@ Usage:
     st (pass|.) [options][-a <attr>...][-l <list>...] [<input>...]
     st (pass|.) (-h | --help)
     st (pass|.) --help-vars
+
+Options:
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -18,11 +21,18 @@ Usage:
 pub fn run() -> CliResult<()> {
     let args = opt::Args::new(USAGE)?;
     let cfg = cfg::Config::from_args(&args)?;
+    let num_threads = args.thread_num()?;
 
     cfg.writer(|writer, mut vars| {
-        cfg.read_sequential_var(&mut vars, |record, vars| {
-            writer.write(&record, vars)?;
-            Ok(true)
-        })
-    })
+        cfg.var_parallel::<_, _, ()>(
+            &mut vars,
+            num_threads,
+            |_, _| Ok(()),
+            |record, _, vars| {
+                writer.write(&record, vars)?;
+                Ok(true)
+            },
+        )
+    })?;
+    Ok(())
 }
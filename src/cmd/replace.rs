@@ -79,7 +79,7 @@ fn run_replace<R: Replacer + Sync>(
     cfg.writer(|writer, mut vars| {
         cfg.var_parallel::<_, _, RecordEditor>(
             &mut vars,
-            num_threads - 1,
+            num_threads,
             |record, editor| {
                 editor.edit_with_val(attr, &record, false, |text, out| {
                     replacer.replace(text, replacement, out)
@@ -18,7 +18,7 @@ Usage:
     st revcomp --help-vars
 
 Options:
-    -t, --threads <N>   Number of threads to use [default: 1]
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -31,7 +31,7 @@ pub fn run() -> CliResult<()> {
     cfg.writer(|writer, mut vars| {
         cfg.var_parallel::<_, _, Box<(Vec<u8>, Vec<u8>, bool)>>(
             &mut vars,
-            num_threads - 1,
+            num_threads,
             |record, data| {
                 let (ref mut seq, ref mut qual, ref mut has_qual) = *data.deref_mut();
                 seq.clear();
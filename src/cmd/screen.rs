@@ -0,0 +1,290 @@
+use fxhash::FxHashSet;
+use seq_io::fasta::{self, Record as FastaRecord};
+
+use cfg;
+use error::CliResult;
+use io::input;
+use io::output::writer::Writer;
+use io::Record;
+use lib::kmers::{packed_kmers, LongKmers, MAX_PACKED_K};
+use opt;
+use var::{self, VarHelp, VarProvider};
+
+static USAGE: &'static str = concat!(
+    "
+Screens sequences against a set of reference sequences (e.g. contaminants
+such as PhiX, adapters or host sequences) by counting the k-mers shared with
+the reference. Canonical k-mers are compared, meaning that matches on both
+strands are found. Only k-mers consisting of A, C, G and T/U are used.
+
+Usage:
+    st screen [options] [-a <attr>...] [-l <list>...] <reference> [<input>...]
+    st screen (-h | --help)
+    st screen --help-vars
+
+Options:
+    <reference>         Reference sequences in FASTA format
+    -K, --kmer-len <k>  K-mer length [default: 21]
+    -n, --min-hits <n>  Minimum number of k-mers shared with the reference for
+                        a sequence to be considered a hit [default: 1]
+    --min-frac <f>      Minimum fraction of the k-mers of a sequence that have
+                        to be found in the reference for a hit [default: 0]
+    -f, --filter        Keep only sequences matching the reference
+    -e, --exclude       Exclude sequences matching the reference
+    --dropped <file>    Output file for sequences that were removed by filtering.
+                        The extension is autorecognized if possible, fallback
+                        is the input format.
+    -t, --threads <N>   Number of threads [default: 1]
+",
+    common_opts!()
+);
+
+pub fn run() -> CliResult<()> {
+    let args = opt::Args::new(USAGE)?;
+    let cfg = cfg::Config::from_args_with_help(&args, &ScreenVarHelp)?;
+    let num_threads = args.thread_num()?;
+
+    let k: usize = args.value("--kmer-len")?;
+    if k == 0 {
+        return fail!("The k-mer length must be > 0");
+    }
+    let min_hits: usize = args.value("--min-hits")?;
+    let min_frac: f64 = args.value("--min-frac")?;
+    let filter = if args.get_bool("--filter") {
+        Some(true)
+    } else if args.get_bool("--exclude") {
+        Some(false)
+    } else {
+        None
+    };
+    let dropped_file = args.opt_str("--dropped");
+
+    let reference = KmerSet::from_fasta(args.get_str("<reference>"), k)?;
+
+    cfg.writer_with(
+        |_| Ok(ScreenVars::new()),
+        |writer, mut vars, mut screen_vars| {
+            if filter.is_none() && !screen_vars.has_vars() {
+                return fail!(
+                    "Screen command does nothing. Use -f/-e for filtering or -a for writing \
+                     attributes."
+                );
+            }
+
+            let mut dropped_file = if let Some(f) = dropped_file {
+                Some(cfg.other_writer(f, Some(&mut vars), Some(&mut screen_vars))?)
+            } else {
+                None
+            };
+
+            let reference = &reference;
+
+            cfg.var_parallel_init(
+                &mut vars,
+                num_threads,
+                || Ok(KmerScratch::default()),
+                Hits::default,
+                |record, hits, scratch| {
+                    // k-mer lookup in worker threads
+                    *hits = reference.hits(record, scratch);
+                    Ok(())
+                },
+                |record, hits, vars| {
+                    screen_vars.set_with(hits, &mut vars.mut_data().symbols);
+
+                    let is_hit = hits.is_hit(min_hits, min_frac);
+                    if let Some(keep) = filter {
+                        if is_hit ^ keep {
+                            if let Some(ref mut f) = dropped_file {
+                                f.write(&record, vars)?;
+                            }
+                            return Ok(true);
+                        }
+                    }
+                    writer.write(&record, vars)?;
+                    Ok(true)
+                },
+            )?;
+            Ok(())
+        },
+    )
+}
+
+#[derive(Debug)]
+enum KmerSet {
+    Packed(FxHashSet<u64>, usize),
+    Long(FxHashSet<Vec<u8>>, usize),
+}
+
+impl KmerSet {
+    fn from_fasta(path: &str, k: usize) -> CliResult<KmerSet> {
+        let mut reader = fasta::Reader::new(input::file_reader(path)?);
+        let mut set = if k <= MAX_PACKED_K {
+            KmerSet::Packed(FxHashSet::default(), k)
+        } else {
+            KmerSet::Long(FxHashSet::default(), k)
+        };
+        let mut seq = vec![];
+        let mut long_kmers = LongKmers::new();
+        let mut n = 0;
+        while let Some(r) = reader.next() {
+            let r = r?;
+            seq.clear();
+            for line in r.seq_lines() {
+                seq.extend_from_slice(line);
+            }
+            match set {
+                KmerSet::Packed(ref mut s, k) => packed_kmers(&seq, k, true, |kmer| {
+                    s.insert(kmer);
+                }),
+                KmerSet::Long(ref mut s, k) => long_kmers.kmers(&seq, k, true, |kmer| {
+                    if !s.contains(kmer) {
+                        s.insert(kmer.to_vec());
+                    }
+                }),
+            }
+            n += 1;
+        }
+        if n == 0 {
+            return fail!("Reference file is empty.");
+        }
+        Ok(set)
+    }
+
+    fn hits(&self, record: &Record, scratch: &mut KmerScratch) -> Hits {
+        scratch.seq.clear();
+        record.write_seq(&mut scratch.seq);
+        let mut h = Hits::default();
+        match *self {
+            KmerSet::Packed(ref s, k) => packed_kmers(&scratch.seq, k, true, |kmer| {
+                h.total += 1;
+                if s.contains(&kmer) {
+                    h.hits += 1;
+                }
+            }),
+            KmerSet::Long(ref s, k) => scratch.long_kmers.kmers(&scratch.seq, k, true, |kmer| {
+                h.total += 1;
+                if s.contains(kmer) {
+                    h.hits += 1;
+                }
+            }),
+        }
+        h
+    }
+}
+
+#[derive(Debug, Default)]
+struct KmerScratch {
+    seq: Vec<u8>,
+    long_kmers: LongKmers,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Hits {
+    hits: usize,
+    total: usize,
+}
+
+impl Hits {
+    fn frac(&self) -> Option<f64> {
+        if self.total == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / self.total as f64)
+        }
+    }
+
+    fn is_hit(&self, min_hits: usize, min_frac: f64) -> bool {
+        self.hits > 0 && self.hits >= min_hits && self.frac().unwrap_or(0.) >= min_frac
+    }
+}
+
+pub struct ScreenVarHelp;
+
+impl VarHelp for ScreenVarHelp {
+    fn name(&self) -> &'static str {
+        "K-mer screening variables"
+    }
+    fn usage(&self) -> &'static str {
+        "kmer:<variable>"
+    }
+    fn vars(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            ("kmer:hits", "Number of k-mers found in the reference"),
+            ("kmer:total", "Total number of k-mers in the sequence"),
+            (
+                "kmer:frac",
+                "Fraction of the k-mers found in the reference (undefined if the \
+                 sequence has no valid k-mer)",
+            ),
+        ])
+    }
+    fn examples(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            (
+                "Removing reads with at least 5 k-mers matching PhiX",
+                "st screen -e -n 5 phix.fasta reads.fq > clean.fq",
+            ),
+            (
+                "Annotating sequences with the fraction of k-mers found in a host genome",
+                "st screen -a host_frac={kmer:frac} host.fasta seqs.fa > annotated.fa",
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ScreenVar {
+    Hits,
+    Total,
+    Frac,
+}
+
+#[derive(Debug)]
+pub struct ScreenVars {
+    vars: Vec<(ScreenVar, usize)>,
+}
+
+impl ScreenVars {
+    pub fn new() -> ScreenVars {
+        ScreenVars { vars: vec![] }
+    }
+
+    fn set_with(&self, hits: &Hits, symbols: &mut var::symbols::Table) {
+        for &(var, id) in &self.vars {
+            match var {
+                ScreenVar::Hits => symbols.set_int(id, hits.hits as i64),
+                ScreenVar::Total => symbols.set_int(id, hits.total as i64),
+                ScreenVar::Frac => match hits.frac() {
+                    Some(f) => symbols.set_float(id, f),
+                    None => symbols.set_none(id),
+                },
+            }
+        }
+    }
+}
+
+impl VarProvider for ScreenVars {
+    fn prefix(&self) -> Option<&str> {
+        Some("kmer")
+    }
+
+    fn name(&self) -> &'static str {
+        "k-mer screening"
+    }
+
+    fn register_var(&mut self, name: &str, id: usize, _: &mut var::VarStore) -> CliResult<bool> {
+        let var = match name {
+            "hits" => ScreenVar::Hits,
+            "total" => ScreenVar::Total,
+            "frac" => ScreenVar::Frac,
+            _ => return Ok(false),
+        };
+        self.vars.push((var, id));
+        Ok(true)
+    }
+
+    fn has_vars(&self) -> bool {
+        !self.vars.is_empty()
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Mutex;
+
 use error::CliResult;
 use io::{RecordEditor, SeqAttr};
 use opt;
@@ -18,6 +20,7 @@ Options:
     -i, --id <expr>     New ID (variables allowed)
     -d, --desc <expr>   New description (variables allowed)
     -s, --seq <expr>    New sequence (variables allowed)
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -25,6 +28,7 @@ Options:
 pub fn run() -> CliResult<()> {
     let args = opt::Args::new(USAGE)?;
     let cfg = cfg::Config::from_args(&args)?;
+    let num_threads = args.thread_num()?;
 
     let mut replacements = vec![];
     if let Some(string) = args.opt_str("--id") {
@@ -47,16 +51,26 @@ pub fn run() -> CliResult<()> {
             })
             .collect::<CliResult<_>>()?;
 
-        let mut editor = RecordEditor::new();
-
-        cfg.read_sequential_var(&mut vars, |record, vars| {
-            for &(ref expr, attr) in &replacements {
-                let val = editor.edit(attr);
-                expr.compose(val, vars.symbols())
-            }
+        // every record set gets its own copy (VarString is not Sync)
+        let replacements = Mutex::new(replacements);
 
-            writer.write(&editor.rec(&record), vars)?;
-            Ok(true)
-        })
-    })
+        cfg.var_parallel_edit(
+            &mut vars,
+            num_threads,
+            || Ok(replacements.lock().unwrap().clone()),
+            RecordEditor::new,
+            |_, editor, replacements, vars| {
+                for &(ref expr, attr) in replacements.iter() {
+                    let val = editor.edit(attr);
+                    expr.compose(val, vars.symbols())
+                }
+                Ok(())
+            },
+            |record, editor, vars| {
+                writer.write(&editor.rec(&record), vars)?;
+                Ok(true)
+            },
+        )
+    })?;
+    Ok(())
 }
@@ -26,6 +26,7 @@ Options:
                         'f_{split:chunk}.{default_ext}'. This is actually a
                         variable string which can be changed using -o/--output.
     -p, --parents       Automatically create all parent directories found in -o
+    -t, --threads <N>   Number of threads [default: 1]
 
 ",
     common_opts!()
@@ -39,6 +40,7 @@ pub fn run() -> CliResult<()> {
     let out = args.opt_str("--output");
     let parents = args.get_bool("--parents");
     let verbose = args.get_bool("--verbose");
+    let num_threads = args.thread_num()?;
 
     let (key, limit) = if let Some(n) = n {
         (out.unwrap_or("f_{split:chunk}.{default_ext}"), n)
@@ -53,12 +55,16 @@ pub fn run() -> CliResult<()> {
     let var_key = vars.build_with(Some(&mut chunk_vars), |b| {
         varstring::VarString::var_or_composed(key, b)
     })?;
+    // All output variables have to be known before the variables are copied
+    // to the worker threads, even if no file has been created yet.
+    cfg.register_output_vars(&mut vars, Some(&mut chunk_vars))?;
 
     let mut outfiles: FxHashMap<_, Box<Writer<_>>> = FxHashMap::default();
     let mut path = vec![];
 
-    cfg.read_sequential_var(&mut vars, |record, mut vars| {
-        // update chunk number variable
+    cfg.var_parallel::<_, _, ()>(&mut vars, num_threads, |_, _| Ok(()), |record, _, vars| {
+        // update chunk number variable (depends on the order of the records,
+        // therefore done in the main thread)
         chunk_vars.increment(&mut vars.mut_data().symbols)?;
 
         // compose key
@@ -87,17 +93,7 @@ pub fn run() -> CliResult<()> {
             create_dir_all(par)?;
         }
 
-        let w = cfg.other_writer(
-            path_str,
-            // only register output variables the first time since different files
-            // do not have different variable sets
-            if outfiles.is_empty() {
-                Some(&mut vars)
-            } else {
-                None
-            },
-            None,
-        )?;
+        let w = cfg.other_writer(path_str, Some(vars), Some(&mut chunk_vars))?;
         outfiles.insert(path.clone(), w);
 
         let writer = outfiles.get_mut(&path).unwrap();
@@ -157,11 +153,12 @@ impl ChunkNum {
     fn increment(&mut self, symbols: &mut symbols::Table) -> CliResult<()> {
         if let Some(var_id) = self.id {
             self.seq_num += 1;
-            if self.chunk_num == 0 || self.seq_num > self.limit {
+            if self.chunk_num == 0 || (self.limit != 0 && self.seq_num > self.limit) {
                 self.seq_num = 1;
                 self.chunk_num += 1;
-                symbols.set_int(var_id, self.chunk_num as i64);
             }
+            // always set, since the symbol table may come from a worker thread
+            symbols.set_int(var_id, self.chunk_num as i64);
         }
         Ok(())
     }
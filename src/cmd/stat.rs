@@ -10,12 +10,13 @@ Returns per sequence statistics as tab delimited list. All statistical variables
 (s:<variable>) can be used.
 
 Usage:
-    st stat <stats> [<input>...]
+    st stat [options] <stats> [<input>...]
     st stat (-h | --help)
 
 Options:
     <stats>             Comma delimited list of statistics. The 's:' prefix
                         is not necessary.
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -24,6 +25,7 @@ pub fn run() -> CliResult<()> {
     let args = opt::Args::new(USAGE)?;
     let cfg = cfg::Config::from_args(&args)?;
     let stats: Vec<_> = args.get_str("<stats>").split(',').collect();
+    let num_threads = args.thread_num()?;
 
     cfg.io_writer(|writer, mut vars| {
         let mut csv_writer = csv::WriterBuilder::new()
@@ -43,23 +45,29 @@ pub fn run() -> CliResult<()> {
         // CSV row
         let mut row = vec![vec![]; stats.len() + 1];
 
-        cfg.read_sequential_var(&mut vars, |record, vars| {
-            {
-                let mut row_iter = row.iter_mut();
-                let id = row_iter.next().unwrap();
-                id.clear();
-                id.extend_from_slice(record.id_bytes());
+        cfg.var_parallel::<_, _, ()>(
+            &mut vars,
+            num_threads,
+            |_, _| Ok(()),
+            |record, _, vars| {
+                {
+                    let mut row_iter = row.iter_mut();
+                    let id = row_iter.next().unwrap();
+                    id.clear();
+                    id.extend_from_slice(record.id_bytes());
 
-                for (&var_id, ref mut field) in var_ids.iter().zip(row_iter) {
-                    let val = vars.symbols().get_text(var_id).unwrap_or(b"");
-                    field.clear();
-                    field.extend_from_slice(val);
+                    for (&var_id, ref mut field) in var_ids.iter().zip(row_iter) {
+                        let val = vars.symbols().get_text(var_id).unwrap_or(b"");
+                        field.clear();
+                        field.extend_from_slice(val);
+                    }
                 }
-            }
 
-            csv_writer.write_record(&row)?;
+                csv_writer.write_record(&row)?;
 
-            Ok(true)
-        })
-    })
+                Ok(true)
+            },
+        )
+    })?;
+    Ok(())
 }
@@ -1,3 +1,5 @@
+use std::sync::Mutex;
+
 use error::CliResult;
 use io::{Record, SeqQualRecord};
 use lib::rng::*;
@@ -21,6 +23,7 @@ Options:
     -e, --exclude       Exclusive trim range: excludes start and end positions
                         from the output sequence.
     -0                  Interpret range as 0-based, with the end not included.
+    -t, --threads <N>   Number of threads [default: 1]
 ",
     common_opts!()
 );
@@ -32,33 +35,36 @@ pub fn run() -> CliResult<()> {
     let range = args.get_str("<range>");
     let rng0 = args.get_bool("-0");
     let exclusive = args.get_bool("--exclude");
+    let num_threads = args.thread_num()?;
 
     cfg.writer(|writer, mut vars| {
-        let mut out_seq = vec![];
-        let mut out_qual = vec![];
-
-        let mut rng = VarRange::from_str(range, &mut vars)?;
-
-        cfg.read_sequential_var(&mut vars, |record, vars| {
-            let seqlen = record.seq_len();
-
-            let (start, end) = rng.get(seqlen, rng0, exclusive, vars.symbols())?;
-
-            let rec = trim(&record, start, end, &mut out_seq, &mut out_qual);
-
-            writer.write(&rec, vars)?;
-            Ok(true)
-        })
-    })
+        // every record set gets its own copy (VarRange is not Sync)
+        let rng = Mutex::new(VarRange::from_str(range, &mut vars)?);
+
+        cfg.var_parallel_edit::<_, _, _, _, _, (Vec<u8>, Vec<u8>)>(
+            &mut vars,
+            num_threads,
+            || Ok(rng.lock().unwrap().clone()),
+            Default::default,
+            |record, &mut (ref mut out_seq, ref mut out_qual), rng, vars| {
+                let seqlen = record.seq_len();
+                let (start, end) = rng.get(seqlen, rng0, exclusive, vars.symbols())?;
+                trim(record, start, end, out_seq, out_qual);
+                Ok(())
+            },
+            |record, &mut (ref out_seq, ref out_qual), vars| {
+                let qual = record.qual().map(|_| out_qual.as_slice());
+                let rec = SeqQualRecord::new(&record, out_seq, qual);
+                writer.write(&rec, vars)?;
+                Ok(true)
+            },
+        )
+    })?;
+    Ok(())
 }
 
-fn trim<'r>(
-    record: &'r Record,
-    start: usize,
-    end: usize,
-    out_seq: &'r mut Vec<u8>,
-    out_qual: &'r mut Vec<u8>,
-) -> SeqQualRecord<'r, &'r Record> {
+/// Copies the trimmed sequence (and qualities if present) to the output vectors
+fn trim(record: &Record, start: usize, end: usize, out_seq: &mut Vec<u8>, out_qual: &mut Vec<u8>) {
     out_seq.clear();
 
     if let Some(qual) = record.qual() {
@@ -69,7 +75,6 @@ fn trim<'r>(
 
         out_seq.extend_from_slice(&seq[start..end]);
         out_qual.extend_from_slice(&qual[start..end]);
-        SeqQualRecord::new(record, out_seq, Some(out_qual))
     } else {
         let mut s = start;
         let mut e = end;
@@ -93,6 +98,5 @@ fn trim<'r>(
             s = 0;
             e -= seq.len();
         }
-        SeqQualRecord::new(record, out_seq, None)
     }
 }
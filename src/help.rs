@@ -4,8 +4,12 @@ macro_rules! common_opts {
 Input options:
     --fmt <format>      Input format: fasta(default), fastq (fastq-illumina,
                         fastq-solexa), or csv / tsv (=txt).
-                        Compression: <format>.<compression> (.gz, .bz2 or .lz4).
-                        Only needed if format cannot be guessed from extension.
+                        Compression: <format>.<compression> (.gz, .bgz, .bz2,
+                        .lz4, .zst or .xz), only needed for output. On input,
+                        it is recognized automatically.
+                        Only needed if the format is not correctly recognized
+                        from the content (FASTA, FASTQ incl. quality encoding,
+                        CSV/TSV) or the file extension.
     --fields <fields>   CSV fields: 'id,seq,desc' (in order) or 'id:2,desc:6,seq:9'
                         (col. num.) or headers: 'id:id,seq:sequence,desc:desc'
                         [default: id,seq,desc]
@@ -20,18 +24,25 @@ Input options:
 
 Output options:
     -o, --output <f>    Write output to <file> instead of STDOUT [default: -].
-    --to <outformat>    Output format and compression. See --fmt. Only needed
-                        if not guessed from the extension (default: input format).
+    --to <outformat>    Output format and compression. See --fmt. Output can
+                        also be json or jsonl (=ndjson, one object per line).
+                        Only needed if not guessed from the extension
+                        (default: input format).
     --wrap <width>      Wrap FASTA sequences to maximum <width> characters
+    --out-header <h>    Template for the complete FASTA/FASTQ header
+                        (variables allowed), e.g. '{id};size={a:size}'
     --out-delim <d>     TSV/CSV delimiter. Defaults: '\t' for tsv/txt; ',' for csv
-    --outfields <f>     TSV/CSV fields (variables allowed). (default: input fields
+    --outfields <f>     TSV/CSV fields or JSON keys (variables allowed). 'qual'
+                        writes Phred scores to JSON. (default: input fields
                         or 'id,seq,desc')
     --to-fa             FASTA output. Short for: '--to fasta'
     --to-fq             FASTQ output. Short for: '--to fastq'
     --to-csv <fields>   CSV output. Short for '--to csv --outfields <f>'
     --to-tsv <fields>   TSV output. Short for '--to tsv --outfields <f>'
-    --compr-level <l>   Level for compressed output. 1-9 for GZIP/BZIP2 and
-                        1-21 for ZSTANDARD
+    --to-json <fields>  JSON output. Short for '--to json --outfields <f>'
+    --to-jsonl <f>      JSON lines output. Short for '--to jsonl --outfields <f>'
+    --compr-level <l>   Level for compressed output. 0-9 for GZIP/BGZF and XZ,
+                        1-9 for BZIP2, 0-12 for LZ4 and 0-22 for ZSTANDARD
     --qual-out <file>   Path to QUAL output file with quality scores
 
 Attribute options:
@@ -40,6 +51,9 @@ Attribute options:
     --adelim <delim>    Attribute delimiter inserted before. If not a space,
                         attributes are appended to the ID (default: ' ')
     --aval-delim <d>    Delimiter between attribute names and values [default: =]
+    --attr-fmt <fmt>    Attribute format, overrides the delimiters: 'desc'
+                        (' key=value'), 'usearch' (>id;key=value;), 'sam'
+                        (' KY:Z:value') or 'colon' (' key:value')
 
 Associated lists:
     -l, --list <path>   Path to list with metadata (multiple -l args possible)
@@ -63,6 +77,10 @@ Advanced Options:
     --write-thread      Write in a different thread. Enabled with compressed output.
     --read-tbufsize S   Buffer size of threaded reader (default: auto)
     --write-tbufsize S  Buffer size of threaded reader (default: auto)
+    --compr-threads N   Number of threads used for compressing GZIP, BGZF and
+                        ZSTD output and for decompressing BGZF input. GZIP
+                        output is then written in independent blocks.
+                        [default: 1]
 "
     };
 }
@@ -77,6 +95,7 @@ Information about sequences
     view        Colored sequence view
     count       Returns the sequence count
     stat        Per-sequence statistics
+    kmers       Count k-mers or return the k-mer frequency histogram
 
 Subsetting / shuffling sequences
     head        Return the first N sequences
@@ -84,12 +103,14 @@ Subsetting / shuffling sequences
     slice       Get a slice of the sequences within a defined range
     sample      Get a random subset of sequences
     filter      Filter based on different criteria
+    screen      Keep or exclude sequences sharing k-mers with a reference
     split       Distribute sequences into multiple files
     interleave  Interleave seqs. from multiple files
 
 Searching and replacing
     find        Find one or more patterns with optional filtering/replacement
     replace     Fast pattern replacement
+    align       Pairwise alignment to one or more reference sequences
 
 Modifying commands
     set         Set a new sequence and/or header
@@ -100,6 +121,8 @@ Modifying commands
     lower       Convert sequences to lowercase (soft mask)
     revcomp     Reverse complement DNA sequences
     concat      Concatenate seqs. from multiple files
+    consensus   Consensus sequences of groups of sequences
+    aln         Multiple sequence alignment utilities
 
 For information about how to use a command use
     st <command> -h/--help
@@ -115,7 +138,7 @@ List and explain available variables:
 pub static USAGE: &'static str = concat!(
     "
 Tool for processing of biological sequences. It can read and write the formats
-FASTA, FASTQ and CSV/TSV.
+FASTA, FASTQ and CSV/TSV, and write JSON.
 
 Usage:
     st <command> [<opts>...]
@@ -0,0 +1,267 @@
+//! Blocked GZIP (BGZF): a series of GZIP members containing at most 64 KiB
+//! of data each, with the compressed size of every member stored in the 'BC'
+//! extra field. The blocks can thus be compressed and decompressed
+//! independently in different threads.
+
+use std::cmp::min;
+use std::io::{self, Read, Write};
+use std::mem::replace;
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{self, Crc};
+
+use lib::ordered_pool::OrderedPool;
+
+/// Maximum amount of data per block (same as in htslib)
+pub const MAX_BLOCK_DATA: usize = 0xff00;
+const MAX_BLOCK_SIZE: usize = 1 << 16;
+const HEADER_LEN: usize = 18;
+const FOOTER_LEN: usize = 8;
+
+/// Empty block, which marks the end of a BGZF file
+pub const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0, 0x1b, 0, 0x03, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+/// Returns true if `start` is the start of a BGZF block (at least 16 bytes needed)
+pub fn is_bgzf(start: &[u8]) -> bool {
+    start.len() >= 16
+        && start.starts_with(&[0x1f, 0x8b, 0x08])
+        && start[3] & 4 != 0
+        && LittleEndian::read_u16(&start[10..12]) >= 6
+        && &start[12..16] == b"BC\x02\x00"
+}
+
+pub fn compress_block(data: &[u8], level: u32) -> io::Result<Vec<u8>> {
+    debug_assert!(data.len() <= MAX_BLOCK_DATA);
+    let mut deflated = deflate(data, level)?;
+    if deflated.len() + HEADER_LEN + FOOTER_LEN > MAX_BLOCK_SIZE {
+        // incompressible data: store uncompressed instead
+        deflated = deflate(data, 0)?;
+    }
+    let bsize = HEADER_LEN + deflated.len() + FOOTER_LEN;
+    let mut block = Vec::with_capacity(bsize);
+    block.extend_from_slice(&EOF_BLOCK[..16]);
+    block.write_u16::<LittleEndian>((bsize - 1) as u16)?;
+    block.extend_from_slice(&deflated);
+    let mut crc = Crc::new();
+    crc.update(data);
+    block.write_u32::<LittleEndian>(crc.sum())?;
+    block.write_u32::<LittleEndian>(data.len() as u32)?;
+    Ok(block)
+}
+
+fn deflate(data: &[u8], level: u32) -> io::Result<Vec<u8>> {
+    let mut e = DeflateEncoder::new(
+        Vec::with_capacity(data.len() / 2),
+        flate2::Compression::new(level),
+    );
+    e.write_all(data)?;
+    e.finish()
+}
+
+fn invalid_block(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid BGZF block: {}", msg))
+}
+
+/// Reads the next compressed block into `block`. Returns false if the end
+/// of the input was reached.
+pub fn read_block<R: Read>(rdr: &mut R, block: &mut Vec<u8>) -> io::Result<bool> {
+    block.clear();
+    rdr.take(12).read_to_end(block)?;
+    if block.is_empty() {
+        return Ok(false);
+    }
+    if block.len() < 12 || !block.starts_with(&[0x1f, 0x8b, 0x08]) || block[3] & 4 == 0 {
+        return Err(invalid_block("not a BGZF file, or it contains other GZIP data"));
+    }
+    let xlen = LittleEndian::read_u16(&block[10..12]) as usize;
+    rdr.take(xlen as u64).read_to_end(block)?;
+    if block.len() < 12 + xlen {
+        return Err(invalid_block("unexpected end of input"));
+    }
+    // find the block size in the extra fields
+    let mut bsize = None;
+    let mut extra = &block[12..];
+    while extra.len() >= 4 {
+        let len = LittleEndian::read_u16(&extra[2..4]) as usize;
+        if &extra[..2] == b"BC" && len == 2 && extra.len() >= 6 {
+            bsize = Some(LittleEndian::read_u16(&extra[4..6]) as usize + 1);
+        }
+        extra = &extra[min(4 + len, extra.len())..];
+    }
+    let bsize = match bsize {
+        Some(s) if s >= block.len() + FOOTER_LEN => s,
+        _ => return Err(invalid_block("missing or invalid block size")),
+    };
+    let remaining = bsize - block.len();
+    rdr.take(remaining as u64).read_to_end(block)?;
+    if block.len() < bsize {
+        return Err(invalid_block("unexpected end of input"));
+    }
+    Ok(true)
+}
+
+/// Decompresses a block obtained with `read_block` and verifies the checksum
+pub fn decompress_block(block: &[u8]) -> io::Result<Vec<u8>> {
+    let xlen = LittleEndian::read_u16(&block[10..12]) as usize;
+    let footer = &block[block.len() - FOOTER_LEN..];
+    let crc_sum = LittleEndian::read_u32(&footer[..4]);
+    let isize = LittleEndian::read_u32(&footer[4..]) as usize;
+    let mut data = Vec::with_capacity(isize);
+    DeflateDecoder::new(&block[12 + xlen..block.len() - FOOTER_LEN]).read_to_end(&mut data)?;
+    let mut crc = Crc::new();
+    crc.update(&data);
+    if data.len() != isize || crc.sum() != crc_sum {
+        return Err(invalid_block("corrupt data (checksum mismatch)"));
+    }
+    Ok(data)
+}
+
+type BlockPool = OrderedPool<Vec<u8>, io::Result<Vec<u8>>>;
+
+/// Writes BGZF blocks, which are compressed in `n_threads` threads.
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    pool: BlockPool,
+    eof_block: bool,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    /// If `eof_block` is true, the empty block marking the end of a
+    /// BGZF file is written in `finish()`.
+    pub fn new(inner: W, level: u32, n_threads: u32, eof_block: bool) -> BgzfWriter<W> {
+        BgzfWriter {
+            inner: inner,
+            buf: Vec::with_capacity(MAX_BLOCK_DATA),
+            pool: OrderedPool::new(n_threads, move |data: Vec<u8>| compress_block(&data, level)),
+            eof_block: eof_block,
+        }
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.pool.is_full() {
+            if let Some(block) = self.pool.recv() {
+                self.inner.write_all(&block?)?;
+            }
+        }
+        let data = replace(&mut self.buf, Vec::with_capacity(MAX_BLOCK_DATA));
+        self.pool.submit(data);
+        Ok(())
+    }
+
+    // writes all pending blocks
+    fn write_pending(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.write_block()?;
+        }
+        while let Some(block) = self.pool.recv() {
+            self.inner.write_all(&block?)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_pending()?;
+        if self.eof_block {
+            self.inner.write_all(&EOF_BLOCK)?;
+        }
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = min(buf.len(), MAX_BLOCK_DATA - self.buf.len());
+        self.buf.extend_from_slice(&buf[..n]);
+        if self.buf.len() == MAX_BLOCK_DATA {
+            self.write_block()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.inner.flush()
+    }
+}
+
+/// Reads BGZF data, blocks are decompressed in `n_threads` threads.
+pub struct BgzfReader<R: Read> {
+    inner: R,
+    pool: BlockPool,
+    data: io::Cursor<Vec<u8>>,
+    finished: bool,
+}
+
+impl<R: Read> BgzfReader<R> {
+    pub fn new(inner: R, n_threads: u32) -> BgzfReader<R> {
+        BgzfReader {
+            inner: inner,
+            pool: OrderedPool::new(n_threads, |block: Vec<u8>| decompress_block(&block)),
+            data: io::Cursor::new(vec![]),
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.data.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            while !self.finished && !self.pool.is_full() {
+                let mut block = vec![];
+                if read_block(&mut self.inner, &mut block)? {
+                    self.pool.submit(block);
+                } else {
+                    self.finished = true;
+                }
+            }
+            match self.pool.recv() {
+                Some(data) => self.data = io::Cursor::new(data?),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 7) as u8 + b'A').collect();
+        let mut w = BgzfWriter::new(vec![], 6, 3, true);
+        w.write_all(&data).unwrap();
+        let compressed = w.finish().unwrap();
+        assert!(is_bgzf(&compressed));
+        assert!(compressed.ends_with(&EOF_BLOCK));
+
+        let mut out = vec![];
+        BgzfReader::new(&compressed[..], 2)
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+
+        // readable as multi-member GZIP
+        let mut out = vec![];
+        flate2::read::MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+
+        // invalid data
+        let mut corrupt = compressed.clone();
+        corrupt[30] ^= 0xff;
+        assert!(BgzfReader::new(&corrupt[..], 2).read_to_end(&mut vec![]).is_err());
+        assert!(BgzfReader::new(&b"ACGT"[..], 2).read_to_end(&mut vec![]).is_err());
+    }
+}
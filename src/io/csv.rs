@@ -139,11 +139,11 @@ where
     }
 }
 
-// method used by seq_io::parallel module
+// method used by the parallel module
 impl<R: io::Read> CsvReader<R> {
     //type Record = CsvRecord;
     pub fn read_record(&mut self, record: &mut CsvRecord) -> Option<io::Result<()>> {
-        if !try_opt!(self.rdr.read_byte_record(&mut self.rec.data)) {
+        if !try_opt!(self.rdr.read_byte_record(&mut record.data)) {
             return None;
         }
         if !record.cols.initialized {
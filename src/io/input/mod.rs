@@ -1,11 +1,12 @@
 use std::fmt;
 use std::fs::File;
-use std::io;
+use std::io::{self, Read};
 use std::path::PathBuf;
 
 use bzip2::read::BzDecoder;
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 use lz4;
+use xz2::read::XzDecoder;
 use seq_io;
 use zstd;
 
@@ -14,10 +15,15 @@ use error::{CliError, CliResult};
 use lib::util;
 use thread_io;
 
+#[macro_use]
+mod parallel;
 #[allow(dead_code)]
 mod parallel_csv;
+mod sniff;
 
-#[derive(Eq, PartialEq, Debug, Clone)]
+pub use self::sniff::*;
+
+#[derive(Eq, PartialEq, Hash, Debug, Clone)]
 pub enum InputType {
     Stdin,
     File(PathBuf),
@@ -37,6 +43,8 @@ pub struct InputOptions {
     pub kind: InputType,
     pub format: InFormat,
     pub compression: Compression,
+    // number of threads for decompressing BGZF
+    pub compr_threads: u32,
     // read in separate thread
     pub threaded: bool,
     pub cap: usize,
@@ -112,6 +120,9 @@ impl InFormat {
                 fields: csv_fields,
                 has_header: header,
             },
+            "json" | "jsonl" => {
+                return fail!("JSON input is not supported, JSON can only be written as output.")
+            }
             _ => {
                 return Err(CliError::Other(format!(
                     "Unknown input format: '{}'.",
@@ -162,25 +173,45 @@ impl seq_io::BufStrategy for LimitedBufStrategy {
 }
 
 fn get_io_reader<'a>(o: &InputOptions) -> CliResult<Box<io::Read + Send + 'a>> {
-    let rdr: Box<io::Read + Send> = match o.kind {
-        InputType::File(ref path) => Box::new(
-            File::open(path)
-                .map_err(|e| format!("Error opening '{}': {}", path.to_string_lossy(), e))?,
-        ),
-        InputType::Stdin => Box::new(io::stdin()),
-    };
-    get_compr_reader(rdr, o.compression).map_err(From::from)
+    let rdr = open_input(&o.kind)?;
+    get_compr_reader(rdr, o.compression, o.compr_threads).map_err(From::from)
+}
+
+/// Opens an additional input file (e.g. references), which is decompressed
+/// if the compression format is recognized.
+pub fn file_reader(path: &str) -> CliResult<Box<io::Read + Send>> {
+    let kind = InputType::File(path.into());
+    let compression = detect_compression(&kind)?;
+    let rdr = open_input(&kind)?;
+    Ok(get_compr_reader(rdr, compression, 1)?)
 }
 
 fn get_compr_reader<'a>(
-    rdr: Box<io::Read + Send + 'a>,
+    mut rdr: Box<io::Read + Send + 'a>,
     compression: Compression,
+    n_threads: u32,
 ) -> io::Result<Box<io::Read + Send + 'a>> {
     Ok(match compression {
-        Compression::GZIP => Box::new(GzDecoder::new(rdr)),
+        Compression::GZIP | Compression::BGZF if n_threads > 1 => {
+            // BGZF blocks can be decompressed in parallel
+            let mut start = vec![];
+            rdr.by_ref().take(16).read_to_end(&mut start)?;
+            let is_bgzf = bgzf::is_bgzf(&start);
+            let rdr = io::Cursor::new(start).chain(rdr);
+            if is_bgzf {
+                Box::new(bgzf::BgzfReader::new(rdr, n_threads))
+            } else {
+                Box::new(MultiGzDecoder::new(rdr))
+            }
+        }
+        // multi-member gzip files (e.g. from concatenation) are read completely
+        // BGZF is a special case of multi-member gzip
+        Compression::GZIP | Compression::BGZF => Box::new(MultiGzDecoder::new(rdr)),
         Compression::BZIP2 => Box::new(BzDecoder::new(rdr)),
         Compression::LZ4 => Box::new(lz4::Decoder::new(rdr)?),
         Compression::ZSTD => Box::new(zstd::Decoder::new(rdr)?),
+        // concatenated streams are read completely
+        Compression::XZ => Box::new(XzDecoder::new_multi_decoder(rdr)),
         Compression::None => rdr,
     })
 }
@@ -222,7 +253,7 @@ pub fn read_parallel<W, S, Si, Di, F, D, R>(
 ) -> CliResult<()>
 where
     W: Fn(&Record, &mut D, &mut S) -> CliResult<()> + Send + Sync,
-    F: FnMut(&Record, &mut D) -> CliResult<bool>,
+    F: FnMut(&Record, &mut D, &mut S) -> CliResult<bool>,
     R: io::Read + Send,
     Di: Fn() -> D + Send + Sync,
     D: Send,
@@ -234,7 +265,7 @@ where
         let mut rset_data = rset_data_init()?;
         run_reader(rdr, &o.format, o.cap, o.max_mem, &mut |record| {
             work(record, &mut out, &mut rset_data)?;
-            func(record, &mut out)
+            func(record, &mut out, &mut rset_data)
         })
     } else {
         run_reader_parallel(
@@ -246,11 +277,11 @@ where
             |rec, &mut (ref mut out, ref mut res), l| {
                 *res = work(rec, out, l).err();
             },
-            |rec, &mut (ref mut out, ref mut res), _| {
+            |rec, &mut (ref mut out, ref mut res), l| {
                 if let Some(e) = res.take() {
                     return Err(e);
                 }
-                func(rec, out)
+                func(rec, out, l)
             },
         )
     }
@@ -363,7 +394,7 @@ where
     let queue_len = n_threads as usize * 2;
 
     let out: CliResult<Option<CliResult<()>>> = match *format {
-        InFormat::FASTA => seq_io::parallel::parallel_fasta_init(
+        InFormat::FASTA => parallel::parallel_fasta_init(
             n_threads,
             queue_len,
             || Ok::<_, seq_io::fasta::Error>(seq_io::fasta::Reader::new(rdr)),
@@ -382,7 +413,7 @@ where
                 transform_result!(func(&rec, d, s))
             },
         ),
-        InFormat::FASTQ { .. } => seq_io::parallel::parallel_fastq_init(
+        InFormat::FASTQ { .. } => parallel::parallel_fastq_init(
             n_threads,
             queue_len,
             || Ok::<_, seq_io::fastq::Error>(seq_io::fastq::Reader::new(rdr)),
@@ -0,0 +1,217 @@
+//! Parallel processing of record sets, modelled after `seq_io::parallel`.
+//! In contrast to `seq_io`, the processed record sets are returned to the
+//! main thread in the same order as they were read.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::mem::replace;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+use crossbeam;
+use seq_io::parallel::Reader;
+use seq_io::{fasta, fastq};
+
+type Done<D, O, E> = (usize, Result<(D, O), E>);
+
+/// Reads record sets in a background thread and processes them (`work`) in
+/// `n_threads` worker threads. `func` obtains the results in the original
+/// order via `ParallelRecordsets::next()`. The record sets are recycled,
+/// `dataset_init` is called `queue_len + 1` times.
+pub fn read_parallel_init<R, E, Ri, Er, O, Di, Ed, W, F, Out>(
+    n_threads: u32,
+    queue_len: usize,
+    reader_init: Ri,
+    mut dataset_init: Di,
+    work: W,
+    func: F,
+) -> Result<Out, E>
+where
+    R: Reader,
+    R::DataSet: Send,
+    R::Err: Send,
+    Ri: Send + FnOnce() -> Result<R, Er>,
+    Er: Send,
+    E: From<Er> + From<Ed>,
+    O: Send,
+    Di: FnMut() -> Result<R::DataSet, Ed>,
+    W: Send + Sync + Fn(&mut R::DataSet) -> O,
+    F: FnOnce(&mut ParallelRecordsets<R::DataSet, R::Err, O>) -> Out,
+{
+    assert!(n_threads > 0 && queue_len > 0);
+    let (empty_send, empty_recv) = mpsc::sync_channel::<R::DataSet>(queue_len);
+    let (job_send, job_recv) = mpsc::channel::<(usize, R::DataSet)>();
+    let (done_send, done_recv) = mpsc::channel::<Done<R::DataSet, O, R::Err>>();
+    let job_recv = Arc::new(Mutex::new(job_recv));
+
+    crossbeam::scope(|scope| {
+        let work = &work;
+        for _ in 0..n_threads {
+            let job_recv = job_recv.clone();
+            let done_send = done_send.clone();
+            scope.spawn(move || loop {
+                // the lock is released before processing
+                let job = job_recv.lock().unwrap().recv();
+                match job {
+                    Ok((i, mut data)) => {
+                        let out = work(&mut data);
+                        done_send.send((i, Ok((data, out)))).ok();
+                    }
+                    Err(_) => break,
+                }
+            });
+        }
+
+        let handle = scope.spawn(move || {
+            let mut reader = reader_init()?;
+            let mut i = 0;
+            // stops if the main thread does not return the record sets anymore
+            while let Ok(mut data) = empty_recv.recv() {
+                match reader.fill_data(&mut data) {
+                    Some(Ok(())) => {
+                        job_send.send((i, data)).ok();
+                    }
+                    Some(Err(e)) => {
+                        done_send.send((i, Err(e))).ok();
+                        break;
+                    }
+                    None => break,
+                }
+                i += 1;
+            }
+            // the worker threads stop after the job channel is closed
+            Ok::<_, Er>(())
+        });
+
+        for _ in 0..queue_len {
+            empty_send.send(dataset_init()?).ok();
+        }
+
+        let out = {
+            let mut rsets = ParallelRecordsets {
+                empty_send: empty_send,
+                done_recv: done_recv,
+                pending: BTreeMap::new(),
+                next_idx: 0,
+                current_recordset: dataset_init()?,
+            };
+            func(&mut rsets)
+        };
+
+        handle.join().expect("Reader thread panicked")?;
+        Ok(out)
+    })
+}
+
+pub struct ParallelRecordsets<D, E, O> {
+    empty_send: mpsc::SyncSender<D>,
+    done_recv: mpsc::Receiver<Done<D, O, E>>,
+    // results that arrived before the preceding record sets
+    pending: BTreeMap<usize, Result<(D, O), E>>,
+    next_idx: usize,
+    current_recordset: D,
+}
+
+impl<D, E, O> ParallelRecordsets<D, E, O> {
+    /// Returns the next record set in the original order together with the
+    /// output of the worker function. The previously returned record set is
+    /// sent back to the reader for reuse.
+    pub fn next(&mut self) -> Option<Result<(&mut D, O), E>> {
+        let result = loop {
+            if let Some(r) = self.pending.remove(&self.next_idx) {
+                break r;
+            }
+            match self.done_recv.recv() {
+                Ok((i, r)) => {
+                    self.pending.insert(i, r);
+                }
+                // all threads finished
+                Err(_) => return None,
+            }
+        };
+        self.next_idx += 1;
+        Some(result.map(move |(d, o)| {
+            let prev = replace(&mut self.current_recordset, d);
+            // fails if the reader already stopped, which is not a problem
+            self.empty_send.send(prev).ok();
+            (&mut self.current_recordset, o)
+        }))
+    }
+}
+
+/// Generates functions for per-record processing (in the original order),
+/// equivalent to `parallel_fasta_init` & co. in `seq_io::parallel`.
+macro_rules! parallel_record_impl {
+    ($name:ident, $rdr:ty, $dataset:ty, $record:ty, $err:ty) => {
+        pub fn $name<Ri, E, R, Er, Di, D, Ed, Si, S, Es, W, F, Out>(
+            n_threads: u32,
+            queue_len: usize,
+            reader_init: Ri,
+            record_data_init: Di,
+            rset_data_init: Si,
+            work: W,
+            mut func: F,
+        ) -> Result<Option<Out>, E>
+        where
+            R: io::Read,
+            Ri: Send + FnOnce() -> Result<$rdr, Er>,
+            Er: Send,
+            Ed: Send,
+            E: From<$err> + From<Er> + From<Ed> + From<Es>,
+            Di: Fn() -> Result<D, Ed> + Send + Sync,
+            D: Send,
+            Si: Fn() -> Result<S, Es> + Send + Sync,
+            S: Send,
+            W: Send + Sync + Fn($record, &mut D, &mut S),
+            F: FnMut($record, &mut D, &mut S) -> Option<Out>,
+        {
+            let res = $crate::io::input::parallel::read_parallel_init::<_, E, _, _, _, _, Es, _, _, _>(
+                n_threads,
+                queue_len,
+                || reader_init().map(::seq_io::parallel::ReusableReader::<$rdr, (Vec<D>, S)>::new),
+                || rset_data_init().map(|d| (<$dataset>::default(), (vec![], d))),
+                |&mut (ref mut recordset, (ref mut out, ref mut rset_data))| {
+                    let mut record_iter = recordset.into_iter();
+                    for d in out.iter_mut().zip(&mut record_iter) {
+                        work(d.1, d.0, rset_data);
+                    }
+                    for record in record_iter {
+                        out.push(record_data_init()?);
+                        work(record, out.last_mut().unwrap(), rset_data);
+                    }
+                    Ok::<_, Ed>(())
+                },
+                |records| {
+                    while let Some(result) = records.next() {
+                        let (r, res) = result?;
+                        res?;
+                        let &mut (ref records, (ref mut out, ref mut rset_data)) = r;
+                        for x in records.into_iter().zip(out.iter_mut()) {
+                            if let Some(out) = func(x.0, x.1, rset_data) {
+                                return Ok(Some(out));
+                            }
+                        }
+                    }
+                    Ok(None)
+                },
+            )?;
+            res
+        }
+    };
+}
+
+parallel_record_impl!(
+    parallel_fasta_init,
+    fasta::Reader<R>,
+    fasta::RecordSet,
+    fasta::RefRecord,
+    fasta::Error
+);
+
+parallel_record_impl!(
+    parallel_fastq_init,
+    fastq::Reader<R>,
+    fastq::RecordSet,
+    fastq::RefRecord,
+    fastq::Error
+);
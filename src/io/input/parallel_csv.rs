@@ -38,8 +38,10 @@ where
             if let Some(res) = self.read_record(rec) {
                 try_opt!(res);
                 n += 1;
-            } else {
+            } else if n == 0 {
                 return None;
+            } else {
+                break;
             }
         }
         // last recordset smaller
@@ -49,9 +51,7 @@ where
 }
 
 parallel_record_impl!(
-    parallel_csv,
     parallel_csv_init,
-    R,
     CsvReader<R>,
     CsvRecordSet,
     &CsvRecord,
@@ -0,0 +1,313 @@
+//! Recognition of the input format and compression from the file content
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::sync::Mutex;
+
+use super::*;
+use error::CliResult;
+
+/// Number of (decompressed) bytes inspected for recognizing the format
+const SNIFF_SIZE: usize = 1 << 16;
+
+lazy_static! {
+    // Inputs that cannot be opened twice (STDIN, pipes), which are kept open
+    // after recognizing the format, together with the bytes already read.
+    // These are returned again by `open_input`.
+    static ref RETAINED: Mutex<HashMap<InputType, Retained>> = Mutex::new(HashMap::new());
+}
+
+struct Retained {
+    prefix: Vec<u8>,
+    rdr: Box<io::Read + Send>,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Detected {
+    Fasta,
+    Fastq(QualFormat),
+    Csv,
+    Tsv,
+    GenBank,
+    Sam,
+}
+
+impl Detected {
+    /// Returns the format name as accepted by `InFormat::from_opts`
+    pub fn name(&self) -> CliResult<&'static str> {
+        Ok(match *self {
+            Detected::Fasta => "fasta",
+            Detected::Fastq(QualFormat::Illumina) => "fastq-illumina",
+            Detected::Fastq(QualFormat::Solexa) => "fastq-solexa",
+            Detected::Fastq(_) => "fastq",
+            Detected::Csv => "csv",
+            Detected::Tsv => "tsv",
+            Detected::GenBank | Detected::Sam => {
+                return fail!(format!(
+                    "The input seems to be in the {} format, which is not supported. \
+                     Use --fmt to override the format recognition.",
+                    if *self == Detected::Sam { "SAM" } else { "GenBank" }
+                ))
+            }
+        })
+    }
+}
+
+/// Reads the start of the input (after decompression) and tries to recognize
+/// the format. With STDIN and pipes, the input is kept open and the bytes
+/// read are returned again by `open_input`.
+pub fn sniff_input(kind: &InputType, compression: Compression) -> CliResult<Option<Detected>> {
+    let mut buf = vec![];
+    if is_retained(kind)? {
+        let res = with_retained(kind, |r| {
+            // the decoder may read more bytes than needed, therefore all raw
+            // bytes are retained
+            let rdr = PeekReader { retained: r, pos: 0 };
+            get_compr_reader(Box::new(rdr), compression, 1).and_then(|r| read_start(r, &mut buf))
+        })?;
+        // errors are reported later when actually reading
+        if res.is_err() {
+            return Ok(None);
+        }
+    } else {
+        let f = open_file(kind)?;
+        if get_compr_reader(Box::new(f), compression, 1)
+            .and_then(|r| read_start(r, &mut buf))
+            .is_err()
+        {
+            return Ok(None);
+        }
+    }
+    Ok(sniff_format(&buf))
+}
+
+/// Recognizes the compression format from the magic bytes at the start of the
+/// (raw) input. With STDIN and pipes, the input is kept open and the bytes
+/// are returned again by `open_input`.
+pub fn detect_compression(kind: &InputType) -> CliResult<Compression> {
+    let mut magic = vec![];
+    if is_retained(kind)? {
+        with_retained(kind, |r| read_n(PeekReader { retained: r, pos: 0 }, MAGIC_LEN, &mut magic))??;
+    } else {
+        read_n(open_file(kind)?, MAGIC_LEN, &mut magic)?;
+    }
+    Ok(compression_from_magic(&magic))
+}
+
+/// Max. number of bytes needed to recognize the compression format
+const MAGIC_LEN: usize = 6;
+
+fn compression_from_magic(magic: &[u8]) -> Compression {
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        Compression::GZIP
+    } else if magic.starts_with(b"BZh") {
+        Compression::BZIP2
+    } else if magic.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+        Compression::LZ4
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Compression::ZSTD
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+        Compression::XZ
+    } else {
+        Compression::None
+    }
+}
+
+// reads up to `n` bytes (fewer only if the end of the input is reached)
+fn read_n<R: io::Read>(rdr: R, n: usize, buf: &mut Vec<u8>) -> io::Result<()> {
+    rdr.take(n as u64).read_to_end(buf)?;
+    Ok(())
+}
+
+/// Opens the input. If already opened for recognizing the format, the
+/// bytes read so far are returned first.
+pub fn open_input(kind: &InputType) -> CliResult<Box<io::Read + Send>> {
+    if let Some(r) = RETAINED.lock().unwrap().remove(kind) {
+        return Ok(Box::new(io::Cursor::new(r.prefix).chain(r.rdr)));
+    }
+    Ok(match *kind {
+        InputType::File(_) => Box::new(open_file(kind)?),
+        InputType::Stdin => Box::new(io::stdin()),
+    })
+}
+
+fn open_file(kind: &InputType) -> CliResult<File> {
+    match *kind {
+        InputType::File(ref path) => Ok(File::open(path)
+            .map_err(|e| format!("Error opening '{}': {}", path.to_string_lossy(), e))?),
+        InputType::Stdin => unreachable!(),
+    }
+}
+
+// Returns true if the input cannot be opened twice (STDIN and everything
+// that is not a regular file, such as FIFOs or process substitution)
+fn is_retained(kind: &InputType) -> CliResult<bool> {
+    Ok(match *kind {
+        InputType::File(ref path) => match fs::metadata(path) {
+            Ok(m) => !m.file_type().is_file(),
+            // error is reported when opening
+            Err(_) => false,
+        },
+        InputType::Stdin => true,
+    })
+}
+
+// Calls `func` with the retained input, which is opened first if necessary
+fn with_retained<F, O>(kind: &InputType, func: F) -> CliResult<O>
+where
+    F: FnOnce(&mut Retained) -> O,
+{
+    let mut retained = RETAINED.lock().unwrap();
+    if !retained.contains_key(kind) {
+        let rdr: Box<io::Read + Send> = match *kind {
+            InputType::File(_) => Box::new(open_file(kind)?),
+            InputType::Stdin => Box::new(io::stdin()),
+        };
+        retained.insert(kind.clone(), Retained { prefix: vec![], rdr: rdr });
+    }
+    Ok(func(retained.get_mut(kind).unwrap()))
+}
+
+fn read_start<R: io::Read>(rdr: R, buf: &mut Vec<u8>) -> io::Result<()> {
+    read_n(rdr, SNIFF_SIZE, buf)
+}
+
+/// Recognizes the format given the start of the input. Returns `None` if the
+/// input is empty or the format is unknown.
+pub fn sniff_format(data: &[u8]) -> Option<Detected> {
+    let mut lines: Vec<_> = data
+        .split(|&b| b == b'\n')
+        .map(|l| if l.ends_with(b"\r") { &l[..l.len() - 1] } else { l })
+        .collect();
+    if data.len() >= SNIFF_SIZE {
+        // the last line may be incomplete
+        lines.pop();
+    }
+    let mut lines = lines.into_iter().skip_while(|l| l.is_empty()).peekable();
+    let first = *lines.peek()?;
+
+    if first.starts_with(b">") {
+        return Some(Detected::Fasta);
+    }
+    if first.starts_with(b"LOCUS ") {
+        return Some(Detected::GenBank);
+    }
+    if first.starts_with(b"@") {
+        if first.len() > 3 && first[3] == b'\t' && is_sam_header(&first[1..3]) {
+            return Some(Detected::Sam);
+        }
+        return Some(Detected::Fastq(guess_qual_format(lines)));
+    }
+
+    // tabular formats: SAM (no header), TSV or CSV
+    let lines: Vec<_> = lines.filter(|l| !l.is_empty()).take(100).collect();
+    let is_sam = lines.iter().all(|l| {
+        let fields: Vec<_> = l.split(|&b| b == b'\t').collect();
+        fields.len() >= 11 && is_num(fields[1]) && is_num(fields[3]) && is_num(fields[4])
+    });
+    if is_sam {
+        return Some(Detected::Sam);
+    }
+    let count = |l: &[u8], delim| l.iter().filter(|&&b| b == delim).count();
+    for &(delim, fmt) in &[(b'\t', Detected::Tsv), (b',', Detected::Csv)] {
+        let n = count(lines[0], delim);
+        if n > 0 && lines.iter().all(|l| count(l, delim) == n) {
+            return Some(fmt);
+        }
+    }
+    None
+}
+
+fn is_sam_header(tag: &[u8]) -> bool {
+    match tag {
+        b"HD" | b"SQ" | b"RG" | b"PG" | b"CO" => true,
+        _ => false,
+    }
+}
+
+fn is_num(s: &[u8]) -> bool {
+    !s.is_empty() && s.iter().all(u8::is_ascii_digit)
+}
+
+/// Guesses the FASTQ quality encoding from the range of quality characters
+/// (every fourth line, starting with the fourth)
+fn guess_qual_format<'a, I>(lines: I) -> QualFormat
+where
+    I: Iterator<Item = &'a [u8]>,
+{
+    let (min, max) = lines
+        .skip(3)
+        .step_by(4)
+        .flat_map(|l| l.iter().cloned())
+        .fold((255, 0), |(min, max), q| (::std::cmp::min(min, q), ::std::cmp::max(max, q)));
+    // Offset 64 is only assumed if the range is clearly incompatible with offset 33,
+    // which is the default: Illumina 1.3+ / Solexa Phred scores do not exceed
+    // ~41 ('i'), higher characters only occur with offset 33 (e.g. PacBio HiFi).
+    if min < 59 || max <= 74 || max > 105 {
+        QualFormat::Sanger
+    } else if min < 64 {
+        QualFormat::Solexa
+    } else {
+        QualFormat::Illumina
+    }
+}
+
+// Returns the retained bytes first, then reads from the underlying reader.
+// All newly read bytes are added to the retained bytes.
+struct PeekReader<'a> {
+    retained: &'a mut Retained,
+    pos: usize,
+}
+
+impl<'a> io::Read for PeekReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = if self.pos < self.retained.prefix.len() {
+            (&self.retained.prefix[self.pos..]).read(buf)?
+        } else {
+            let n = self.retained.rdr.read(buf)?;
+            self.retained.prefix.extend_from_slice(&buf[..n]);
+            n
+        };
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        assert_eq!(sniff_format(b""), None);
+        assert_eq!(sniff_format(b"\n>id\nSEQ\n"), Some(Detected::Fasta));
+        assert_eq!(sniff_format(b"@id\nSEQ\n+\nIII\n"), Some(Detected::Fastq(QualFormat::Sanger)));
+        assert_eq!(sniff_format(b"@id\nSEQ\n+\nhhh\n@id\nSEQ\n+\n@BB\n"),
+                   Some(Detected::Fastq(QualFormat::Illumina)));
+        assert_eq!(sniff_format(b"@id\nSEQ\n+\n;hh\n"), Some(Detected::Fastq(QualFormat::Solexa)));
+        // high-quality Sanger scores
+        assert_eq!(sniff_format(b"@r\nACGT\n+\nJJ@@\n@s\nACGT\n+\nh@@~\n"),
+                   Some(Detected::Fastq(QualFormat::Sanger)));
+        assert_eq!(sniff_format(b"id,seq\ns1,ACG\n"), Some(Detected::Csv));
+        assert_eq!(sniff_format(b"s1\tACG\r\ns2\tGGG\r\n"), Some(Detected::Tsv));
+        assert_eq!(sniff_format(b"LOCUS       NC_001 100 bp\n"), Some(Detected::GenBank));
+        assert_eq!(sniff_format(b"@HD\tVN:1.6\n"), Some(Detected::Sam));
+        assert_eq!(
+            sniff_format(b"r1\t0\tchr1\t100\t60\t3M\t*\t0\t0\tACG\tIII\n"),
+            Some(Detected::Sam)
+        );
+        assert_eq!(sniff_format(b"ACGT\n"), None);
+    }
+
+    #[test]
+    fn magic() {
+        assert_eq!(compression_from_magic(b"\x1f\x8b\x08\x00"), Compression::GZIP);
+        assert_eq!(compression_from_magic(b"BZh9"), Compression::BZIP2);
+        assert_eq!(compression_from_magic(b"\x04\x22\x4d\x18"), Compression::LZ4);
+        assert_eq!(compression_from_magic(b"\x28\xb5\x2f\xfd"), Compression::ZSTD);
+        assert_eq!(compression_from_magic(b"\xfd7zXZ\x00"), Compression::XZ);
+        assert_eq!(compression_from_magic(b">seq"), Compression::None);
+        assert_eq!(compression_from_magic(b""), Compression::None);
+    }
+}
@@ -19,22 +19,44 @@ pub trait SeqWriter<W: io::Write> {
 pub enum Compression {
     None,
     GZIP,
+    BGZF,
     BZIP2,
     LZ4,
     ZSTD,
+    XZ,
 }
 
 impl Compression {
     pub fn from_str(s: &str) -> Option<Compression> {
         match s {
             "gz" => Some(Compression::GZIP),
+            "bgz" | "bgzf" => Some(Compression::BGZF),
             "bz2" => Some(Compression::BZIP2),
             "lz4" => Some(Compression::LZ4),
             "zst" => Some(Compression::ZSTD),
+            "xz" => Some(Compression::XZ),
             _ => None,
         }
     }
 
+    /// Checks if the compression level (--compr-level) is in the valid range
+    pub fn check_level(self, level: u8) -> CliResult<()> {
+        let (min, max) = match self {
+            Compression::GZIP | Compression::BGZF | Compression::XZ => (0, 9),
+            Compression::BZIP2 => (1, 9),
+            Compression::LZ4 => (0, 12),
+            Compression::ZSTD => (0, 22),
+            Compression::None => return Ok(()),
+        };
+        if level < min || level > max {
+            return fail!(format!(
+                "Invalid compression level for {:?}: {}. Valid levels are {}-{}.",
+                self, level, min, max
+            ));
+        }
+        Ok(())
+    }
+
     pub fn best_read_bufsize(self) -> usize {
         match self {
             Compression::ZSTD => zstd::Decoder::<io::Empty>::recommended_output_size(),
@@ -50,6 +72,7 @@ impl Compression {
     }
 }
 
+pub mod bgzf;
 pub mod csv;
 pub mod fa_qual;
 pub mod fasta;
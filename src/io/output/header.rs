@@ -0,0 +1,58 @@
+use std::io;
+use std::marker::PhantomData;
+
+use error::CliResult;
+use var;
+
+use super::{Record, Writer};
+use io::DefRecord;
+use io::SeqWriter;
+
+/// Replaces the whole FASTA/FASTQ header by a template
+/// (`--out-header` option)
+pub struct HeaderWriter<W: io::Write, S: SeqWriter<W>> {
+    inner: S,
+    template: String, // used only until 'register_vars' called
+    compiled: Option<var::varstring::VarString>,
+    temp: Vec<u8>,
+    _w: PhantomData<W>,
+}
+
+impl<W: io::Write, S: SeqWriter<W>> HeaderWriter<W, S> {
+    pub fn new(writer: S, template: String) -> HeaderWriter<W, S> {
+        HeaderWriter {
+            inner: writer,
+            template: template,
+            compiled: None,
+            temp: vec![],
+            _w: PhantomData,
+        }
+    }
+}
+
+impl<W: io::Write, S: SeqWriter<W>> Writer<W> for HeaderWriter<W, S> {
+    fn register_vars(&mut self, builder: &mut var::VarBuilder) -> CliResult<()> {
+        let e = var::varstring::VarString::parse_register(&self.template, builder)?;
+        self.compiled = Some(e);
+        Ok(())
+    }
+
+    #[inline]
+    fn has_vars(&self) -> bool {
+        true
+    }
+
+    fn write(&mut self, record: &Record, vars: &var::Vars) -> CliResult<()> {
+        self.temp.clear();
+        self.compiled
+            .as_ref()
+            .expect("Bug: header template not registered")
+            .compose(&mut self.temp, vars.symbols());
+        self.inner
+            .write(&DefRecord::new(&record, &self.temp, None), vars)
+    }
+
+    fn into_inner(self: Box<Self>) -> Option<CliResult<W>> {
+        Box::new(self.inner).into_inner()
+    }
+}
@@ -0,0 +1,166 @@
+use std::io::{self, Write};
+
+use error::CliResult;
+use var;
+use var::varstring;
+
+use super::{Record, Writer};
+use io::{QualFormat, SeqAttr};
+
+enum Field {
+    SeqAttr(SeqAttr),
+    // quality scores written as list of Phred scores
+    Qual,
+    Expr(varstring::VarString),
+}
+
+/// Writes one JSON object per record with keys given by `fields`. Either
+/// a JSON array of objects is written, or one object per line (JSON lines).
+pub struct JsonWriter<W: io::Write> {
+    writer: W,
+    fields: Vec<String>,
+    // (JSON-encoded key, field)
+    compiled_fields: Vec<(Vec<u8>, Field)>,
+    lines: bool,
+    started: bool,
+    buf: Vec<u8>,
+}
+
+impl<W: io::Write> JsonWriter<W> {
+    pub fn new(writer: W, fields: Vec<String>, lines: bool) -> JsonWriter<W> {
+        JsonWriter {
+            writer: writer,
+            fields: fields,
+            compiled_fields: vec![],
+            lines: lines,
+            started: false,
+            buf: vec![],
+        }
+    }
+}
+
+impl<W: io::Write> Writer<W> for JsonWriter<W> {
+    fn register_vars(&mut self, builder: &mut var::VarBuilder) -> CliResult<()> {
+        self.compiled_fields.clear();
+        for field in &self.fields {
+            let compiled = if field == "qual" {
+                Field::Qual
+            } else {
+                match SeqAttr::from_str(field) {
+                    Some(a) => Field::SeqAttr(a),
+                    None => {
+                        let expr = varstring::VarString::var_or_composed(field, builder)?;
+                        Field::Expr(expr)
+                    }
+                }
+            };
+            let mut key = vec![];
+            write_str(&mut key, field.as_bytes())?;
+            self.compiled_fields.push((key, compiled));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn has_vars(&self) -> bool {
+        !self.fields.is_empty()
+    }
+
+    fn write(&mut self, record: &Record, vars: &var::Vars) -> CliResult<()> {
+        if !self.lines {
+            self.writer
+                .write_all(if self.started { b",\n" } else { b"[\n" })?;
+        }
+        self.started = true;
+
+        self.writer.write_all(b"{")?;
+        for (i, &(ref key, ref field)) in self.compiled_fields.iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b",")?;
+            }
+            self.writer.write_all(key)?;
+            self.writer.write_all(b":")?;
+            match *field {
+                Field::SeqAttr(attr) => {
+                    self.buf.clear();
+                    record.write_attr(attr, &mut self.buf);
+                    write_str(&mut self.writer, &self.buf)?;
+                }
+                Field::Qual => {
+                    if let Some(qual) = record.qual() {
+                        let conv = &vars.data().qual_converter;
+                        self.writer.write_all(b"[")?;
+                        for (j, &q) in qual.iter().enumerate() {
+                            if j > 0 {
+                                self.writer.write_all(b",")?;
+                            }
+                            write!(self.writer, "{}", conv.convert(q, QualFormat::Phred)?)?;
+                        }
+                        self.writer.write_all(b"]")?;
+                    } else {
+                        self.writer.write_all(b"null")?;
+                    }
+                }
+                Field::Expr(ref expr) => {
+                    let symbols = vars.symbols();
+                    match expr.get_one_var() {
+                        Some(id) if symbols.is_none(id) => self.writer.write_all(b"null")?,
+                        Some(id) if symbols.is_numeric(id) => {
+                            // NaN and infinite values are not valid JSON
+                            let finite = symbols.get_float(id)?.unwrap().is_finite();
+                            if finite {
+                                self.writer.write_all(symbols.get_text(id).unwrap())?;
+                            } else {
+                                self.writer.write_all(b"null")?;
+                            }
+                        }
+                        _ => {
+                            self.buf.clear();
+                            expr.compose(&mut self.buf, symbols);
+                            write_str(&mut self.writer, &self.buf)?;
+                        }
+                    }
+                }
+            }
+        }
+        self.writer.write_all(b"}")?;
+        if self.lines {
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn into_inner(mut self: Box<Self>) -> Option<CliResult<W>> {
+        if !self.lines {
+            let end: &[u8] = if self.started { b"\n]\n" } else { b"[]\n" };
+            try_opt!(self.writer.write_all(end));
+        }
+        Some(Ok(self.writer))
+    }
+}
+
+/// Writes a quoted and escaped JSON string
+fn write_str<W: io::Write>(mut out: W, s: &[u8]) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    let mut start = 0;
+    for (i, &b) in s.iter().enumerate() {
+        let esc: &[u8] = match b {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0...0x1f => b"",
+            _ => continue,
+        };
+        out.write_all(&s[start..i])?;
+        if esc.is_empty() {
+            write!(out, "\\u{:04x}", b)?;
+        } else {
+            out.write_all(esc)?;
+        }
+        start = i + 1;
+    }
+    out.write_all(&s[start..])?;
+    out.write_all(b"\"")
+}
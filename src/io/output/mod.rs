@@ -5,6 +5,7 @@ use std::path::PathBuf;
 use bzip2;
 use flate2;
 use lz4;
+use xz2;
 use zstd;
 
 use error::{CliError, CliResult};
@@ -12,12 +13,14 @@ use lib::util;
 use thread_io;
 
 use super::input::InFormat;
-use super::{fa_qual, fasta, fastq, Compression, QualFormat, Record};
+use super::{bgzf, fa_qual, fasta, fastq, Compression, QualFormat, Record, SeqWriter};
 
 pub use self::writer::*;
 
 pub mod attr;
 pub mod csv;
+pub mod header;
+pub mod json;
 pub mod writer;
 
 lazy_static! {
@@ -30,6 +33,8 @@ pub struct OutputOptions {
     pub format: OutFormat,
     pub compression: Compression,
     pub compression_level: Option<u8>,
+    // number of threads for GZIP and ZSTD compression
+    pub compr_threads: u32,
     pub threaded: bool,
     pub thread_bufsize: Option<usize>,
 }
@@ -40,10 +45,12 @@ impl Default for OutputOptions {
             kind: OutputKind::Stdout,
             format: OutFormat::FASTA {
                 attrs: vec![],
+                header: None,
                 wrap_width: None,
             },
             compression: Compression::None,
             compression_level: None,
+            compr_threads: 1,
             threaded: false,
             thread_bufsize: None,
         }
@@ -61,15 +68,19 @@ pub enum OutFormat {
     FASTA {
         attrs: Vec<(String, String)>,
         // Vec<(attr_name, attr_value)>, default_seqattr_for_attrs
+        // template for the whole header
+        header: Option<String>,
         wrap_width: Option<usize>,
     },
     FASTQ {
         // only Some() if different from input format
         format: Option<QualFormat>,
         attrs: Vec<(String, String)>,
+        header: Option<String>,
     },
     FaQual {
         attrs: Vec<(String, String)>,
+        header: Option<String>,
         wrap_width: Option<usize>,
         qfile: PathBuf,
     },
@@ -77,6 +88,11 @@ pub enum OutFormat {
         delim: u8,
         fields: Vec<String>,
     },
+    JSON {
+        fields: Vec<String>,
+        // one object per line instead of array
+        lines: bool,
+    },
 }
 
 impl OutFormat {
@@ -90,12 +106,35 @@ impl OutFormat {
             } else {
                 "csv"
             },
+            OutFormat::JSON { lines, .. } => if lines {
+                "jsonl"
+            } else {
+                "json"
+            },
+        }
+    }
+
+    /// Adds attributes to FASTA/FASTQ headers, unless an attribute with
+    /// the same name was already specified or a header template is used.
+    pub fn add_attrs(&mut self, new: &[(String, String)]) {
+        match *self {
+            OutFormat::FASTA { ref mut attrs, header: None, .. }
+            | OutFormat::FASTQ { ref mut attrs, header: None, .. }
+            | OutFormat::FaQual { ref mut attrs, header: None, .. } => {
+                for &(ref name, ref value) in new {
+                    if !attrs.iter().any(|&(ref n, _)| n == name) {
+                        attrs.push((name.clone(), value.clone()));
+                    }
+                }
+            }
+            _ => {}
         }
     }
 
     pub fn from_opts(
         string: &str,
         attrs: &[(String, String)],
+        header: Option<&str>,
         wrap_fasta: Option<usize>,
         csv_delim: Option<&str>,
         csv_fields: Option<&str>,
@@ -111,22 +150,32 @@ impl OutFormat {
             .or_else(|| in_fields.cloned())
             .unwrap_or_else(|| vec!["id".to_string(),"desc".to_string(),"seq".to_string()]);
 
+        if header.is_some() && !attrs.is_empty() {
+            return fail!("Attributes (-a) cannot be added if a header template is used. \
+                         Specify them in the header template instead.");
+        }
+        let header = header.map(|h| h.to_string());
+
         let mut format = match string {
             "fasta" | "fna" | "fa" | "<FASTA/QUAL>" => OutFormat::FASTA {
                 attrs: attrs.to_owned(),
+                header: header.clone(),
                 wrap_width: wrap_fasta,
             },
             "fastq" | "fq" => OutFormat::FASTQ {
                 format: Some(QualFormat::Sanger),
                 attrs: attrs.to_owned(),
+                header: header.clone(),
             },
             "fastq-illumina" | "fq-illumina" => OutFormat::FASTQ {
                 format: Some(QualFormat::Illumina),
                 attrs: attrs.to_owned(),
+                header: header.clone(),
             },
             "fastq-solexa" | "fq-solexa" => OutFormat::FASTQ {
                 format: Some(QualFormat::Solexa),
                 attrs: attrs.to_owned(),
+                header: header.clone(),
             },
             "csv" => OutFormat::CSV {
                 delim: util::parse_delimiter(csv_delim.unwrap_or(","))?,
@@ -136,6 +185,14 @@ impl OutFormat {
                 delim: util::parse_delimiter(csv_delim.unwrap_or("\t"))?,
                 fields: csv_fields,
             },
+            "json" => OutFormat::JSON {
+                fields: csv_fields,
+                lines: false,
+            },
+            "jsonl" | "ndjson" => OutFormat::JSON {
+                fields: csv_fields,
+                lines: true,
+            },
             _ => {
                 return Err(CliError::Other(format!(
                     "Unknown output format: '{}'",
@@ -144,6 +201,15 @@ impl OutFormat {
             }
         };
 
+        if header.is_some() {
+            match format {
+                OutFormat::CSV { .. } | OutFormat::JSON { .. } => {
+                    return fail!("--out-header is only valid for FASTA/FASTQ output")
+                }
+                _ => {}
+            }
+        }
+
         // remove quality output format if equal to input format
         if let OutFormat::FASTQ { format: outfmt, .. } = &mut format {
             if let Some(&InFormat::FASTQ { format: infmt }) = informat {
@@ -156,9 +222,14 @@ impl OutFormat {
         // FaQual format
         if let Some(f) = qfile {
             match format {
-                OutFormat::FASTA { attrs, wrap_width } => {
+                OutFormat::FASTA {
+                    attrs,
+                    header,
+                    wrap_width,
+                } => {
                     format = OutFormat::FaQual {
                         attrs,
+                        header,
                         wrap_width,
                         qfile: PathBuf::from(f),
                     };
@@ -215,6 +286,24 @@ impl<W: io::Write> WriteFinish for flate2::write::GzEncoder<W> {
     }
 }
 
+impl<W: io::Write> WriteFinish for bgzf::BgzfWriter<W> {
+    fn finish<'a>(self: Box<Self>) -> io::Result<Box<io::Write + 'a>>
+    where
+        Self: 'a,
+    {
+        (*self).finish().map(|w| Box::new(w) as Box<io::Write>)
+    }
+}
+
+impl<W: io::Write> WriteFinish for xz2::write::XzEncoder<W> {
+    fn finish<'a>(self: Box<Self>) -> io::Result<Box<io::Write + 'a>>
+    where
+        Self: 'a,
+    {
+        (*self).finish().map(|w| Box::new(w) as Box<io::Write>)
+    }
+}
+
 impl<W: io::Write> WriteFinish for bzip2::write::BzEncoder<W> {
     fn finish<'a>(self: Box<Self>) -> io::Result<Box<io::Write + 'a>>
     where
@@ -230,7 +319,12 @@ where
 {
     io_writer(o, |io_writer| {
         let mut w = from_format(io_writer, &o.format)?;
-        func(&mut w)
+        let out = func(&mut w)?;
+        // some formats need to write a footer
+        if let Some(res) = w.into_inner() {
+            res?;
+        }
+        Ok(out)
     })
 }
 
@@ -248,7 +342,7 @@ where
             4,
             || {
                 let mut writer = io_writer_from_kind(&o.kind)?;
-                writer = compr_writer(writer, o.compression, o.compression_level)?;
+                writer = compr_writer(writer, o.compression, o.compression_level, o.compr_threads)?;
                 Ok(writer)
             },
             |mut w| func(&mut w),
@@ -269,29 +363,54 @@ where
     Ok(match *format {
         OutFormat::FASTA {
             ref attrs,
+            ref header,
             wrap_width,
         } => {
             let writer = fasta::FastaWriter::new(io_writer, wrap_width);
-            Box::new(attr::AttrWriter::new(writer, attrs.clone()))
+            header_writer(writer, attrs, header)
         }
-        OutFormat::FASTQ { format, ref attrs } => {
+        OutFormat::FASTQ {
+            format,
+            ref attrs,
+            ref header,
+        } => {
             let writer = fastq::FastqWriter::new(io_writer, format);
-            Box::new(attr::AttrWriter::new(writer, attrs.clone()))
+            header_writer(writer, attrs, header)
         }
         OutFormat::FaQual {
             ref attrs,
+            ref header,
             wrap_width,
             ref qfile,
         } => {
             let writer = fa_qual::FaQualWriter::new(io_writer, wrap_width, qfile)?;
-            Box::new(attr::AttrWriter::new(writer, attrs.clone()))
+            header_writer(writer, attrs, header)
         }
         OutFormat::CSV { delim, ref fields } => {
             Box::new(csv::CsvWriter::new(io_writer, fields.clone(), delim))
         }
+        OutFormat::JSON { ref fields, lines } => {
+            Box::new(json::JsonWriter::new(io_writer, fields.clone(), lines))
+        }
     })
 }
 
+fn header_writer<'a, W, S>(
+    writer: S,
+    attrs: &[(String, String)],
+    header: &Option<String>,
+) -> Box<Writer<W> + 'a>
+where
+    W: io::Write + 'a,
+    S: SeqWriter<W> + 'a,
+{
+    if let Some(ref h) = *header {
+        Box::new(header::HeaderWriter::new(writer, h.clone()))
+    } else {
+        Box::new(attr::AttrWriter::new(writer, attrs.to_owned()))
+    }
+}
+
 pub fn io_writer_from_kind(kind: &OutputKind) -> io::Result<Box<WriteFinish>> {
     Ok(match *kind {
         OutputKind::Stdout => Box::new(io::BufWriter::new(STDOUT.lock())),
@@ -308,12 +427,28 @@ pub fn compr_writer(
     writer: Box<WriteFinish>,
     compression: Compression,
     level: Option<u8>,
+    n_threads: u32,
 ) -> io::Result<Box<WriteFinish>> {
     Ok(match compression {
+        // with multiple threads, blocks are compressed independently
+        // (BGZF layout, readable by any GZIP decoder)
+        Compression::GZIP if n_threads > 1 => Box::new(bgzf::BgzfWriter::new(
+            writer,
+            u32::from(level.unwrap_or(6)),
+            n_threads,
+            false,
+        )),
         Compression::GZIP => Box::new(flate2::write::GzEncoder::new(
             writer,
             flate2::Compression::new(u32::from(level.unwrap_or(6))),
         )),
+        // the end of the file is marked by an empty block
+        Compression::BGZF => Box::new(bgzf::BgzfWriter::new(
+            writer,
+            u32::from(level.unwrap_or(6)),
+            n_threads,
+            true,
+        )),
         Compression::BZIP2 => {
             let c = match level {
                 Some(0...3) => bzip2::Compression::Fastest,
@@ -323,8 +458,22 @@ pub fn compr_writer(
             };
             Box::new(bzip2::write::BzEncoder::new(writer, c))
         }
-        Compression::LZ4 => Box::new(lz4::EncoderBuilder::new().build(writer)?),
-        Compression::ZSTD => Box::new(zstd::Encoder::new(writer, i32::from(level.unwrap_or(0)))?),
+        Compression::LZ4 => Box::new(
+            lz4::EncoderBuilder::new()
+                .level(u32::from(level.unwrap_or(0)))
+                .build(writer)?,
+        ),
+        Compression::ZSTD => {
+            let mut e = zstd::Encoder::new(writer, i32::from(level.unwrap_or(0)))?;
+            if n_threads > 1 {
+                e.multithread(n_threads)?;
+            }
+            Box::new(e)
+        }
+        Compression::XZ => Box::new(xz2::write::XzEncoder::new(
+            writer,
+            u32::from(level.unwrap_or(6)),
+        )),
         Compression::None => writer,
     })
 }
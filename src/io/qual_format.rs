@@ -22,7 +22,7 @@ impl QualFormat {
 
 use self::QualFormat::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct QualConverter {
     fmt: QualFormat,
 }
@@ -99,6 +99,11 @@ impl QualConverter {
         })
     }
 
+    /// Converts a Phred score to a quality character of this format
+    pub fn from_phred(&self, q: u8) -> u8 {
+        QualConverter::new(Phred).convert(q, self.fmt).unwrap()
+    }
+
     // pub fn get_phred(&self, q: u8) -> Result<u8, String> {
     //
     //     Ok(match self.fmt {
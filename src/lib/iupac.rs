@@ -0,0 +1,86 @@
+//! IUPAC nucleotide ambiguity codes, represented as bit sets
+//! (A = 1, C = 2, G = 4, T/U = 8).
+
+pub const A: u8 = 1;
+pub const C: u8 = 2;
+pub const G: u8 = 4;
+pub const T: u8 = 8;
+
+/// Returns the set of bases represented by a (case insensitive) nucleotide
+/// code, or `None` if the character is not a valid code.
+pub fn bases(code: u8) -> Option<u8> {
+    Some(match code.to_ascii_uppercase() {
+        b'A' => A,
+        b'C' => C,
+        b'G' => G,
+        b'T' | b'U' => T,
+        b'M' => A | C,
+        b'R' => A | G,
+        b'W' => A | T,
+        b'S' => C | G,
+        b'Y' => C | T,
+        b'K' => G | T,
+        b'V' => A | C | G,
+        b'H' => A | C | T,
+        b'D' => A | G | T,
+        b'B' => C | G | T,
+        b'N' => A | C | G | T,
+        _ => return None,
+    })
+}
+
+/// Returns the uppercase DNA code for a non-empty set of bases
+pub fn code(bases: u8) -> u8 {
+    b"-ACMGRSVTWYHKDBN"[(bases & 15) as usize]
+}
+
+/// Returns the complement of a nucleotide code (including ambiguity codes).
+/// The case is preserved and U is complemented to A (and vice versa, if `rna` is true).
+/// Other characters are returned unchanged.
+pub fn complement(c: u8, rna: bool) -> u8 {
+    let b = match bases(c) {
+        Some(b) => b,
+        None => return c,
+    };
+    // A <-> T and C <-> G: reverse the order of the four bits
+    let comp = (b & A) << 3 | (b & C) << 1 | (b & G) >> 1 | (b & T) >> 3;
+    let mut out = code(comp);
+    if rna && out == b'T' {
+        out = b'U';
+    }
+    if c.is_ascii_lowercase() {
+        out.to_ascii_lowercase()
+    } else {
+        out
+    }
+}
+
+/// Writes the reverse complement of `seq` to `out`
+pub fn revcomp(seq: &[u8], rna: bool, out: &mut Vec<u8>) {
+    out.extend(seq.iter().rev().map(|&c| complement(c, rna)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        for &c in b"ACGTMRWSYKVHDBN" {
+            assert_eq!(code(bases(c).unwrap()), c);
+        }
+        assert_eq!(bases(b'u'), Some(T));
+        assert_eq!(bases(b'X'), None);
+        assert_eq!(code(A | G), b'R');
+    }
+
+    #[test]
+    fn revcomp_ambig() {
+        let mut out = vec![];
+        revcomp(b"ACGTMRWSYKVHDBNacgtn-", false, &mut out);
+        assert_eq!(&out, b"-nacgtNVHDBMRSWYKACGT");
+        out.clear();
+        revcomp(b"ACGU", true, &mut out);
+        assert_eq!(&out, b"ACGU");
+    }
+}
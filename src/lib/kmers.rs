@@ -0,0 +1,145 @@
+//! K-mer extraction from DNA/RNA sequences. Short k-mers are packed into
+//! integers (2 bits per base), longer ones are returned as normalized
+//! byte slices. K-mers containing characters other than A, C, G, T/U
+//! are skipped.
+
+use std::cmp::min;
+
+/// K-mers up to this length are packed into a `u64`
+pub const MAX_PACKED_K: usize = 31;
+
+#[inline]
+fn encode(b: u8) -> Option<u64> {
+    match b {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' | b'U' | b'u' => Some(3),
+        _ => None,
+    }
+}
+
+/// Calls `func` with every packed k-mer (k <= `MAX_PACKED_K`) of the sequence.
+/// If `canonical` is true, the smaller value of the k-mer and its reverse
+/// complement is returned.
+pub fn packed_kmers<F>(seq: &[u8], k: usize, canonical: bool, mut func: F)
+where
+    F: FnMut(u64),
+{
+    assert!(k > 0 && k <= MAX_PACKED_K);
+    let mask = (1u64 << (2 * k)) - 1;
+    let shift = 2 * (k - 1);
+    let mut fwd = 0;
+    let mut rev = 0;
+    let mut valid = 0;
+    for &b in seq {
+        if let Some(c) = encode(b) {
+            fwd = ((fwd << 2) | c) & mask;
+            rev = (rev >> 2) | ((3 - c) << shift);
+            valid += 1;
+            if valid >= k {
+                func(if canonical { min(fwd, rev) } else { fwd });
+            }
+        } else {
+            valid = 0;
+        }
+    }
+}
+
+/// Writes the sequence of a packed k-mer to `out`
+pub fn decode_packed(kmer: u64, k: usize, out: &mut Vec<u8>) {
+    for i in (0..k).rev() {
+        out.push(b"ACGT"[((kmer >> (2 * i)) & 3) as usize]);
+    }
+}
+
+/// Extracts k-mers of any length. The k-mers are uppercase, and
+/// U is converted to T.
+#[derive(Debug, Default)]
+pub struct LongKmers {
+    norm: Vec<u8>,
+    rc: Vec<u8>,
+}
+
+impl LongKmers {
+    pub fn new() -> LongKmers {
+        LongKmers::default()
+    }
+
+    pub fn kmers<F>(&mut self, seq: &[u8], k: usize, canonical: bool, mut func: F)
+    where
+        F: FnMut(&[u8]),
+    {
+        assert!(k > 0);
+        self.norm.clear();
+        self.norm.extend(seq.iter().map(|&b| match b {
+            b'a' | b'A' => b'A',
+            b'c' | b'C' => b'C',
+            b'g' | b'G' => b'G',
+            b't' | b'T' | b'u' | b'U' => b'T',
+            _ => b'N',
+        }));
+        if canonical {
+            self.rc.clear();
+            self.rc.extend(self.norm.iter().rev().map(|&b| match b {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                b'T' => b'A',
+                _ => b'N',
+            }));
+        }
+        let n = self.norm.len();
+        let mut valid_start = 0;
+        for i in 0..n {
+            if self.norm[i] == b'N' {
+                valid_start = i + 1;
+                continue;
+            }
+            if i + 1 - valid_start >= k {
+                let start = i + 1 - k;
+                let kmer = &self.norm[start..i + 1];
+                if canonical {
+                    let rc = &self.rc[n - i - 1..n - start];
+                    func(if rc < kmer { rc } else { kmer });
+                } else {
+                    func(kmer);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packed(seq: &[u8], k: usize, canonical: bool) -> Vec<String> {
+        let mut out = vec![];
+        packed_kmers(seq, k, canonical, |kmer| {
+            let mut s = vec![];
+            decode_packed(kmer, k, &mut s);
+            out.push(String::from_utf8(s).unwrap());
+        });
+        out
+    }
+
+    fn long(seq: &[u8], k: usize, canonical: bool) -> Vec<String> {
+        let mut out = vec![];
+        LongKmers::new().kmers(seq, k, canonical, |kmer| {
+            out.push(String::from_utf8(kmer.to_vec()).unwrap());
+        });
+        out
+    }
+
+    #[test]
+    fn kmers() {
+        let seq = b"ACGTTnAAcg";
+        assert_eq!(packed(seq, 3, false), vec!["ACG", "CGT", "GTT", "AAC", "ACG"]);
+        assert_eq!(packed(seq, 3, true), vec!["ACG", "ACG", "AAC", "AAC", "ACG"]);
+        assert_eq!(long(seq, 3, false), packed(seq, 3, false));
+        assert_eq!(long(seq, 3, true), packed(seq, 3, true));
+        assert_eq!(packed(b"AUG", 3, false), vec!["ATG"]);
+        assert!(packed(b"AC", 3, false).is_empty());
+    }
+}
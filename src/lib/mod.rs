@@ -2,8 +2,11 @@
 
 pub mod bytesize;
 pub mod inner_result;
+pub mod iupac;
+pub mod kmers;
 pub mod key_value;
 pub mod lazy_value;
+pub mod ordered_pool;
 pub mod rng;
 pub mod seqtype;
 pub mod twoway_iter;
@@ -0,0 +1,86 @@
+//! Thread pool processing jobs in parallel while returning the results
+//! in the order of submission
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Job<I, O> = (I, Sender<O>);
+
+pub struct OrderedPool<I, O> {
+    job_send: Option<Sender<Job<I, O>>>,
+    pending: VecDeque<Receiver<O>>,
+    max_pending: usize,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl<I, O> OrderedPool<I, O>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    /// Creates a pool with `n_threads` workers. The queue is considered full
+    /// (see `is_full`) with `2 * n_threads` pending jobs.
+    pub fn new<F>(n_threads: u32, func: F) -> OrderedPool<I, O>
+    where
+        F: Fn(I) -> O + Send + Sync + 'static,
+    {
+        let (job_send, job_recv) = channel::<Job<I, O>>();
+        let job_recv = Arc::new(Mutex::new(job_recv));
+        let func = Arc::new(func);
+        let threads = (0..n_threads)
+            .map(|_| {
+                let job_recv = job_recv.clone();
+                let func = func.clone();
+                thread::spawn(move || loop {
+                    // the lock is released before processing
+                    let job = job_recv.lock().unwrap().recv();
+                    match job {
+                        Ok((input, out_send)) => {
+                            out_send.send(func(input)).ok();
+                        }
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+
+        OrderedPool {
+            job_send: Some(job_send),
+            pending: VecDeque::new(),
+            max_pending: 2 * n_threads as usize,
+            threads: threads,
+        }
+    }
+
+    /// Submits a new job without waiting. The caller should first obtain
+    /// results with `recv` if the queue is full.
+    pub fn submit(&mut self, input: I) {
+        let (out_send, out_recv) = channel();
+        self.job_send.as_ref().unwrap().send((input, out_send)).unwrap();
+        self.pending.push_back(out_recv);
+    }
+
+    /// Waits for the oldest pending job and returns its result, or `None` if
+    /// there are no pending jobs.
+    pub fn recv(&mut self) -> Option<O> {
+        self.pending
+            .pop_front()
+            .map(|r| r.recv().expect("Worker thread panicked"))
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= self.max_pending
+    }
+}
+
+impl<I, O> Drop for OrderedPool<I, O> {
+    fn drop(&mut self) {
+        // closing the channel stops the workers
+        self.job_send.take();
+        for t in self.threads.drain(..) {
+            t.join().ok();
+        }
+    }
+}
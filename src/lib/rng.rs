@@ -10,7 +10,7 @@ use var::varstring::VarString;
 
 /// Represents a range bound integer stored either directly or in a `VarString`
 /// that is evaluated later with `RngBound::value()`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RngBound {
     Number(isize),
     Expr(VarString),
@@ -40,7 +40,7 @@ impl RngBound {
 
 /// Represents a range that is either stored directly or evaluated
 /// later
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum VarRange {
     /// range (..) notation already found in input text
     Split(Option<RngBound>, Option<RngBound>),
@@ -88,13 +88,13 @@ impl VarRange {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum VarRangesType {
     Split(Vec<VarRange>),
     Full(VarString),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VarRanges {
     ty: VarRangesType,
     out: Vec<(usize, usize)>,
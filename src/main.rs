@@ -1,5 +1,6 @@
 // used everywhere
 extern crate bio;
+extern crate crossbeam;
 extern crate csv;
 extern crate docopt;
 extern crate fxhash;
@@ -35,6 +36,7 @@ extern crate termcolor;
 extern crate bzip2;
 extern crate flate2;
 extern crate lz4;
+extern crate xz2;
 extern crate zstd;
 
 use self::error::*;
@@ -117,10 +119,13 @@ fn run_cmd(cmd: &str) -> CliResult<()> {
         "set" => cmd::set::run(),
         "del" => cmd::del::run(),
         "find" => cmd::find::run(),
+        "align" => cmd::align::run(),
+        "screen" => cmd::screen::run(),
         "replace" => cmd::replace::run(),
         #[cfg(feature = "exprtk")]
         "filter" => cmd::filter::run(),
         "count" => cmd::count::run(),
+        "kmers" => cmd::kmers::run(),
         "at" => cmd::stat::run(),
         "upper" => cmd::upper::run(),
         "lower" => cmd::lower::run(),
@@ -128,6 +133,8 @@ fn run_cmd(cmd: &str) -> CliResult<()> {
         "revcomp" => cmd::revcomp::run(),
         "interleave" => cmd::interleave::run(),
         "concat" => cmd::concat::run(),
+        "consensus" => cmd::consensus::run(),
+        "aln" => cmd::aln::run(),
         "view" => cmd::view::run(),
         _ => Err(CliError::Other(
             concat!("Unknown command! Available commands:\n", command_list!()).to_string(),
@@ -46,6 +46,17 @@ impl Args {
         Ok(n)
     }
 
+    pub fn compr_threads(&self) -> CliResult<u32> {
+        let n = self.get_str("--compr-threads");
+        let n = n
+            .parse()
+            .map_err(|_| format!("Invalid number of compression threads: {}", n))?;
+        if n == 0 {
+            return fail!("The number of compression threads must be > 0");
+        }
+        Ok(n)
+    }
+
     pub fn get_input_opts(&self) -> CliResult<Vec<InputOptions>> {
         let mut paths = self.get_vec("<input>");
         if paths.is_empty() {
@@ -90,6 +101,7 @@ impl Args {
         let cap = parse_bytesize(self.get_str("--buf-cap"))?.floor() as usize;
         let max_mem = parse_bytesize(self.get_str("--max-mem"))?.floor() as usize;
         let threaded = self.get_bool("--read-thread");
+        let compr_threads = self.compr_threads()?;
         let thread_bufsize = self
             .opt_str("--read-tbufsize")
             .map_res(|s| parse_bytesize(s))?
@@ -105,22 +117,42 @@ impl Args {
         let input: Vec<_> = paths
             .into_iter()
             .map(|path| {
-                let (kind, compression, fmt_str) = if path == "-" {
-                    (
-                        InputType::Stdin,
-                        arg_compr.unwrap_or(Compression::None),
-                        arg_fmt.clone().unwrap_or_else(|| "fasta".to_string()),
-                    )
+                let kind = if path == "-" {
+                    InputType::Stdin
                 } else {
-                    let (path_fmt, path_compr) = path_info(&path);
-
-                    (
-                        InputType::File(PathBuf::from(&path)),
-                        arg_compr.or(path_compr).unwrap_or(Compression::None),
-                        arg_fmt
-                            .clone()
-                            .unwrap_or_else(|| path_fmt.unwrap_or("fasta").to_string()),
-                    )
+                    InputType::File(PathBuf::from(&path))
+                };
+
+                // Compression is recognized from the magic bytes, unless specified
+                // with --fmt (e.g. fasta.gz)
+                let compression = match arg_compr {
+                    Some(c) if c != Compression::None => c,
+                    _ => detect_compression(&kind)?,
+                };
+
+                // If not specified, the format is recognized from the content,
+                // the extension is only used as fallback
+                let fmt_str = match arg_fmt {
+                    Some(ref f) => f.clone(),
+                    None => match sniff_input(&kind, compression)? {
+                        Some(detected) => {
+                            if let Detected::Fastq(q) = detected {
+                                if q != QualFormat::Sanger {
+                                    eprintln!(
+                                        "Warning: The quality scores of '{}' seem to be in the {} \
+                                         format, use --fmt fastq if this is wrong.",
+                                        kind,
+                                        detected.name()?
+                                    );
+                                }
+                            }
+                            detected.name()?.to_string()
+                        }
+                        None => {
+                            let path_fmt = if path == "-" { None } else { path_info(&path).0 };
+                            path_fmt.unwrap_or("fasta").to_string()
+                        }
+                    },
                 };
 
                 let format = InFormat::from_opts(&fmt_str, delim, fields, header, qfile)?;
@@ -129,6 +161,7 @@ impl Args {
                     kind,
                     format,
                     compression,
+                    compr_threads,
                     threaded,
                     thread_bufsize,
                     cap,
@@ -153,6 +186,10 @@ impl Args {
             (Some("csv"), Some(","), Some(fields))
         } else if let Some(fields) = self.opt_str("--to-tsv") {
             (Some("tsv"), Some("\t"), Some(fields))
+        } else if let Some(fields) = self.opt_str("--to-json") {
+            (Some("json"), None, Some(fields))
+        } else if let Some(fields) = self.opt_str("--to-jsonl") {
+            (Some("jsonl"), None, Some(fields))
         } else {
             (None, None, None)
         };
@@ -178,6 +215,7 @@ impl Args {
             .map(|s| s as usize);
         let compr_level = self.opt_value("--compr-level")?;
         let qfile = self.opt_str("--qual-out");
+        let header = self.opt_str("--out-header");
 
         let (arg_fmt, arg_compr) = self
             .opt_str("--to")
@@ -206,14 +244,19 @@ impl Args {
                 }),
             )
         };
+        let compr = compr.unwrap_or(Compression::None);
+        if let Some(l) = compr_level {
+            compr.check_level(l)?;
+        }
 
         Ok(OutputOptions {
             kind: kind,
             format: OutFormat::from_opts(
-                fmt_opts, &attrs, wrap_fasta, csv_delim, csv_fields, informat, qfile,
+                fmt_opts, &attrs, header, wrap_fasta, csv_delim, csv_fields, informat, qfile,
             )?,
-            compression: compr.unwrap_or(Compression::None),
+            compression: compr,
             compression_level: compr_level,
+            compr_threads: self.compr_threads()?,
             threaded: threaded,
             thread_bufsize: thread_bufsize,
         })
@@ -238,6 +281,7 @@ impl Args {
                 value_delim: self
                     .opt_string_or_env("--aval-delim", "ST_ATTRVAL_DELIM")
                     .unwrap_or_else(|| "=".to_string()),
+                format: self.opt_string_or_env("--attr-fmt", "ST_ATTR_FORMAT"),
             },
             allow_missing: self.0.get_bool("--missing"),
             var_help: self.0.get_bool("--help-vars"),
@@ -303,6 +347,30 @@ impl Args {
 }
 
 pub fn path_info<P: AsRef<Path>>(path: &P) -> (Option<&'static str>, Option<Compression>) {
+    let (ext, compr) = ext_compr(path);
+
+    let fmt = match ext {
+        Some(ext) => match ext.to_ascii_lowercase().as_str() {
+            "fastq" | "fq" => Some("fastq"),
+            "fasta" | "fa" | "fna" | "fsa" => Some("fasta"),
+            "csv" => Some("csv"),
+            "tsv" | "txt" => Some("tsv"),
+            "json" => Some("json"),
+            "jsonl" | "ndjson" => Some("jsonl"),
+            _ => {
+                eprintln!("Unknown extension: '{}', assuming FASTA format", ext);
+                None
+            }
+        },
+        None => None,
+    };
+
+    (fmt, compr)
+}
+
+/// Returns the file extension (without the compression extension, if any)
+/// and the compression format
+pub fn ext_compr<P: AsRef<Path>>(path: &P) -> (Option<&str>, Option<Compression>) {
     let path = path.as_ref();
     let ext = match path.extension().and_then(OsStr::to_str) {
         Some(ext) => ext,
@@ -311,9 +379,11 @@ pub fn path_info<P: AsRef<Path>>(path: &P) -> (Option<&'static str>, Option<Comp
 
     let compr = match ext.to_ascii_lowercase().as_str() {
         "gz" | "gzip" => Some(Compression::GZIP),
+        "bgz" | "bgzf" => Some(Compression::BGZF),
         "bz2" | "bzip2" => Some(Compression::BZIP2),
         "lz4" => Some(Compression::LZ4),
         "zst" => Some(Compression::ZSTD),
+        "xz" => Some(Compression::XZ),
         _ => None,
     };
 
@@ -324,21 +394,7 @@ pub fn path_info<P: AsRef<Path>>(path: &P) -> (Option<&'static str>, Option<Comp
 
     let path = if compr.is_some() { stem } else { path };
 
-    let fmt = match path.extension().and_then(OsStr::to_str) {
-        Some(ext) => match ext.to_ascii_lowercase().as_str() {
-            "fastq" | "fq" => Some("fastq"),
-            "fasta" | "fa" | "fna" | "fsa" => Some("fasta"),
-            "csv" => Some("csv"),
-            "tsv" | "txt" => Some("tsv"),
-            _ => {
-                eprintln!("Unknown extension: '{}', assuming FASTA format", ext);
-                None
-            }
-        },
-        None => None,
-    };
-
-    (fmt, compr)
+    (path.extension().and_then(OsStr::to_str), compr)
 }
 
 pub fn parse_attr(text: &str) -> CliResult<(String, String)> {
@@ -364,7 +420,7 @@ pub fn parse_format_str(string: &str) -> CliResult<(String, Compression)> {
     } else {
         let compr = Compression::from_str(parts[1]).ok_or_else(|| {
             format!(
-                "Unknown compression format: '{}'. Valid formats are gz, bz2, lz4, 7z",
+                "Unknown compression format: '{}'. Valid formats are gz, bgz, bz2, lz4, zst and xz",
                 parts[1]
             )
         })?;
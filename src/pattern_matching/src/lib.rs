@@ -18,3 +18,4 @@ impl<'a, T: IntoIterator<Item = &'a u8>> IntoTextIterator<'a> for T {}
 
 
 pub mod myers;
+pub mod myers_long;
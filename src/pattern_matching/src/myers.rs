@@ -7,7 +7,7 @@
 
 //! Myers bit-parallel approximate pattern matching algorithm.
 //! Finds all matches up to a given edit distance. The pattern has to fit into a bitvector,
-//! and is here limited to 64 symbols.
+//! and is here limited to 64 symbols (see `myers_long` for longer patterns).
 //! Complexity: O(n)
 //!
 //! # Example
@@ -226,7 +226,7 @@ impl State {
     /// Create new state.
     pub fn new(m: u8) -> Self {
         State {
-            pv: (1 << m) - 1,
+            pv: u64::MAX >> (64 - m),
             mv: 0,
             dist: m,
         }
@@ -381,11 +381,13 @@ impl Traceback {
 
         macro_rules! move_up_many {
             ($state:expr, $n:expr) => {
-                let mask = ((1 << $n) - 1) << (self.m - $n);
+                // avoid overflowing shifts if m = 64
+                let mask = 1u64.checked_shl($n as u32).unwrap_or(0).wrapping_sub(1)
+                    .checked_shl((self.m - $n) as u32).unwrap_or(0);
                 $state.dist += (($state.mv & mask)).count_ones() as u8;
                 $state.dist -= (($state.pv & mask)).count_ones() as u8;
-                $state.mv <<= $n;
-                $state.pv <<= $n;
+                $state.mv = $state.mv.checked_shl($n as u32).unwrap_or(0);
+                $state.pv = $state.pv.checked_shl($n as u32).unwrap_or(0);
 
                 // equally fast:
                 // let n = self.m - $n;
@@ -0,0 +1,484 @@
+//! Block-based variant of Myers' bit-parallel approximate pattern matching algorithm
+//! (Hyyrö 2003), which is not restricted to patterns of 64 symbols. The pattern
+//! is split into blocks of 64 symbols, and the horizontal deltas are passed
+//! from block to block. The API corresponds to `myers::Myers`, but distances
+//! are of type `usize`.
+//!
+//! # Example
+//!
+//! ```
+//! use pattern_matching::myers_long::MyersLong;
+//!
+//! # fn main() {
+//! let text = b"ACCGTGGATGAGCGCCATAG";
+//! let pattern =      b"TGAGCGT";
+//!
+//! let myers = MyersLong::new(pattern);
+//! let occ: Vec<_> = myers.find_all_end(text, 1).collect();
+//!
+//! assert_eq!(occ, [(13, 1), (14, 1)]);
+//! # }
+//! ```
+
+use std::iter;
+use std::u64;
+
+use super::*;
+use myers::AlignmentOperation;
+use myers::AlignmentOperation::*;
+
+const WORD_SIZE: usize = 64;
+const HIGH_BIT: u64 = 1 << (WORD_SIZE - 1);
+
+/// Myers algorithm for patterns of any length.
+pub struct MyersLong {
+    // bit vectors of the pattern: peq[symbol * n_blocks + block]
+    peq: Vec<u64>,
+    m: usize,
+    n_blocks: usize,
+    // mask for the last row of the pattern in the last block
+    last_bound: u64,
+    tb: Traceback,
+}
+
+impl MyersLong {
+    /// Create a new instance of the algorithm for a given pattern.
+    pub fn new<'a, P: IntoTextIterator<'a>>(pattern: P) -> Self {
+        Self::from_variants(pattern.into_iter().cloned().map(Some))
+    }
+
+    /// Like `MyersLong::new()`, but additionally allows for specifying
+    /// multiple matching characters in a pattern (see `Myers::from_variants`).
+    pub fn from_variants<P, I>(pattern: P) -> Self
+        where P: IntoIterator<Item=I>,
+              I: IntoIterator<Item=u8>
+    {
+        let variants: Vec<Vec<u8>> = pattern
+            .into_iter()
+            .map(|v| v.into_iter().collect())
+            .collect();
+        let m = variants.len();
+        assert!(m > 0);
+
+        let n_blocks = (m + WORD_SIZE - 1) / WORD_SIZE;
+        let mut peq = vec![0; 256 * n_blocks];
+        for (i, var) in variants.into_iter().enumerate() {
+            for a in var {
+                peq[a as usize * n_blocks + i / WORD_SIZE] |= 1 << (i % WORD_SIZE);
+            }
+        }
+
+        MyersLong {
+            peq: peq,
+            m: m,
+            n_blocks: n_blocks,
+            last_bound: 1 << ((m - 1) % WORD_SIZE),
+            tb: Traceback::new(),
+        }
+    }
+
+    /// Create a new instance of the algorithm for a given pattern and a wildcard
+    /// character that shall match any character.
+    pub fn with_wildcard(pattern: TextSlice, wildcard: u8) -> Self {
+        let mut myers = Self::new(pattern);
+        let n = myers.n_blocks;
+        for b in &mut myers.peq[wildcard as usize * n..(wildcard as usize + 1) * n] {
+            *b = u64::MAX;
+        }
+        myers
+    }
+
+    fn step(&self, state: &mut State, a: u8) {
+        let peq = &self.peq[a as usize * self.n_blocks..(a as usize + 1) * self.n_blocks];
+        // horizontal delta entering the current block from above
+        // (the first row is always 0 in semi-global alignment)
+        let mut hin = 0i8;
+
+        for (b, &eq) in peq.iter().enumerate() {
+            let pv = state.pv[b];
+            let mv = state.mv[b];
+            let hin_neg = (hin < 0) as u64;
+
+            let xv = eq | mv;
+            let eq = eq | hin_neg;
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+
+            let bound = if b + 1 == self.n_blocks { self.last_bound } else { HIGH_BIT };
+            let hout = if ph & bound != 0 {
+                1
+            } else if mh & bound != 0 {
+                -1
+            } else {
+                0
+            };
+
+            ph <<= 1;
+            mh <<= 1;
+            mh |= hin_neg;
+            ph |= (hin > 0) as u64;
+            state.pv[b] = mh | !(xv | ph);
+            state.mv[b] = ph & xv;
+            hin = hout;
+        }
+
+        if hin > 0 {
+            state.dist += 1;
+        } else if hin < 0 {
+            state.dist -= 1;
+        }
+    }
+
+    fn step_trace(&mut self, state: &mut State, a: u8) {
+        self.step(state, a);
+        self.tb.add_state(state);
+    }
+
+    /// Calculate the global distance of the pattern to the given text.
+    pub fn distance<'a, I: IntoTextIterator<'a>>(&self, text: I) -> usize {
+        let mut state = State::new(self.m, self.n_blocks);
+        for &a in text {
+            self.step(&mut state, a);
+        }
+        state.dist
+    }
+
+    /// Find all matches of pattern in the given text up to a given maximum distance.
+    /// Matches are returned as an iterator over pairs of end position and distance.
+    pub fn find_all_end<'a, I: IntoTextIterator<'a>>(&'a self,
+                                                     text: I,
+                                                     max_dist: usize)
+                                                     -> Matches<I::IntoIter> {
+        Matches {
+            myers: self,
+            state: State::new(self.m, self.n_blocks),
+            text: text.into_iter().enumerate(),
+            max_dist: max_dist,
+        }
+    }
+
+    /// Find all matches of pattern in the given text up to a given maximum distance.
+    /// Matches are returned as an iterator over ranges of (start, end, distance),
+    /// see `Myers::find_all_pos()`. The alignment path can be obtained using
+    /// `FullMatches::next_path()`.
+    pub fn find_all_pos<'a, I: IntoTextIterator<'a>>(&'a mut self,
+                                                     text: I,
+                                                     max_dist: usize)
+                                                     -> FullMatches<I::IntoIter> {
+        self.tb.init(self.m, self.n_blocks, max_dist);
+        let state = State::new(self.m, self.n_blocks);
+        FullMatches {
+            myers: self,
+            state: state,
+            text: text.into_iter().enumerate(),
+            max_dist: max_dist,
+        }
+    }
+}
+
+
+/// The current algorithm state (one column of the DP matrix).
+#[derive(Clone, Debug, Default)]
+struct State {
+    pv: Vec<u64>,
+    mv: Vec<u64>,
+    dist: usize,
+}
+
+impl State {
+    fn new(m: usize, n_blocks: usize) -> Self {
+        State {
+            pv: vec![u64::MAX; n_blocks],
+            mv: vec![0; n_blocks],
+            dist: m,
+        }
+    }
+
+    #[inline]
+    fn pv_bit(&self, i: usize) -> bool {
+        self.pv[i / WORD_SIZE] & (1 << (i % WORD_SIZE)) != 0
+    }
+
+    /// Returns the distance at row `i` (0 = top, m = bottom of the column)
+    fn row_dist(&self, i: usize, m: usize) -> usize {
+        // D[i] = D[m] - sum of the vertical deltas of the rows i+1..m,
+        // which are stored at bit positions i..m-1
+        let mut plus = 0;
+        let mut minus = 0;
+        let mut pos = i;
+        while pos < m {
+            let b = pos / WORD_SIZE;
+            let offset = pos % WORD_SIZE;
+            let end = ::std::cmp::min(m, (b + 1) * WORD_SIZE);
+            let n = end - pos;
+            let mask = if n == WORD_SIZE { u64::MAX } else { ((1 << n) - 1) << offset };
+            plus += (self.pv[b] & mask).count_ones() as usize;
+            minus += (self.mv[b] & mask).count_ones() as usize;
+            pos = end;
+        }
+        self.dist + minus - plus
+    }
+}
+
+
+/// Iterator over pairs of end positions and distance of matches.
+pub struct Matches<'a, I: TextIterator<'a>> {
+    myers: &'a MyersLong,
+    state: State,
+    text: iter::Enumerate<I>,
+    max_dist: usize,
+}
+
+impl<'a, I: Iterator<Item = &'a u8>> Iterator for Matches<'a, I> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        for (i, &a) in self.text.by_ref() {
+            self.myers.step(&mut self.state, a);
+            if self.state.dist <= self.max_dist {
+                return Some((i, self.state.dist));
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over (start, end, distance) of matches.
+pub struct FullMatches<'a, I: TextIterator<'a>> {
+    myers: &'a mut MyersLong,
+    state: State,
+    text: iter::Enumerate<I>,
+    max_dist: usize,
+}
+
+impl<'a, I: TextIterator<'a>> FullMatches<'a, I> {
+
+    pub fn next_path(&mut self, ops: &mut Vec<AlignmentOperation>) -> Option<(usize, usize, usize)> {
+        self.find_next(Some(ops))
+    }
+
+    pub fn find_next(&mut self, ops: Option<&mut Vec<AlignmentOperation>>) -> Option<(usize, usize, usize)> {
+        for (i, &a) in self.text.by_ref() {
+            self.myers.step_trace(&mut self.state, a);
+            if self.state.dist <= self.max_dist {
+                let h_offset = self.myers.tb.traceback(ops);
+                return Some((i + 1 - h_offset, i + 1, self.state.dist));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a u8>> Iterator for FullMatches<'a, I> {
+    type Item = (usize, usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize, usize)> {
+        self.find_next(None)
+    }
+}
+
+
+/// Ring buffer of the last m + k + 1 columns
+struct Traceback {
+    states: Vec<State>,
+    num_cols: usize,
+    pos: usize,
+    m: usize,
+}
+
+impl Traceback {
+    fn new() -> Traceback {
+        Traceback {
+            states: vec![],
+            num_cols: 0,
+            pos: 0,
+            m: 0,
+        }
+    }
+
+    fn init(&mut self, m: usize, n_blocks: usize, k: usize) {
+        self.m = m;
+        self.num_cols = m + k + 1;
+        if self.states.len() < self.num_cols {
+            self.states.resize(self.num_cols, State::default());
+        }
+        // leftmost column: D[i] = i
+        self.states[0] = State::new(m, n_blocks);
+        self.pos = 0;
+    }
+
+    #[inline]
+    fn add_state(&mut self, s: &State) {
+        self.pos = (self.pos + 1) % self.num_cols;
+        self.states[self.pos].clone_from(s);
+    }
+
+    /// Returns the length of the current match, optionally adding the
+    /// alignment path to `ops`
+    fn traceback(&self, mut ops: Option<&mut Vec<AlignmentOperation>>) -> usize {
+        if let Some(o) = ops.as_mut() {
+            o.clear();
+        }
+
+        let m = self.m;
+        let mut pos = self.pos;
+        // horizontal distance from right end
+        let mut h_offset = 0;
+        // current row and distance
+        let mut i = m;
+        let mut dist = self.states[pos].dist;
+
+        while i > 0 {
+            let state = &self.states[pos];
+            let op = if state.pv_bit(i - 1) {
+                // up
+                i -= 1;
+                dist -= 1;
+                Ins
+            } else {
+                let lpos = if pos == 0 { self.num_cols - 1 } else { pos - 1 };
+                let lstate = &self.states[lpos];
+                let op = if lstate.row_dist(i, m) + 1 == dist {
+                    // left
+                    Del
+                } else {
+                    // diagonal
+                    i -= 1;
+                    if lstate.row_dist(i, m) == dist {
+                        Match
+                    } else {
+                        Subst
+                    }
+                };
+                // move left
+                pos = lpos;
+                dist = lstate.row_dist(i, m);
+                h_offset += 1;
+                op
+            };
+
+            if let Some(o) = ops.as_mut() {
+                o.push(op);
+            }
+        }
+
+        if let Some(o) = ops.as_mut() {
+            o.reverse();
+        }
+
+        h_offset
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use myers::Myers;
+
+    // simple semi-global edit distance DP for comparison
+    fn end_dists(pattern: &[u8], text: &[u8]) -> Vec<usize> {
+        let mut col: Vec<usize> = (0..pattern.len() + 1).collect();
+        text.iter()
+            .map(|&c| {
+                let mut diag = col[0];
+                for i in 1..col.len() {
+                    let d = if pattern[i - 1] == c { diag } else { diag + 1 };
+                    diag = col[i];
+                    col[i] = *[d, col[i] + 1, col[i - 1] + 1].iter().min().unwrap();
+                }
+                col[pattern.len()]
+            })
+            .collect()
+    }
+
+    fn pseudo_random_seq(len: usize, seed: u64) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                b"ACGT"[(x >> 33) as usize % 4]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_distance() {
+        let text = b"TGAGCNT";
+        let pattern = b"TGAGCGT";
+
+        let myers = MyersLong::new(pattern);
+        assert_eq!(myers.distance(text), 1);
+
+        let myers_wildcard = MyersLong::with_wildcard(pattern, b'N');
+        assert_eq!(myers_wildcard.distance(text), 0);
+    }
+
+    #[test]
+    fn test_same_as_short() {
+        // patterns up to 64 symbols: same results as `Myers`
+        let text = pseudo_random_seq(300, 1);
+        for &len in &[1, 5, 30, 63, 64] {
+            let pattern = pseudo_random_seq(len, len as u64);
+            let k = len / 2;
+            let mut myers = Myers::new(&pattern);
+            let mut myers_long = MyersLong::new(&pattern);
+
+            let ends: Vec<_> = myers.find_all_end(&text, k as u8)
+                .map(|(e, d)| (e, d as usize))
+                .collect();
+            let ends_long: Vec<_> = myers_long.find_all_end(&text, k).collect();
+            assert_eq!(ends, ends_long);
+
+            let mut aln = vec![];
+            let mut aln_long = vec![];
+            let mut matches = myers.find_all_pos(&text, k as u8);
+            let mut matches_long = myers_long.find_all_pos(&text, k);
+            while let Some((s, e, d)) = matches.next_path(&mut aln) {
+                assert_eq!(matches_long.next_path(&mut aln_long), Some((s, e, d as usize)));
+                assert_eq!(aln, aln_long);
+            }
+            assert!(matches_long.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_long() {
+        let mut text = pseudo_random_seq(500, 2);
+        let pattern = text[100..300].to_vec();
+        // introduce some differences
+        text[120] = b'N';
+        text.remove(200);
+        text.insert(250, b'A');
+        text.insert(250, b'A');
+
+        let myers = MyersLong::new(&pattern);
+        let dists = end_dists(&pattern, &text);
+        let ends: Vec<_> = myers.find_all_end(&text, 30).collect();
+        let expected: Vec<_> = dists.iter().cloned().enumerate().filter(|&(_, d)| d <= 30).collect();
+        assert_eq!(ends, expected);
+
+        let mut myers = MyersLong::new(&pattern);
+        let mut aln = vec![];
+        let mut matches = myers.find_all_pos(&text, 4);
+        let (start, end, dist) = matches.next_path(&mut aln).unwrap();
+        assert_eq!((start, dist), (100, 4));
+        assert_eq!(end - start, aln.iter().filter(|&&op| op != Ins).count());
+        assert_eq!(aln.iter().filter(|&&op| op != Del).count(), pattern.len());
+        assert_eq!(aln.iter().filter(|&&op| op != Match).count(), 4);
+    }
+
+    #[test]
+    fn test_shorter() {
+        let text = "ATG";
+        let pattern: String = iter::repeat("CATGC").take(20).collect();
+
+        let mut myers = MyersLong::new(pattern.as_bytes());
+        let mut matches = myers.find_all_pos(text.as_bytes(), 97);
+        let mut aln = vec![];
+        assert_eq!(matches.find_next(Some(&mut aln)).unwrap(), (0, 3, 97));
+        assert_eq!(aln.len(), 100);
+    }
+}
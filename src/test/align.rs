@@ -0,0 +1,42 @@
+
+use super::*;
+
+
+#[test]
+fn align() {
+    let fasta = ">s1\nTTTACGTACGTTTT\n>s2\nGGACGACGTGG\n";
+    let vars = "id,aln:score,aln:ident,aln:cigar,aln:start,aln:end,aln:ref_start,aln:ref_end";
+    Tester::new()
+        .cmp(&["align", "--to-tsv", vars, "ACGTACGT"], fasta,
+             "s1\t8\t1\t3S8=3S\t4\t11\t1\t8\ns2\t1\t0.875\t2S3=1D4=2S\t3\t9\t1\t8\n")
+        .cmp(&["align", "--to-tsv", vars, "-t", "2", "ACGTACGT"], fasta,
+             "s1\t8\t1\t3S8=3S\t4\t11\t1\t8\ns2\t1\t0.875\t2S3=1D4=2S\t3\t9\t1\t8\n")
+        .cmp(&["align", "--to-tsv", "id,aln:score,aln:cigar", "--mode", "local", "ACGTACGT"],
+             fasta, "s1\t8\t3S8=3S\ns2\t4\t5S4=2S\n")
+        .cmp(&["align", "--to-tsv", "id,aln:score,aln:ref_start,aln:ref_end", "--mode", "seq-global",
+               "TTTTTACGTACGTTTTTT"], ">s1\nTTTACGTACGTTTT\n", "s1\t14\t3\t16\n")
+        .cmp(&["align", "--to-tsv", "id,aln:score", "--mode", "local", "GGGG"], ">s\nTTTT\n", "s\t0\n")
+        .cmp(&["align", "--out", "trim", "ACGTACGT"], fasta, ">s1\nACGTACGT\n>s2\nACGACGT\n")
+        .cmp(&["align", "--out", "trim", "--fq", "ACGT"], "@s\nTTACGTT\n+\n1234567\n",
+             "@s\nACGT\n+\n3456\n")
+        .cmp(&["align", "--out", "aligned", "ACGTACGT"], fasta, ">s1\nACGTACGT\n>s2\nACG-ACGT\n")
+        .fails(&["align", "--out", "aligned", "--fq", "ACGT"], "@s\nTTACGTT\n+\n1234567\n",
+               "cannot be written together with quality scores")
+        .fails(&["align", "--match", "-1", "ACGT"], fasta, "The match score must be >= 0");
+}
+
+#[test]
+fn align_multiple() {
+    let fasta = ">s\nTTACGTT\n";
+    let t = Tester::new();
+    t.temp_file("refs.fa", Some(">r1\nGGGG\n>r2\nACGT\n"), |p, _| {
+        let refs = format!("file:{}", p);
+        t.cmp(&["align", "--to-tsv", "id,aln:ref,aln:score", &refs], fasta, "s\tr2\t4\n");
+    });
+    // compressed references
+    t.temp_file("refs.fa.gz", None, |p, _| {
+        t.succeeds(&[".", "-o", p], ">r1\nGGGG\n>r2\nACGT\n");
+        let refs = format!("file:{}", p);
+        t.cmp(&["align", "--to-tsv", "id,aln:ref,aln:score", &refs], fasta, "s\tr2\t4\n");
+    });
+}
@@ -0,0 +1,57 @@
+
+use super::*;
+
+
+static MSA: &'static str = ">a d1\nAC-GT-A\n>b\nACTGT-A\n>c\nAT-GA-A\n";
+
+#[test]
+fn aln_cols() {
+    Tester::new()
+        .cmp(&["aln", "check"], MSA, "3 sequences, 7 columns\n")
+        .fails(&["aln", "check"], ">a\nACG\n>b\nAC\n", "The sequences are not aligned")
+        .cmp(&["aln", "degap"], MSA, ">a d1\nAC-GTA\n>b\nACTGTA\n>c\nAT-GAA\n")
+        .cmp(&["aln", "degap", "--max-gaps", "0.4"], MSA, ">a d1\nACGTA\n>b\nACGTA\n>c\nATGAA\n")
+        .cmp(&["aln", "cols", "2..3,-1"], MSA, ">a d1\nC-A\n>b\nCTA\n>c\nT-A\n")
+        .cmp(&["aln", "cols", "-e", "2..3"], MSA, ">a d1\nAGT-A\n>b\nAGT-A\n>c\nAGA-A\n");
+}
+
+#[test]
+fn aln_consensus() {
+    Tester::new()
+        .cmp(&["aln", "consensus"], MSA, ">consensus\nAY-GW-A\n")
+        .cmp(&["aln", "consensus", "--min-freq", "0.5", "--ungapped", "--id", "c"], MSA,
+             ">c\nACGTA\n")
+        .cmp(&["aln", "consensus", "--majority"], MSA, ">consensus\nAC-GT-A\n")
+        .cmp(&["aln", "consensus"], ">a\nACGU\n>b\nARGU\n", ">consensus\nAVGU\n")
+        .fails(&["aln", "consensus"], ">a\nEF\n>b\nEF\n", "use --majority")
+        .cmp(&["aln", "conservation"], ">a\nA-\n>b\nC-\n>c\nC-\n",
+             "column\tresidue\tfreq\tgaps\tentropy\n1\tC\t0.6667\t0\t0.9183\n2\t-\t0\t1\t0\n");
+}
+
+#[test]
+fn aln_formats() {
+    let fasta = ">a\nAC-GT\n>b\nACTGT\n";
+    let phylip = "2 5\na  AC-GT\nb  ACTGT\n";
+    let nexus = "#NEXUS\nBEGIN DATA;\n  DIMENSIONS NTAX=2 NCHAR=5;\n  FORMAT DATATYPE=DNA MISSING=? GAP=-;\n\
+                 MATRIX\na  AC-GT\nb  ACTGT\n;\nEND;\n";
+    let stockholm = "# STOCKHOLM 1.0\na  AC-GT\nb  ACTGT\n//\n";
+    Tester::new()
+        .cmp(&["aln", "convert", "--aln-out", "phylip"], fasta, phylip)
+        .cmp(&["aln", "convert", "--aln-out", "nexus"], fasta, nexus)
+        .cmp(&["aln", "convert", "--aln-out", "stockholm"], fasta, stockholm)
+        .cmp(&["aln", "convert", "--aln-in", "phylip"], phylip, fasta)
+        .cmp(&["aln", "convert", "--aln-in", "nexus"], nexus, fasta)
+        .cmp(&["aln", "convert", "--aln-in", "stockholm"], stockholm, fasta)
+        // interleaved / multi-block input
+        .cmp(&["aln", "convert", "--aln-in", "phylip"], "2 5\na AC\nb AC\n\n-GT\nTGT\n", fasta)
+        .cmp(&["aln", "convert", "--aln-in", "phylip"], "2 5\na AC\n-GT\nb AC\nTGT\n", fasta)
+        // interleaved without empty lines between the blocks
+        .cmp(&["aln", "convert", "--aln-in", "phylip"], "2 5\na AC\nb AC\n-GT\nTGT\n", fasta)
+        .fails(&["aln", "convert", "--aln-in", "phylip"], "2 4\na AC\nb AC\n-GT\nTGT\n", "longer than 4")
+        .fails(&["aln", "convert", "--aln-in", "phylip"], " 0 4\n", "Invalid PHYLIP header")
+        .fails(&["aln", "convert", "--aln-in", "phylip"], "2 0\na\nb\n", "Invalid PHYLIP header")
+        .cmp(&["aln", "convert", "--aln-in", "nexus"],
+             "#NEXUS\nbegin data;\nmatrix [comment]\n'a' AC\nb AC\n\na -GT\nb TGT;\nend;\n", fasta)
+        .cmp(&["aln", "convert", "--aln-in", "stockholm"],
+             "# STOCKHOLM 1.0\n#=GF ID x\na AC\nb AC\n\na -GT\nb TGT\n//\n", fasta);
+}
@@ -1,4 +1,6 @@
 
+use std::fs::File;
+
 use super::*;
 
 
@@ -20,10 +22,47 @@ fn compress_pipe() {
         .pipe(
             &[".", "--to", "fasta.zst", "--compr-level", "9"], &FASTA,
             &[".", "--fmt", "fasta.zst"], &FASTA
+        )
+        .pipe(
+            &[".", "--to", "fasta.xz", "--compr-level", "9"], &FASTA,
+            &[".", "--fmt", "fasta.xz"], &FASTA
+        )
+        .pipe(
+            &[".", "--to", "fasta.bgz", "--compr-level", "9"], &FASTA,
+            &[".", "--fmt", "fasta.bgz"], &FASTA
         );
 }
 
 
+#[test]
+fn compress_detect() {
+    let t = Tester::new();
+    // compression is recognized from the magic bytes
+    for ext in &["gz", "bgz", "bz2", "lz4", "zst", "xz"] {
+        t.pipe(&[".", "--to", &format!("fasta.{}", ext)], &FASTA, &["."], &FASTA)
+            .pipe(&[".", "--to", &format!("fasta.{}", ext)], &FASTA, &[".", "--fmt", "fasta"], &FASTA);
+    }
+
+    // multi-member gzip and concatenated zstd frames
+    t.temp_dir("compress_detect", |d| {
+        for &(name, compr) in &[
+            ("multi.gz", "fasta.gz"),
+            ("multi.fa", "fasta.zst"),
+            ("multi.xz", "fasta.xz"),
+        ] {
+            let path = d.path().join(name);
+            let path = path.to_str().unwrap();
+            t.succeeds(&[".", "--to", compr, "-o", path], *FASTA);
+            let mut data = vec![];
+            File::open(path).unwrap().read_to_end(&mut data).unwrap();
+            let data2 = data.clone();
+            data.extend(data2);
+            File::create(path).unwrap().write_all(&data).unwrap();
+            t.cmp(&[".", "--to-tsv", "id"], FileInput(path), "seq1\nseq0\nseq3\nseq2\n".repeat(2).as_str());
+        }
+    });
+}
+
 #[test]
 fn compress_file() {
     let t = Tester::new();
@@ -35,3 +74,56 @@ fn compress_file() {
         t.cmp(&[".", "--fmt", "fasta.gz"], FileInput(path), *FASTA);
     });
 }
+
+#[test]
+fn compress_threads() {
+    let t = Tester::new();
+    // large enough for several blocks
+    let input = FASTA.repeat(2000);
+    for ext in &["gz", "zst"] {
+        let fmt = format!("fasta.{}", ext);
+        t.pipe(&[".", "--to", &fmt, "--compr-threads", "3"], &input, &["."], &input)
+            .pipe(&[".", "--to", &fmt, "--compr-threads", "3"], &input, &[".", "--compr-threads", "2"], &input)
+            .pipe(&[".", "--to", &fmt], &input, &[".", "--compr-threads", "2"], &input);
+    }
+
+    // multithreaded GZIP output is written in BGZF blocks
+    t.temp_file("compr_threads.fa.gz", None, |path, _| {
+        t.succeeds(&[".", "-o", path, "--compr-threads", "2"], &input);
+        let mut data = vec![];
+        File::open(path).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(&data[12..16], b"BC\x02\x00");
+        t.cmp(&[".", "--compr-threads", "4"], FileInput(path), &input);
+    });
+
+    t.fails(&[".", "--compr-threads", "0"], *FASTA, "The number of compression threads must be > 0");
+}
+
+#[test]
+fn compress_bgzf() {
+    let t = Tester::new();
+    t.temp_file("compr.fa.bgz", None, |path, _| {
+        t.succeeds(&[".", "-o", path], *FASTA);
+        let mut data = vec![];
+        File::open(path).unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(&data[12..16], b"BC\x02\x00");
+        // empty EOF block
+        assert!(data.ends_with(&[
+            0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0, 0x42, 0x43, 2, 0, 0x1b, 0, 3, 0, 0, 0, 0, 0,
+            0, 0, 0, 0
+        ]));
+        t.cmp(&["."], FileInput(path), *FASTA);
+    });
+}
+
+#[test]
+fn compress_level() {
+    Tester::new()
+        .fails(&[".", "--to", "fasta.gz", "--compr-level", "10"], *FASTA,
+               "Invalid compression level for GZIP: 10. Valid levels are 0-9.")
+        .fails(&[".", "--to", "fasta.zst", "--compr-level", "23"], *FASTA,
+               "Invalid compression level for ZSTD: 23. Valid levels are 0-22.")
+        // zstd level 0 is the default level
+        .pipe(&[".", "--to", "fasta.zst", "--compr-level", "0"], &FASTA, &["."], &FASTA)
+        .pipe(&[".", "--to", "fasta.lz4", "--compr-level", "12"], &FASTA, &["."], &FASTA);
+}
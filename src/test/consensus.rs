@@ -0,0 +1,34 @@
+
+use super::*;
+
+static SEQS: &'static str = ">a c=1\nACGT\n>b c=1\nACGA\n>c c=2\nTT-A\n>d c=1\nACGA\n>e c=2\nTTCA\n";
+
+#[test]
+fn consensus() {
+    Tester::new()
+        .cmp(&["consensus"], SEQS, ">consensus n=5\nACGA\n")
+        .cmp(&["consensus", "-k", "{a:c}"], SEQS, ">1 n=3\nACGA\n>2 n=2\nTTCA\n")
+        .cmp(&["consensus", "-k", "{a:c}", "--n-attr", "size", "-a", "s={cons:mean_support}"], SEQS,
+             ">1 s=0.9166666666666666 size=3\nACGA\n>2 s=0.875 size=2\nTTCA\n")
+        .cmp(&["consensus", "--keep-gaps"], ">a\nAC-\n>b\nA--\n>c\nA-C\n", ">consensus n=3\nA--\n")
+        .cmp(&["consensus"], ">a\nAC-\n>b\nA--\n>c\nA-C\n", ">consensus n=3\nA\n")
+        .fails(&["consensus"], ">a\nACG\n>b\nAC\n", "different lengths");
+}
+
+#[test]
+fn consensus_iupac() {
+    Tester::new()
+        .cmp(&["consensus", "-k", "{a:c}", "--iupac", "--min-freq", "0.3"], SEQS,
+             ">1 n=3\nACGW\n>2 n=2\nTTCA\n")
+        .cmp(&["consensus", "--iupac", "-a", "a={cons:ambig}"], ">a\nACGU\n>b\nARGU\n",
+             ">consensus a=1 n=2\nAVGU\n")
+        .fails(&["consensus", "--iupac"], ">a\nEF\n>b\nEF\n", "Invalid character");
+}
+
+#[test]
+fn consensus_qual() {
+    let fq = "@a\nACGT\n+\nIIII\n@b\nACGA\n+\nIII#\n@c\nACGA\n+\nIII#\n";
+    Tester::new()
+        .cmp(&["consensus", "--fq"], fq, "@consensus n=3\nACGT\n+\n]]]%\n")
+        .cmp(&["consensus", "--fq", "--no-qual", "--to", "fasta"], fq, ">consensus n=3\nACGA\n");
+}
@@ -1,4 +1,5 @@
-use std::fs::File;
+use std::fs::{self, File};
+use std::thread;
 
 use super::*;
 
@@ -42,6 +43,58 @@ fn var_format() {
     t.var("ST_FORMAT", "fastq").cmp(&[".", "--to-tsv", "id,seq"], fq, tsv);
 }
 
+#[test]
+fn format_detection() {
+    let fa = ">seq\nATGC\n";
+    let fq = "@seq\nATGC\n+\nXXXX\n";
+    let fq_illumina = "@seq\nATGC\n+\nhh@h\n";
+    let fq_high = "@r\nACGT\n+\nJJ@@\n@s\nACGT\n+\nh@@~\n";
+    let sam = "@HD\tVN:1.6\nseq\t0\tchr1\t100\t60\t4M\t*\t0\t0\tATGC\tXXXX\n";
+
+    let t = Tester::new();
+    t.cmp(&["."], fq, fq)
+        .cmp(&[".", "--to-fa"], fq, fa)
+        .cmp(&[".", "--to-tsv", "id,seq"], "seq,ATGC\n", "seq\tATGC\n")
+        .cmp(&[".", "--fields", "id,seq", "--to-fa"], "seq\tATGC\n", fa)
+        // quality encoding
+        .cmp(&[".", "--to", "fastq"], fq_illumina, "@seq\nATGC\n+\nII!I\n")
+        // high-quality Sanger scores are not mistaken for Illumina 1.3+
+        .cmp(&[".", "--to", "fastq"], fq_high, fq_high)
+        .fails(&["."], sam, "SAM format");
+
+    // content recognition takes precedence over the extension
+    t.temp_file("reads.txt", Some(fq), |p, _| {
+        t.cmp(&[".", p], "", fq);
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn fifo_input() {
+    // FIFOs / process substitution can only be read once, compression
+    // and format recognition must not consume the input
+    let t = Tester::new();
+    t.temp_dir("fifo_input", |d| {
+        let gz = d.path().join("input.gz");
+        let gz = gz.to_str().unwrap();
+        t.succeeds(&[".", "--to", "fasta.gz", "-o", gz], *FASTA);
+        let mut compressed = vec![];
+        File::open(gz).unwrap().read_to_end(&mut compressed).unwrap();
+
+        let fifo = d.path().join("fifo");
+        for content in &[FASTA.as_bytes().to_vec(), compressed] {
+            assert!(Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+            let writer = {
+                let (fifo, content) = (fifo.clone(), content.clone());
+                thread::spawn(move || File::create(fifo).unwrap().write_all(&content).unwrap())
+            };
+            t.cmp(&["."], FileInput(fifo.to_str().unwrap()), &FASTA);
+            writer.join().unwrap();
+            fs::remove_file(&fifo).unwrap();
+        }
+    });
+}
+
 #[test]
 fn txt_input() {
     let txt = "seq1\tATGC\tdesc1\nseq2\tATGC\tdesc2\n";
@@ -147,3 +200,26 @@ fn qfile() {
         t.fails(&[".", "--qual", p], fa, "Invalid quality score");
     });
 }
+
+#[test]
+fn json_output() {
+    let fa = ">seq1 a=1\nATGC\n>seq2\nAT\"C\n";
+    let fq = "@seq\nATGC\n+\nII5#\n";
+
+    Tester::new()
+        .cmp(&[".", "--to-jsonl", "id,seq,s:seqlen,a:a"], fa,
+             "{\"id\":\"seq1\",\"seq\":\"ATGC\",\"s:seqlen\":4,\"a:a\":\"1\"}\n\
+              {\"id\":\"seq2\",\"seq\":\"AT\\\"C\",\"s:seqlen\":4,\"a:a\":null}\n")
+        .cmp(&[".", "--to-json", "id,{id}_{s:seqlen}"], fa,
+             "[\n{\"id\":\"seq1\",\"{id}_{s:seqlen}\":\"seq1_4\"},\n\
+              {\"id\":\"seq2\",\"{id}_{s:seqlen}\":\"seq2_4\"}\n]\n")
+        .cmp(&["head", "-n", "0", "--to", "json"], fa, "[]\n")
+        .cmp(&[".", "--fq", "--to-jsonl", "id,qual"], fq, "{\"id\":\"seq\",\"qual\":[40,40,20,2]}\n")
+        .cmp(&[".", "--to-jsonl", "id,qual"], fa, "{\"id\":\"seq1\",\"qual\":null}\n{\"id\":\"seq2\",\"qual\":null}\n")
+        .fails(&[".", "--fmt", "json"], fa, "JSON input is not supported");
+    // the format of empty input is deduced from the extension
+    let t = Tester::new();
+    t.temp_file("empty.json", Some(""), |p, _| {
+        t.fails(&["."], FileInput(p), "JSON input is not supported");
+    });
+}
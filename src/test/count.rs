@@ -24,3 +24,15 @@ fn missing() {
         .cmp(&["count", "-k", "{{a:missing + 1}}"], *FASTA, "NaN\t4\n")
         .cmp(&["count", "-k", "n:{{a:missing + 1}}"], *FASTA, "NaN\t4\n");
 }
+
+#[test]
+fn count_threaded() {
+    let fasta = repeat(*FASTA as &str).take(50).collect::<String>();
+    let t = Tester::new();
+    for n in &["1", "2", "4"] {
+        t.cmp(&["count", "-t", n, "--buf-cap", "100", "-k", "a:p"], &fasta,
+              "1\t50\n10\t50\n11\t50\n2\t50\n")
+         .cmp(&["count", "-t", n, "--buf-cap", "100", "-k", "n:10:{a:p}"], &fasta,
+              "(0,10]\t100\n(10,20]\t100\n");
+    }
+}
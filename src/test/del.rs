@@ -8,5 +8,17 @@ fn del() {
     Tester::new()
         .cmp(&["del", "-d"], fasta, ">seq;p=0\nATGC\n")
         .cmp(&["del", "--attrs", "a,b"], fasta, ">seq;p=0\nATGC\n")
-        .cmp(&["del", "--adelim", ";", "--attrs", "p"], fasta, ">seq a=1 b=2\nATGC\n");
+        .cmp(&["del", "--adelim", ";", "--attrs", "p"], fasta, ">seq a=1 b=2\nATGC\n")
+        .cmp(&["del", "--attrs", "b,a"], fasta, ">seq;p=0\nATGC\n");
+}
+
+#[test]
+fn del_attr_fmt() {
+    let usearch = ">seq;size=3;s=A;\nATGC\n";
+    let sam = ">seq BC:Z:ACGT RG:Z:g\nATGC\n";
+    Tester::new()
+        .cmp(&["del", "--attr-fmt", "usearch", "--attrs", "size"], usearch, ">seq;s=A;\nATGC\n")
+        .cmp(&["del", "--attr-fmt", "usearch", "--attrs", "s"], usearch, ">seq;size=3;\nATGC\n")
+        .cmp(&["del", "--attr-fmt", "usearch", "--attrs", "s,size"], usearch, ">seq\nATGC\n")
+        .cmp(&["del", "--attr-fmt", "sam", "--attrs", "BC"], sam, ">seq RG:Z:g\nATGC\n");
 }
@@ -91,6 +91,232 @@ fn vars() {
             "seq,CAGG,5,8,5-8,-21,-18,5..8,-21..-18,pattern,0,CAGG\n"
         );
 }
+#[test]
+fn both_strands() {
+    let fasta = ">f\nAAGGCTTTT\n>r\nAAAAGCCTT\n>n\nCCCCCCCCC\n";
+    Tester::new()
+        .cmp(&["find", "GGCT", "--both-strands", "--to-tsv", "id,f:strand,f:range,f:match"], fasta,
+             "f\t+\t3-6\tGGCT\nr\t-\t4-7\tAGCC\nn\t\t\t\n")
+        // ambiguities
+        .cmp(&["find", "GGYT", "--both-strands", "--to-tsv", "id,f:strand,f:range"], fasta,
+             "f\t+\t3-6\nr\t-\t4-7\nn\t\t\n")
+        // max. shift is relative to the strand of the pattern
+        .cmp(&["find", "GGCT", "--both-strands", "-f", "--max-shift-l", "2"], fasta,
+             ">f\nAAGGCTTTT\n>r\nAAAAGCCTT\n")
+        .cmp(&["find", "GGCT", "-f", "--max-shift-l", "2"], fasta, ">f\nAAGGCTTTT\n")
+        .cmp(&["find", "GGCT", "--reorient", "-a", "s={f:strand}", "-a", "r={f:range}"], fasta,
+             ">f s=+ r=3-6\nAAGGCTTTT\n>r s=- r=3-6\nAAGGCTTTT\n>n s= r=\nCCCCCCCCC\n")
+        .cmp(&["find", "--fq", "--reorient", "-f", "GGCT"], "@r\nAAAAGCCTT\n+\nABCDEFGHI\n",
+             "@r\nAAGGCTTTT\n+\nIHGFEDCBA\n")
+        // record numbers are not affected, sequence-dependent variables are updated
+        .cmp(&["find", "GGCT", "--reorient", "-a", "n={num}", "-a", "c={cnt:id}", "-a", "a={s:count:A}",
+               "-a", "s={seq}", "-a", "r={f:range}"], fasta,
+             ">f n=1 c=1 a=2 s=AAGGCTTTT r=3-6\nAAGGCTTTT\n>r n=2 c=1 a=2 s=AAGGCTTTT r=3-6\nAAGGCTTTT\n\
+              >n n=3 c=1 a=0 s=CCCCCCCCC r=\nCCCCCCCCC\n")
+        .fails(&["find", "-r", "--both-strands", "GG"], fasta, "Regular expressions cannot")
+        .fails(&["find", "--both-strands", "--seqtype", "protein", "EF"], fasta, "only possible with DNA/RNA");
+}
+
+#[test]
+fn pattern_sets() {
+    let fasta = ">a\nAAGGCTTTTCCCCGGAATT\n>b\nAAGGCTTTTCCCCGGTATT\n>c\nTTTTCCCCGG\n";
+    Tester::new()
+        .cmp(&["find", "GGCT", "-p", "GGAA;rng=-8..", "--to-tsv", "id,f:range,f1:range,f2:range"],
+             fasta, "a\t3-6\t3-6\t14-17\nb\t3-6\t3-6\t\nc\t\t\t\n")
+        .cmp(&["find", "GGCT", "-p", "GGAA;rng=..10", "-f"], fasta, "")
+        .cmp(&["find", "GGCT", "-p", "GGAA;dist=1", "-a", "d={f2:dist}", "-f"], fasta,
+             ">a d=0\nAAGGCTTTTCCCCGGAATT\n>b d=1\nAAGGCTTTTCCCCGGTATT\n")
+        .cmp(&["find", "GGCT", "-p", "GGAA;dist=1", "--extract"], fasta,
+             ">a\nTTTCCCC\n>b\nTTTCCCC\n>c\nTTTTCCCCGG\n")
+        .fails(&["find", "GGCT", "-a", "x={f2:start}"], fasta, "Unknown variable prefix: f2")
+        .fails(&["find", "GGCT", "-p", "GGAA;x=1", "-f"], fasta, "Unknown pattern set option")
+        .fails(&["find", "GGCT", "--extract"], fasta, "requires at least two pattern sets")
+        .fails(&["find", "--id", "GGCT", "-p", "GGAA", "--extract"], fasta, "only possible when searching in sequences")
+        .fails(&["find", "--fq", "--desc", "GGCT", "-p", "GGAA", "--extract"], "@a\nA\n+\nI\n",
+               "only possible when searching in sequences");
+}
+
+#[test]
+fn hits() {
+    let t = Tester::new();
+    let fa = ">f\nAAGGCTTTT\n>r\nAAAAGCCTT\n";
+    t.temp_dir("find_hits", |d| {
+        let patterns = d.path().join("patterns.fa");
+        File::create(&patterns).unwrap().write_all(b">p1\nGGCT\n>p2\nTTT\n").unwrap();
+        let patt_arg = format!("file:{}", patterns.to_str().unwrap());
+
+        let out = d.path().join("hits.tsv");
+        let out_path = out.to_str().expect("invalid path");
+        t.cmp(&["find", &patt_arg, "--both-strands", "--hits", out_path], fa, fa);
+        let mut s = String::new();
+        File::open(out_path).unwrap().read_to_string(&mut s).unwrap();
+        assert_eq!(&s, "id\tpattern\tstart\tend\tstrand\tdist\tsubst\tins\tdel\n\
+                        f\tp1\t3\t6\t+\t0\t0\t0\t0\n\
+                        f\tp2\t6\t8\t+\t0\t0\t0\t0\n\
+                        f\tp2\t7\t9\t+\t0\t0\t0\t0\n\
+                        r\tp2\t1\t3\t-\t0\t0\t0\t0\n\
+                        r\tp2\t2\t4\t-\t0\t0\t0\t0\n\
+                        r\tp1\t4\t7\t-\t0\t0\t0\t0\n");
+
+        let out = d.path().join("hits.bed");
+        let out_path = out.to_str().expect("invalid path");
+        t.cmp(&["find", &patt_arg, "--hits", out_path], fa, fa);
+        let mut s = String::new();
+        File::open(out_path).unwrap().read_to_string(&mut s).unwrap();
+        assert_eq!(&s, "f\t2\t6\tp1\t0\t+\nf\t5\t8\tp2\t0\t+\nf\t6\t9\tp2\t0\t+\n");
+    })
+}
+
+#[test]
+fn aln_vars() {
+    let fasta = ">s\nAAGAGCGTTAAA\n>t\nAAGGCAGTTAAA\n";
+    Tester::new()
+        .cmp(&["find", "TGAGCAGT", "-d", "2", "--to-tsv",
+               "id,f:dist,f:subst,f:ins,f:del,f:cigar,f:aln_pattern,f:aln_text"], fasta,
+             "s\t2\t0\t2\t0\t1I4=1I2=\tTGAGCAGT\t-GAGC-GT\n\
+              t\t2\t0\t2\t0\t1I1=1I5=\tTGAGCAGT\t-G-GCAGT\n")
+        .cmp(&["find", "AGCCGT", "-d", "1", "--to-tsv", "id,f:subst,f:del,f:cigar,f:aln_pattern,f:aln_text"],
+             fasta, "s\t0\t0\t3=1I2=\tAGCCGT\tAGC-GT\nt\t\t\t\t\t\n")
+        .cmp(&["find", "AGGAGC", "-d", "1", "--to-tsv", "id,f:del,f:cigar,f:aln_pattern,f:aln_text"],
+             ">s\nAGGTAGC\n", "s\t1\t3=1D3=\tAGG-AGC\tAGGTAGC\n")
+        // exact matches
+        .cmp(&["find", "GAGC", "--to-tsv", "id,f:subst,f:cigar,f:aln_text"], fasta,
+             "s\t0\t4=\tGAGC\nt\t\t\t\n")
+        .cmp(&["find", "-d", "2", "--both-strands", "ACTGCTCA", "--to-tsv", "f:strand,f:cigar,f:aln_pattern"],
+             fasta, "-\t1I4=1I2=\tTGAGCAGT\n-\t1I1=1I5=\tTGAGCAGT\n");
+}
+
+#[test]
+fn long_pattern() {
+    // patterns > 64 symbols are searched with the block-based Myers algorithm
+    let pattern = "CACTCCAACCCCGGCCCCTGAGTCCGAGGAGAGGGTGCTTCAGAGTATGTATACCACTGGGTAGGATAC\
+                   GGCGGAGGGCACGTCAATACGGTTCAATGCC";
+    let fasta = ">s\nGGATCACAGTCTACACTGCTCACTCCAACCCCGGCCCCTGAGTCCGAGGATAGGGTGCTTCAGAGTATGTAT\
+                 ACCACTGGGTAGGATACGCGGAGGGCACGTCAATACGGTTCAATGCCCTACTGCATGCTCTTGTGGTTCATCTGCAT\n";
+    Tester::new()
+        .cmp(&["find", pattern, "-d", "3", "--to-tsv", "id,f:range,f:dist,f:cigar"], fasta,
+             "s\t21-119\t2\t30=1X39=1I29=\n")
+        .cmp(&["find", pattern, "-d", "1", "--to-tsv", "id,f:range"], fasta, "s\t\n");
+}
+
+#[test]
+fn many_patterns() {
+    // with many patterns, a pre-filter is used
+    let patterns = ">p0\nGCAGCTTACAG\n>p1\nCCGATGCAAG\n>p2\nCGTCCAACCCT\n>p3\nATTTTTCTATC\n\
+                    >p4\nAGTTTAGAATT\n>p5\nAAGCATCCAAT\n>p6\nCCTTGGTCCAG\n>p7\nGTCGCGGACGC\n\
+                    >p8\nAGGCGATGTGT\n>p9\nCTACACCGAAT\n>p10\nGCTCCTTTTAA\n>p11\nGAAAAGCTCAC\n";
+    let fasta = ">a\nTTGCAGCTTACAGTTTT\n>b\nTTCCGATCCAAGTTT\n>c\nGGGGGGGGGG\n>d\nAAAAACTTGTAAGCTGCAAA\n";
+    let t = Tester::new();
+    t.temp_file("patterns.fa", Some(patterns), |p, _| {
+        let patt_arg = format!("file:{}", p);
+        t.cmp(&["find", &patt_arg, "--to-tsv", "id,f:name,f:range"], fasta,
+              "a\tp0\t3-13\nb\t\t\nc\t\t\nd\t\t\n")
+         .cmp(&["find", &patt_arg, "-d", "1", "--both-strands", "--to-tsv", "id,f:name,f:range,f:dist,f:strand"],
+              fasta, "a\tp0\t3-13\t0\t+\nb\tp1\t3-12\t1\t+\nc\t\t\t\t\nd\tp0\t6-17\t1\t-\n");
+    });
+}
+
+#[test]
+fn matrix() {
+    let fasta = ">a\nMKTAYIAKQRQISFVKSHFSRQ\n>b\nMKTAYIAKQRQLSFVKSHFSRQ\n>c\nMKTAYIAKQISFVKSHFSRQ\n\
+                 >d\nMKTAYIAKQRQAAAISFVKSHFSRQ\n>e\nGGGGGGGGGG\n";
+    let t = Tester::new();
+    t.cmp(&["find", "--matrix", "blosum62", "--min-score", "25", "KQRQISFVK",
+            "--to-tsv", "id,f:range,f:score,f:dist,f:ident,f:cigar"], fasta,
+          "a\t8-16\t43\t0\t1\t9=\n\
+           b\t8-16\t41\t2\t0.8888888888888888\t4=1X4=\n\
+           c\t6-14\t26\t17\t0.6666666666666666\t3X6=\n\
+           d\t8-19\t29\t14\t0.75\t4=3D5=\n\
+           e\t\t\t\t\t\n")
+     .cmp(&["find", "--matrix", "pam250", "--min-score", "30", "KQRQISFVK", "--to-tsv", "id,f:score"],
+          fasta, "a\t44\nb\t41\nc\t30\nd\t30\ne\t\n")
+     .fails(&["find", "--matrix", "blosum62", "KQRQ"], fasta, "requires a minimum score")
+     .fails(&["find", "--matrix", "blosum62", "--min-score", "5", "-d", "1", "KQRQ"], fasta,
+            "cannot be combined")
+     .fails(&["find", "--matrix", "notfound", "--min-score", "5", "KQRQ"], fasta,
+            "Unknown substitution matrix");
+
+    let matrix = "# test\n   A  C  G  T\nA  2 -1 -1 -1\nC -1  2 -1 -1\nG -1 -1  2 -1\nT -1 -1 -1  2\n";
+    t.temp_file("matrix.txt", Some(matrix), |p, _| {
+        t.cmp(&["find", "--matrix", p, "--min-score", "8", "--gap-open", "-2", "ACGTAC",
+                "--to-tsv", "id,f:range,f:score,f:cigar"], ">s\nTTACGAACTT\n>t\nACGTTAC\n",
+              "s\t3-8\t9\t3=1X2=\nt\t1-7\t9\t3=1D3=\n");
+    });
+}
+
+#[test]
+fn costs() {
+    let fasta = ">a\nTTTACGTACGTTT\n>b\nCGTACGTTT\n>c\nTTTACGTAC\n>d\nTTTACGAACGTTT\n";
+    Tester::new()
+        // free end gaps: overhanging pattern characters are reported as insertions
+        .cmp(&["find", "ACGTACGT", "-d", "1", "--free-ends", "2", "--to-tsv", "id,f:range,f:dist,f:cigar"],
+             fasta, "a\t4-11\t0\t8=\nb\t1-7\t0\t1I7=\nc\t4-9\t0\t6=2I\nd\t4-11\t1\t3=1X4=\n")
+        // weighted substitutions
+        .cmp(&["find", "ACGTACGT", "-d", "2", "--subst-cost", "2", "--to-tsv", "id,f:dist,f:cigar"],
+             fasta, "a\t0\t8=\nb\t1\t1I7=\nc\t2\t6=2I\nd\t2\t3=1X4=\n")
+        .cmp(&["find", "ACGTACGT", "-d", "1", "--ins-cost", "2", "-f"], fasta,
+             ">a\nTTTACGTACGTTT\n>d\nTTTACGAACGTTT\n")
+        .fails(&["find", "ACGT", "--del-cost", "0"], fasta, "costs must be > 0")
+        .fails(&["find", "-r", "ACGT", "--free-ends", "1"], fasta, "cannot be used with regular");
+}
+
+#[test]
+fn motifs() {
+    let prot = ">a\nMKCAACGGGLK\n>b\nMKCAAAACGGGMK\n>c\nMKCACGGGLK\n>d\nMKCAACGGGPK\n";
+    let dna = ">a\nTTGACGTAT\n>b\nTTGCGTAT\n>c\nAATACGTCAA\n";
+    Tester::new()
+        // PROSITE patterns (converted to regular expressions)
+        .cmp(&["find", "--prosite", "C-x(2,4)-C-x(3)-[LIVM]", "--to-tsv", "id,f:range"], prot,
+             "a\t3-10\nb\t3-12\nc\t\nd\t\n")
+        .cmp(&["find", "--prosite", "<M-K-C-x(2)-C-x(3)-{P}-K>.", "--to-tsv", "id,f:range"], prot,
+             "a\t1-11\nb\t\nc\t\nd\t\n")
+        // fixed-length PROSITE patterns with mismatches
+        .cmp(&["find", "--prosite", "C-x(2)-C-x(3)-[LIVM]", "-d", "1", "--to-tsv", "id,f:range,f:dist"],
+             prot, "a\t3-10\t0\nb\t6-12\t1\nc\t3-9\t1\nd\t3-9\t1\n")
+        .fails(&["find", "--prosite", "C-x(2,4)-C", "-d", "1"], prot,
+               "cannot be searched with -d/--dist")
+        .fails(&["find", "--prosite", "C-x(2", "-f"], prot, "Invalid PROSITE pattern")
+        // IUPAC with repeat counts
+        .cmp(&["find", "--iupac", "N{2}ACGT", "--to-tsv", "id,f:range"], dna,
+             "a\t2-7\nb\t\nc\t2-7\n")
+        .cmp(&["find", "--iupac", "TGN{0,1}CGY", "--to-tsv", "id,f:range"], dna,
+             "a\t2-7\nb\t2-6\nc\t\n")
+        .cmp(&["find", "--iupac", "GRC{2}T", "-d", "1", "--both-strands",
+               "--to-tsv", "id,f:range,f:strand"], dna,
+             "a\t3-7\t+\nb\t\t\nc\t4-8\t-\n")
+        .fails(&["find", "--iupac", "-r", "N{2}"], dna, "cannot be combined");
+}
+
+#[test]
+fn pwm() {
+    let jaspar = ">MA0004.1 Arnt\nA  [ 4 19  0  0  0  0 ]\nC  [16  0 20  0  0  0 ]\n\
+                  G  [ 0  1  0 20  0 20 ]\nT  [ 0  0  0  0 20  0 ]\n";
+    let meme = "MEME version 4\n\nALPHABET= ACGT\n\nMOTIF MA0004.1 Arnt\n\
+                letter-probability matrix: alength= 4 w= 6 nsites= 20 E= 0\n\
+                0.2 0.8 0 0\n0.95 0 0.05 0\n0 1 0 0\n0 0 1 0\n0 0 0 1\n0 0 1 0\n";
+    let fasta = ">a\nTTTCACGTGTTT\n>b\nTTTCACGAGTTT\n>c\nAAAAAAAAAA\n>d\nTTTTTCACGTTGG\n";
+    let t = Tester::new();
+    t.temp_file("arnt.jaspar", Some(jaspar), |p, _| {
+        t.cmp(&["find", "--pwm", p, "--to-tsv", "id,f:name,f:range,f:strand,f:pvalue,f:dist"], fasta,
+              "a\tMA0004.1\t4-9\t+\t0.000244140625\t0\n\
+               b\tMA0004.1\t4-9\t+\t0.0048828125\t7\n\
+               c\t\t\t\t\t\n\
+               d\tMA0004.1\t6-11\t-\t0.00048828125\t2\n")
+         .cmp(&["find", "--pwm", p, "--min-score", "9", "-f"], fasta,
+              ">a\nTTTCACGTGTTT\n>d\nTTTTTCACGTTGG\n")
+         .cmp(&["find", "--pwm", p, "--min-score", "99%", "-f"], fasta, ">a\nTTTCACGTGTTT\n")
+         // alignment to the consensus sequence (CACGTG)
+         .cmp(&["find", "--pwm", p, "--to-tsv", "id,f:aln_pattern,f:aln_text,f:cigar,f:subst,f:ident"], ">b\nTTTCACGAGTTT\n",
+              "b\tCACGTG\tCACGAG\t4=1X1=\t1\t0.8333333333333334\n")
+         .fails(&["find", "--pwm", p, "-d", "1"], fasta, "cannot be combined")
+         .fails(&["find", "--pwm", p, "--min-score", "120%"], fasta, "between 0 and 100%");
+    });
+    t.temp_file("arnt.meme", Some(meme), |p, _| {
+        t.cmp(&["find", "--pwm", p, "--to-tsv", "id,f:range,f:strand"], fasta,
+              "a\t4-9\t+\nb\t4-9\t+\nc\t\t\nd\t6-11\t-\n");
+    });
+}
+
 //
 // #[test]
 // fn fuzzy() {
@@ -234,20 +460,11 @@ fn threaded() {
         let mut cap = 3;
         while cap < t * FASTA.len() {
             Tester::new()
-                .cmd(&[
+                .cmp(&[
                     "find", "-f", "--id", "--to-tsv", "id",
                     "-t", &format!("{}", t),
                     "--buf-cap", &format!("{}", cap), "seq"
-                ], *FASTA)
-                .stdout()
-                .contains("seq0")
-                .stdout()
-                .contains("seq1")
-                .stdout()
-                .contains("seq2")
-                .stdout()
-                .contains("seq3")
-                .unwrap();
+                ], *FASTA, "seq1\nseq0\nseq3\nseq2\n");
             cap += 10;
         }
     }
@@ -0,0 +1,33 @@
+
+use super::*;
+
+
+#[test]
+fn kmers() {
+    let fasta = ">a\nACGTTnAAcg\n>b\nACG\n";
+    Tester::new()
+        .cmp(&["kmers", "-K", "3"], fasta, "ACG\t4\nAAC\t2\n")
+        .cmp(&["kmers", "-K", "3", "-t", "2"], fasta, "ACG\t4\nAAC\t2\n")
+        .cmp(&["kmers", "-K", "3", "-s"], fasta, "ACG\t3\nAAC\t1\nCGT\t1\nGTT\t1\n")
+        .cmp(&["kmers", "-K", "3", "-s", "-n", "2"], fasta, "ACG\t3\nAAC\t1\n")
+        .cmp(&["kmers", "-K", "3", "-H"], fasta, "2\t1\n4\t1\n")
+        .cmp(&["kmers", "-K", "3", "-k", "{id}", "-n", "1"], fasta, "a\tACG\t3\nb\tACG\t1\n")
+        .fails(&["kmers", "-K", "0"], fasta, "The k-mer length must be > 0");
+}
+
+#[test]
+fn kmers_threaded() {
+    // many record sets, counted in different threads
+    let fasta = ">a\nACGTTnAAcg\n>b\nACG\n".repeat(5000);
+    Tester::new()
+        .cmp(&["kmers", "-K", "3", "-t", "3"], fasta.as_str(), "ACG\t20000\nAAC\t10000\n")
+        .cmp(&["kmers", "-K", "3", "-t", "3", "-H"], fasta.as_str(), "10000\t1\n20000\t1\n");
+}
+
+#[test]
+fn long_kmers() {
+    let fasta = ">a\nACGTACGTACGTACGTACGTACGTACGTACGTACG\n";
+    Tester::new()
+        .cmp(&["kmers", "-K", "33"], fasta, "CGTACGTACGTACGTACGTACGTACGTACGTAC\t2\nACGTACGTACGTACGTACGTACGTACGTACGTA\t1\n")
+        .cmp(&["kmers", "-K", "33", "-k", "{id}", "-H"], fasta, "a\t1\t1\na\t2\t1\n");
+}
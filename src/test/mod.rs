@@ -225,6 +225,7 @@ mod pass;
 mod compress;
 mod convert;
 mod count;
+mod kmers;
 mod slice;
 mod sample;
 mod head;
@@ -234,6 +235,8 @@ mod set;
 mod del;
 mod replace;
 mod find;
+mod align;
+mod screen;
 mod split;
 mod upper;
 mod lower;
@@ -244,3 +247,5 @@ mod stat;
 mod filter;
 mod interleave;
 mod concat;
+mod consensus;
+mod aln;
@@ -0,0 +1,32 @@
+
+use super::*;
+
+
+#[test]
+fn screen() {
+    let reference = ">r\nACGTTGCA\n";
+    // a: forward, b: reverse complement, c: no hit, d: no k-mer
+    let fasta = ">a\nACGTTGC\n>b\nTGCAACG\n>c\nGGGGGGG\n>d\nAC\n";
+    let t = Tester::new();
+    t.temp_file("ref.fa", Some(reference), |p, _| {
+        t.cmp(&["screen", "-K", "4", "--to-csv", "id,kmer:hits,kmer:total,kmer:frac", p],
+              fasta, "a,4,4,1\nb,4,4,1\nc,0,4,0\nd,0,0,\n")
+         .cmp(&["screen", "-K", "4", "-f", p], fasta, ">a\nACGTTGC\n>b\nTGCAACG\n")
+         .cmp(&["screen", "-K", "4", "-e", p], fasta, ">c\nGGGGGGG\n>d\nAC\n")
+         .cmp(&["screen", "-K", "4", "-e", "-t", "2", p], fasta, ">c\nGGGGGGG\n>d\nAC\n")
+         .cmp(&["screen", "-K", "4", "-f", "-n", "5", p], fasta, "")
+         .cmp(&["screen", "-K", "4", "-f", "--min-frac", "0.5", p],
+              ">e\nACGTTAAAA\n>f\nACGTTGAAA\n", ">f\nACGTTGAAA\n")
+         .cmp(&["screen", "-K", "33", "-f", p], fasta, "")
+         .fails(&["screen", p], fasta, "Screen command does nothing");
+    });
+}
+
+#[test]
+fn screen_compressed() {
+    let t = Tester::new();
+    t.temp_file("ref.fa.gz", None, |p, _| {
+        t.succeeds(&[".", "-o", p], ">r\nACGTTGCA\n");
+        t.cmp(&["screen", "-K", "4", "-f", p], ">a\nACGTTGC\n>c\nGGGGGGG\n", ">a\nACGTTGC\n");
+    });
+}
@@ -8,3 +8,103 @@ fn set() {
         .cmp(&["set", "-d", "desc"], fasta, ">seq desc\nATGC\n")
         .cmp(&["set", "-s", "NNNN"], fasta, ">seq\nNNNN\n");
 }
+
+#[test]
+fn running_vars() {
+    let fasta = ">a s=x\nAC\n>b s=y\nACG\n>c s=x\nA\n";
+    Tester::new()
+        .cmp(&[".", "--to-tsv", "id,filenum,cnt:{a:s}"], fasta, "a\t1\t1\nb\t2\t1\nc\t3\t2\n")
+        .cmp(&[".", "--to-tsv", "cumsum:s:seqlen,prev:id"], fasta, "2\t\n5\ta\n6\tb\n")
+        .cmp(&["set", "-i", "{a:s}_{cnt:a:s}"], fasta, ">x_1 s=x\nAC\n>y_1 s=y\nACG\n>x_2 s=x\nA\n");
+}
+
+#[test]
+fn header_template() {
+    let fasta = ">seq1 size=3\nATGC\n";
+    let fastq = "@seq1 size=3\nATGC\n+\nIIII\n";
+    Tester::new()
+        .cmp(&[".", "--out-header", "{id};size={a:size};"], fasta, ">seq1;size=3;\nATGC\n")
+        .cmp(&[".", "--fq", "--out-header", "{id} n={num}"], fastq, "@seq1 n=1\nATGC\n+\nIIII\n")
+        .fails(&[".", "--out-header", "{id}", "-a", "a=b"], fasta, "cannot be added")
+        .fails(&[".", "--out-header", "{id}", "--to-tsv", "id"], fasta, "only valid for FASTA/FASTQ")
+        .fails(&[".", "--out-header", "{id}", "--to-json", "id"], fasta, "only valid for FASTA/FASTQ");
+}
+
+#[test]
+fn attr_fmt() {
+    let usearch = ">seq;size=3;\nATGC\n";
+    let sam = "@seq 1:N:0:AC BC:Z:ACGT\nATGC\n+\nIIII\n";
+    Tester::new()
+        .cmp(&[".", "--attr-fmt", "usearch", "-a", "s=A"], usearch, ">seq;size=3;s=A;\nATGC\n")
+        .cmp(&[".", "--attr-fmt", "usearch", "-a", "s=A"], ">seq\nATGC\n", ">seq;s=A;\nATGC\n")
+        .cmp(&[".", "--attr-fmt", "usearch", "-a", "size={{a:size + 1}}"], usearch, ">seq;size=4;\nATGC\n")
+        .cmp(&[".", "--fq", "--attr-fmt", "sam", "--to-tsv", "a:BC"], sam, "ACGT\n")
+        .cmp(&[".", "--fq", "--attr-fmt", "sam", "-a", "RG=g"], sam,
+             "@seq 1:N:0:AC BC:Z:ACGT RG:Z:g\nATGC\n+\nIIII\n")
+        .cmp(&[".", "--attr-fmt", "colon", "-a", "a=1"], ">seq b:2\nATGC\n", ">seq b:2 a:1\nATGC\n")
+        .fails(&[".", "--attr-fmt", "x"], ">seq\nATGC\n", "Unknown attribute format");
+}
+
+#[test]
+fn illumina_vars() {
+    let fq = "@M1:12:FC1:3:1101:150:200 1:N:0:ACGT+GGTT\nA\n+\nI\n\
+              @M1:12:FC1:4:1101:15:20:UMI 2:Y:0:3\nA\n+\nI\n\
+              @HWUSI:6:73:941:1973#0/1\nA\n+\nI\n";
+    Tester::new()
+        .cmp(&[".", "--fq", "--to-tsv", "ill:run,ill:flowcell,ill:lane,ill:tile,ill:x,ill:y,ill:umi"], fq,
+             "12\tFC1\t3\t1101\t150\t200\t\n12\tFC1\t4\t1101\t15\t20\tUMI\n\t\t6\t73\t941\t1973\t\n")
+        .cmp(&[".", "--fq", "--to-tsv", "ill:read,ill:filtered,ill:index,ill:index1,ill:index2"], fq,
+             "1\t0\tACGT+GGTT\tACGT\tGGTT\n2\t1\t3\t3\t\n1\t\t0\t0\t\n")
+        .cmp(&["set", "--fq", "-i", "{ill:lane}_{ill:read}"], fq,
+             "@3_1 1:N:0:ACGT+GGTT\nA\n+\nI\n@4_2 2:Y:0:3\nA\n+\nI\n@6_1\nA\n+\nI\n")
+        // slashes in other IDs are not read numbers
+        .cmp(&[".", "--fq", "--to-tsv", "ill:read,ill:lane"], "@SRR1/ccs/12\nA\n+\nI\n@HWUSI:6:73:941:1973/4\nA\n+\nI\n",
+             "\t\n4\t6\n");
+}
+
+#[test]
+fn threaded() {
+    // small buffer -> many record sets
+    let fasta = FASTA.repeat(50);
+    let tsv: String = SEQS.iter()
+        .map(|s| {
+            let mut lines = s[1..].lines();
+            let header = lines.next().unwrap().replace(' ', "\t");
+            format!("{}\t{}\n", header, lines.next().unwrap())
+        })
+        .collect::<String>()
+        .repeat(50);
+    let attrs = ["2", "1", "10", "11"];
+    let ids = ["seq1", "seq0", "seq3", "seq2"];
+    let by_attr: String = (0..200).map(|i| format!("{}_{}\n", ids[i % 4], attrs[i % 4])).collect();
+    let by_num: String = (0..200).map(|i| format!("{}_{}\n", ids[i % 4], i + 1)).collect();
+    let by_gc: String = repeat("seq1_60\nseq0_66.66666666666666\nseq3_66.66666666666666\nseq2_65.21739130434783\n").take(50).collect();
+    let lower: String = SEQS.iter()
+        .map(|s| format!("{}\n", s.lines().nth(1).unwrap().to_lowercase()))
+        .collect::<String>()
+        .repeat(50);
+    let t = Tester::new();
+    for n in &["2", "4"] {
+        // evaluated in worker threads
+        t.cmp(&["set", "-t", n, "--buf-cap", "100", "-i", "{id}_{a:p}", "--to-tsv", "id"], &fasta, &by_attr)
+            .cmp(&["set", "-t", n, "--buf-cap", "100", "-i", "{id}_{s:gc}", "--to-tsv", "id"], &fasta, &by_gc)
+            .cmp(&["lower", "-t", n, "--buf-cap", "100", "--to-tsv", "seq"], &fasta, &lower)
+            .cmp(&["trim", "-t", n, "--buf-cap", "100", "..{a:p}", "--to-tsv", "seq"], &fasta,
+                 &repeat("TT\nC\nCAGGCAGGCC\nACGG-AGGCC-\n").take(50).collect::<String>())
+            // order-dependent: evaluated in the main thread
+            .cmp(&["set", "-t", n, "--buf-cap", "100", "-i", "{id}_{num}", "--to-tsv", "id"], &fasta, &by_num)
+            // CSV input
+            .cmp(&["set", "-t", n, "--tsv", "id,desc,seq", "-i", "{id}_{a:p}", "--to-tsv", "id"], &tsv, &by_attr);
+    }
+
+    // unordered lists are read completely before being copied to worker threads
+    let list = "2\tseq1\n1\tseq0\n10\tseq3\n11\tseq2\n";
+    t.temp_file("list.tsv", Some(list), |p, _| {
+        t.cmp(&["set", "-t", "2", "-ul", p, "--id-col", "2", "-i", "{id}_{l:1}", "--to-tsv", "id"], &fasta, &by_attr);
+    });
+    t.temp_file("list.tsv", Some(&format!("{}12\n", list)), |p, _| {
+        t.cmp(&["set", "-ul", p, "--id-col", "2", "-i", "{id}_{l:1}", "--to-tsv", "id"], &fasta, &by_attr)
+            .fails(&["set", "-t", "2", "-ul", p, "--id-col", "2", "-i", "{id}_{l:1}"], &fasta,
+                   "ID Column not found");
+    });
+}
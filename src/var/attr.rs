@@ -1,6 +1,6 @@
 use io::SeqAttr;
 
-use memchr::memchr;
+use memchr::{memchr, memrchr};
 
 use lib::key_value;
 
@@ -17,7 +17,7 @@ pub enum Action {
     Delete,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Attrs {
     parser: Parser,
     // (attr_id, name, action)
@@ -32,6 +32,10 @@ pub struct Attrs {
     // the distinction of ID and description makes handling of spaces somehow complicated
     adelim_is_space: bool,
     append_attr: SeqAttr,
+    // USEARCH style: the last attribute is followed by a delimiter (>id;size=1;)
+    trailing_delim: bool,
+    // SAM style tags with type: KEY:TYPE:VALUE (KEY:Z:VALUE is appended)
+    typed: bool,
 }
 
 impl Attrs {
@@ -46,9 +50,22 @@ impl Attrs {
             attr_value_delim: attr_value_delim,
             adelim_is_space: attr_delim == b' ',
             append_attr: append_attr,
+            trailing_delim: false,
+            typed: false,
         }
     }
 
+    pub fn trailing_delim(mut self, trailing_delim: bool) -> Self {
+        self.trailing_delim = trailing_delim;
+        self
+    }
+
+    pub fn typed(mut self, typed: bool) -> Self {
+        self.typed = typed;
+        self.parser.typed = typed;
+        self
+    }
+
     // Not a "smart" function, names must be added in order of IDs (just supplied to ensure
     // consistency). Only used for importing attributes from VarStore, which assigns the IDs
     pub fn add_attr(&mut self, name: &str, id: usize, action: Option<Action>) {
@@ -90,6 +107,9 @@ impl Attrs {
                 self._append_ids.push(attr_id);
             }
         }
+        // actions are applied in the order of occurrence
+        self._id_actions.sort_by_key(|&(_, _, ref pos)| pos.start);
+        self._desc_actions.sort_by_key(|&(_, _, ref pos)| pos.start);
     }
 
     pub fn compose<F>(
@@ -106,13 +126,17 @@ impl Attrs {
         out_desc.clear();
 
         self._compose(id, &self._id_actions, out_id, &mut push_fn);
+        if self.trailing_delim {
+            self.remove_trailing(out_id);
+        }
 
         if let Some(d) = desc {
             self._compose(d, &self._desc_actions, out_desc, &mut push_fn);
         }
 
         if self.append_attr == SeqAttr::Id {
-            self.append_missing(out_id, &self._append_ids, true, &mut push_fn);
+            let delim_before = !(self.trailing_delim && out_id.last() == Some(&self.attr_delim));
+            self.append_missing(out_id, &self._append_ids, delim_before, &mut push_fn);
         } else if self.append_attr == SeqAttr::Desc {
             let delim_before = !(self.adelim_is_space && out_desc.is_empty());
             self.append_missing(out_desc, &self._append_ids, delim_before, &mut push_fn);
@@ -137,12 +161,13 @@ impl Attrs {
                 }
                 Action::Delete => {
                     // remove the delimiter before if possible, but pos.start == 0 is also possible
-                    let end = if pos.start > prev_end {
-                        pos.start - 1
-                    } else {
-                        pos.start
-                    };
-                    new_text.extend_from_slice(&text[prev_end..end]);
+                    if pos.start > prev_end {
+                        new_text.extend_from_slice(&text[prev_end..pos.start - 1]);
+                    } else if text.get(pos.end) == Some(&self.attr_delim) {
+                        // remove the delimiter after instead
+                        prev_end = pos.end + 1;
+                        continue;
+                    }
                 }
             }
             prev_end = pos.end;
@@ -150,6 +175,24 @@ impl Attrs {
         new_text.extend_from_slice(&text[prev_end..]);
     }
 
+    // Removes a trailing delimiter that is left after all attributes
+    // were deleted (>id;a=1; -> >id; -> >id)
+    fn remove_trailing(&self, text: &mut Vec<u8>) {
+        if self._id_actions.iter().all(|&(_, a, _)| a != Action::Delete) {
+            return;
+        }
+        if text.last() == Some(&self.attr_delim) {
+            let last_attr = {
+                let rest = &text[..text.len() - 1];
+                let start = memrchr(self.attr_delim, rest).map(|p| p + 1).unwrap_or(0);
+                memchr(self.attr_value_delim, &rest[start..]).is_some()
+            };
+            if !last_attr {
+                text.pop();
+            }
+        }
+    }
+
     fn append_missing<F>(
         &self,
         new_text: &mut Vec<u8>,
@@ -170,7 +213,15 @@ impl Attrs {
             }
             new_text.extend_from_slice(attr_name.as_bytes());
             new_text.push(self.attr_value_delim);
+            if self.typed {
+                new_text.push(b'Z');
+                new_text.push(self.attr_value_delim);
+            }
             push_fn(attr_id, new_text);
+            if self.trailing_delim {
+                new_text.push(self.attr_delim);
+                delim_before = false;
+            }
         }
     }
 
@@ -198,7 +249,7 @@ impl Attrs {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct AttrData {
     // used to know if the position is up-to date
     // (instead of resetting before each record)
@@ -231,13 +282,15 @@ impl AttrData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Parser {
     data: Vec<AttrData>,
     search_id: usize,
     num_found: usize,
     delim: u8,
     value_delim: u8,
+    // KEY:TYPE:VALUE
+    typed: bool,
 }
 
 impl Parser {
@@ -248,6 +301,7 @@ impl Parser {
             num_found: 0,
             delim: delim,
             value_delim: value_delim,
+            typed: false,
         }
     }
 
@@ -272,7 +326,15 @@ impl Parser {
 
     fn check_pos(&mut self, text: &[u8], offset: usize, seq_attr: SeqAttr) -> bool {
         let rv = key_value::parse(text, self.delim, self.value_delim);
-        if let Some((key, vstart, end)) = rv {
+        if let Some((key, mut vstart, end)) = rv {
+            if self.typed {
+                // SAM tags: two-character key and type (e.g. 'BC:Z:value')
+                let value = &text[vstart..end];
+                if key.len() != 2 || value.len() < 2 || value[1] != self.value_delim {
+                    return false;
+                }
+                vstart += 2;
+            }
             let pos = AttrPosition {
                 start: offset,
                 value_start: offset + vstart,
@@ -448,6 +510,49 @@ mod tests {
         assert_eq!(&out_desc, b"");
     }
 
+    #[test]
+    fn usearch() {
+        let mut out_id = vec![];
+        let mut out_desc = vec![];
+        let id = b"id;a=0;b=1;";
+
+        let mut a = Attrs::new(b';', b'=', SeqAttr::Id).trailing_delim(true);
+        a.add_attr("b", 0, Some(Action::Edit));
+        a.add_attr("c", 1, Some(Action::Edit));
+        a.parse(id, None);
+        a.compose(id, None, &mut out_id, &mut out_desc, |_, out| {
+            out.extend_from_slice(b"val");
+        });
+        assert_eq!(&out_id, b"id;a=0;b=val;c=val;");
+
+        let mut a = Attrs::new(b';', b'=', SeqAttr::Id).trailing_delim(true);
+        a.add_attr("a", 0, Some(Action::Delete));
+        a.add_attr("b", 1, Some(Action::Delete));
+        a.parse(id, None);
+        a.compose(id, None, &mut out_id, &mut out_desc, |_, _| {});
+        assert_eq!(&out_id, b"id");
+    }
+
+    #[test]
+    fn sam_tags() {
+        let mut out_id = vec![];
+        let mut out_desc = vec![];
+        let id = b"id";
+        let desc = Some(&b"1:N:0:ACGT BC:Z:ACGT"[..]);
+
+        let mut a = Attrs::new(b' ', b':', SeqAttr::Desc).typed(true);
+        a.add_attr("BC", 0, None);
+        a.add_attr("1", 1, None);
+        a.add_attr("RG", 2, Some(Action::Edit));
+        a.parse(id, desc);
+        assert_eq!(a.get_value(0, id, desc), Some(&b"ACGT"[..]));
+        assert_eq!(a.get_value(1, id, desc), None);
+        a.compose(id, desc, &mut out_id, &mut out_desc, |_, out| {
+            out.extend_from_slice(b"val");
+        });
+        assert_eq!(&out_desc, b"1:N:0:ACGT BC:Z:ACGT RG:Z:val");
+    }
+
     // #[bench]
     // fn bench_attr_parser(b: &mut test::Bencher) {
     //     let mut a = Attrs::new(b' ', b'=', SeqAttr::Desc);
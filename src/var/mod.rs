@@ -35,6 +35,8 @@ pub struct VarOpts<'a> {
 pub struct AttrOpts {
     pub delim: String,
     pub value_delim: String,
+    // attribute dialect, overrides the delimiters
+    pub format: Option<String>,
 }
 
 impl Default for AttrOpts {
@@ -42,16 +44,47 @@ impl Default for AttrOpts {
         AttrOpts {
             delim: " ".to_string(),
             value_delim: "=".to_string(),
+            format: None,
         }
     }
 }
 
+impl AttrOpts {
+    pub fn get_attrs(&self) -> CliResult<attr::Attrs> {
+        if let Some(ref f) = self.format {
+            let a = match f.as_str() {
+                "desc" => attr::Attrs::new(b' ', b'=', SeqAttr::Desc),
+                "usearch" => attr::Attrs::new(b';', b'=', SeqAttr::Id).trailing_delim(true),
+                "sam" => attr::Attrs::new(b' ', b':', SeqAttr::Desc).typed(true),
+                "colon" => attr::Attrs::new(b' ', b':', SeqAttr::Desc),
+                _ => {
+                    return fail!(format!(
+                        "Unknown attribute format: '{}'. Valid are desc, usearch, sam and colon.",
+                        f
+                    ))
+                }
+            };
+            return Ok(a);
+        }
+        let delim = parse_delimiter(&self.delim)?;
+        let value_delim = parse_delimiter(&self.value_delim)?;
+        let append_attr = if delim == b' ' {
+            SeqAttr::Desc
+        } else {
+            SeqAttr::Id
+        };
+        Ok(attr::Attrs::new(delim, value_delim, append_attr))
+    }
+}
+
 pub fn var_help() -> String {
     let help_mod: &[Box<var::VarHelp>] = &[
         Box::new(modules::builtins::BuiltinHelp),
         Box::new(modules::stats::StatHelp),
         Box::new(modules::attr::AttrHelp),
+        Box::new(modules::illumina::IlluminaHelp),
         Box::new(modules::list::ListHelp),
+        Box::new(modules::running::RunningHelp),
         Box::new(expr_module::ExprHelp),
     ];
     help_mod
@@ -63,13 +96,7 @@ pub fn var_help() -> String {
 
 pub fn get_vars<'a>(o: &VarOpts, informat: &InFormat) -> CliResult<Vars<'a>> {
     // Vars instance
-    let delim = parse_delimiter(&o.attr_opts.delim)?;
-    let value_delim = parse_delimiter(&o.attr_opts.value_delim)?;
-    let append_attr = if delim == b' ' {
-        SeqAttr::Desc
-    } else {
-        SeqAttr::Id
-    };
+    let attrs = o.attr_opts.get_attrs()?;
     // quality converter is not related to variables,
     // therefore stored in InFormat
     let qual_converter = match *informat {
@@ -78,7 +105,7 @@ pub fn get_vars<'a>(o: &VarOpts, informat: &InFormat) -> CliResult<Vars<'a>> {
         _ => QualFormat::Sanger,
     }.get_converter();
 
-    let mut vars = Vars::new(delim, value_delim, append_attr, qual_converter);
+    let mut vars = Vars::new(attrs, qual_converter);
 
     // lists
     let list_delim = parse_delimiter(o.list_delim)?;
@@ -109,6 +136,13 @@ pub fn get_vars<'a>(o: &VarOpts, informat: &InFormat) -> CliResult<Vars<'a>> {
     // TODO: allow_missing may not be used at all, a separate option may not make sense
     vars.add_module(modules::attr::AttrVars::new(true));
 
+    vars.add_module(modules::illumina::IlluminaVars::new());
+
+    // running values depend on other variables, which therefore have to be set before
+    vars.add_module(modules::running::RunningVars::new(modules::running::Running::Count));
+    vars.add_module(modules::running::RunningVars::new(modules::running::Running::CumSum));
+    vars.add_module(modules::running::RunningVars::new(modules::running::Running::Prev));
+
     vars.add_module(expr_module::ExprVars::new()?);
 
     Ok(vars)
@@ -29,7 +29,7 @@ impl VarHelp for AttrHelp {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AttrVars {
     attrs: Vec<(String, usize, usize)>,
     allow_missing: bool,
@@ -71,6 +71,10 @@ impl VarProvider for AttrVars {
         !self.attrs.is_empty()
     }
 
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        Ok(Some(Box::new(self.clone())))
+    }
+
     fn set(&mut self, rec: &Record, data: &mut Data) -> CliResult<()> {
         for &(ref name, attr_id, id) in &self.attrs {
             //let (id_bytes, desc_bytes) = (b"", None); // rec.id_desc_bytes();
@@ -24,6 +24,10 @@ impl VarHelp for BuiltinHelp {
             ("desc", "Record description (everything after first space)"),
             ("seq", "Record sequence"),
             ("num", "Sequence number starting with 1"),
+            (
+                "filenum",
+                "Sequence number within the current input file starting with 1",
+            ),
             (
                 "path",
                 "Path to the current input file (or '-' if reading from STDIN)",
@@ -63,6 +67,7 @@ enum BuiltinVar {
     Desc,
     Seq,
     Num,
+    FileNum,
     InPath,
     InName,
     InStem,
@@ -71,7 +76,7 @@ enum BuiltinVar {
     Dir,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 struct PathInfo {
     path: Option<Vec<u8>>,
     name: Option<Vec<u8>>,
@@ -81,10 +86,11 @@ struct PathInfo {
     out_ext: Vec<u8>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BuiltinVars {
     vars: Vec<(BuiltinVar, usize)>,
     num: usize,
+    file_num: usize,
     path_info: PathInfo,
 }
 
@@ -93,6 +99,7 @@ impl BuiltinVars {
         BuiltinVars {
             vars: vec![],
             num: 0,
+            file_num: 0,
             path_info: PathInfo::default(),
         }
     }
@@ -113,6 +120,7 @@ impl VarProvider for BuiltinVars {
             "desc" => Desc,
             "seq" => Seq,
             "num" => Num,
+            "filenum" => FileNum,
             "path" => {
                 self.path_info.path = Some(vec![]);
                 InPath
@@ -144,8 +152,17 @@ impl VarProvider for BuiltinVars {
         !self.vars.is_empty()
     }
 
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        // the record numbers depend on the order
+        if self.vars.iter().any(|&(v, _)| v == Num || v == FileNum) {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(self.clone())))
+    }
+
     fn set(&mut self, record: &Record, data: &mut Data) -> CliResult<()> {
         self.num += 1;
+        self.file_num += 1;
 
         for &(var, id) in &self.vars {
             match var {
@@ -160,6 +177,7 @@ impl VarProvider for BuiltinVars {
                     }
                 }
                 Num => data.symbols.set_int(id, self.num as i64),
+                FileNum => data.symbols.set_int(id, self.file_num as i64),
                 InPath => data
                     .symbols
                     .set_text(id, self.path_info.path.as_ref().unwrap()),
@@ -181,12 +199,25 @@ impl VarProvider for BuiltinVars {
         Ok(())
     }
 
+    fn set_seq(&mut self, record: &Record, data: &mut Data) -> CliResult<()> {
+        for &(var, id) in &self.vars {
+            if var == Seq {
+                let concatenated = data.symbols.mut_text(id);
+                for s in record.seq_segments() {
+                    concatenated.extend_from_slice(s);
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn out_opts(&mut self, out_opts: &OutputOptions) -> CliResult<()> {
         self.path_info.out_ext = out_opts.format.default_ext().as_bytes().to_owned();
         Ok(())
     }
 
     fn new_input(&mut self, in_opts: &InputOptions) -> CliResult<()> {
+        self.file_num = 0;
         if let Some(ref mut path) = self.path_info.path {
             write_os_str(in_opts, path, |p| Some(p.as_os_str()))
         }
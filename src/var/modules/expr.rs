@@ -45,7 +45,7 @@ lazy_static! {
         regex::Regex::new(r"[A-Za-z][A-Za-z0-9_]*(:[A-Za-z0-9][A-Za-z0-9\._]*)+").unwrap();
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExprVars {
     // var id -> varname
     vars: Vec<(usize, String)>,
@@ -95,8 +95,10 @@ impl VarProvider for ExprVars {
         for v in expr.iter() {
             if let meval::tokenizer::Token::Var(ref name) = *v {
                 let orig_name = replacements.get(name).unwrap_or(name);
-                let (var_id, exists) = vars.register_var(orig_name);
-                if !exists {
+                let (var_id, _) = vars.register_var(orig_name);
+                // the variable may already be in use elsewhere, but still needs
+                // to be known to the expression context
+                if !self.ctx.contains_key(name) {
                     self.vars.push((var_id, name.to_string()));
                     self.ctx.insert(name.to_string(), 0.);
                 }
@@ -111,6 +113,10 @@ impl VarProvider for ExprVars {
         !self.vars.is_empty()
     }
 
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        Ok(Some(Box::new(self.clone())))
+    }
+
     fn set(&mut self, _: &Record, data: &mut Data) -> CliResult<()> {
         // copy values from symbol table to context
         for &(var_id, ref name) in &self.vars {
@@ -126,4 +132,9 @@ impl VarProvider for ExprVars {
 
         Ok(())
     }
+
+    fn set_seq(&mut self, rec: &Record, data: &mut Data) -> CliResult<()> {
+        // expressions may use sequence-dependent variables
+        self.set(rec, data)
+    }
 }
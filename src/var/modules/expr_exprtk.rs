@@ -196,4 +196,9 @@ impl VarProvider for ExprVars {
         }
         Ok(())
     }
+
+    fn set_seq(&mut self, rec: &Record, data: &mut Data) -> CliResult<()> {
+        // expressions may use sequence-dependent variables
+        self.set(rec, data)
+    }
 }
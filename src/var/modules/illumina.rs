@@ -0,0 +1,262 @@
+use std::str;
+
+use self::IllVar::*;
+use error::CliResult;
+use io::Record;
+use var::*;
+
+pub struct IlluminaHelp;
+
+impl VarHelp for IlluminaHelp {
+    fn name(&self) -> &'static str {
+        "Illumina header fields"
+    }
+    fn usage(&self) -> &'static str {
+        "ill:<field>"
+    }
+    fn desc(&self) -> Option<&'static str> {
+        Some(
+            "Fields parsed from Illumina read headers in the current format \
+             (@instrument:run:flowcell:lane:tile:x:y[:umi] read:filtered:control:index) \
+             or the older format (@instrument:lane:tile:x:y#index/read). \
+             Fields that are not present are undefined (empty).",
+        )
+    }
+    fn vars(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            ("ill:instrument", "Instrument name"),
+            ("ill:run", "Run number"),
+            ("ill:flowcell", "Flowcell ID"),
+            ("ill:lane", "Flowcell lane"),
+            ("ill:tile", "Tile number"),
+            ("ill:x", "X coordinate of the cluster"),
+            ("ill:y", "Y coordinate of the cluster"),
+            ("ill:umi", "UMI sequence (optional 8th field of the ID)"),
+            ("ill:read", "Read number (1 or 2 for paired-end reads)"),
+            (
+                "ill:filtered",
+                "1 if the read was filtered (did not pass the chastity filter), otherwise 0",
+            ),
+            ("ill:control", "Control bits (0 if none is set)"),
+            (
+                "ill:index",
+                "Index sequence (or sample number), with both indexes in case of dual indexing",
+            ),
+            ("ill:index1", "First index sequence"),
+            ("ill:index2", "Second index sequence in case of dual indexing (i7+i5)"),
+        ])
+    }
+    fn examples(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            (
+                "Splitting reads by flowcell lane",
+                "st split -o '{ill:lane}.fq' reads.fq",
+            ),
+            (
+                "Removing reads that did not pass the filter",
+                "st filter 'ill:filtered == 0' reads.fq > filtered.fq",
+            ),
+        ])
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum IllVar {
+    Instrument,
+    Run,
+    Flowcell,
+    Lane,
+    Tile,
+    X,
+    Y,
+    Umi,
+    Read,
+    Filtered,
+    Control,
+    Index,
+    Index1,
+    Index2,
+}
+
+#[derive(Debug, Default)]
+struct Fields<'a> {
+    instrument: Option<&'a [u8]>,
+    run: Option<&'a [u8]>,
+    flowcell: Option<&'a [u8]>,
+    lane: Option<&'a [u8]>,
+    tile: Option<&'a [u8]>,
+    x: Option<&'a [u8]>,
+    y: Option<&'a [u8]>,
+    umi: Option<&'a [u8]>,
+    read: Option<&'a [u8]>,
+    filtered: Option<&'a [u8]>,
+    control: Option<&'a [u8]>,
+    index: Option<&'a [u8]>,
+}
+
+impl<'a> Fields<'a> {
+    fn parse(id: &'a [u8], desc: Option<&'a [u8]>) -> Fields<'a> {
+        let mut f = Fields::default();
+        let mut id = id;
+
+        // older format: @instrument:lane:tile:x:y#index/read
+        // (other IDs may contain slashes as well)
+        if let Some(p) = id.iter().rposition(|&c| c == b'/') {
+            let read = &id[p + 1..];
+            let n_fields = id[..p]
+                .split(|&c| c == b'#')
+                .next()
+                .unwrap()
+                .split(|&c| c == b':')
+                .count();
+            if read == b"1" || read == b"2" || read == b"3" || n_fields == 5 {
+                f.read = Some(read);
+                id = &id[..p];
+            }
+        }
+        if let Some(p) = id.iter().rposition(|&c| c == b'#') {
+            f.index = Some(&id[p + 1..]);
+            id = &id[..p];
+        }
+
+        let parts: Vec<_> = id.split(|&c| c == b':').collect();
+        match parts.len() {
+            5 => {
+                f.instrument = Some(parts[0]);
+                f.lane = Some(parts[1]);
+                f.tile = Some(parts[2]);
+                f.x = Some(parts[3]);
+                f.y = Some(parts[4]);
+            }
+            7 | 8 => {
+                f.instrument = Some(parts[0]);
+                f.run = Some(parts[1]);
+                f.flowcell = Some(parts[2]);
+                f.lane = Some(parts[3]);
+                f.tile = Some(parts[4]);
+                f.x = Some(parts[5]);
+                f.y = Some(parts[6]);
+                f.umi = parts.get(7).cloned();
+            }
+            _ => {}
+        }
+
+        // current format: description with read:filtered:control:index
+        if let Some(d) = desc {
+            let comment = d.split(|&c| c == b' ').next().unwrap();
+            let parts: Vec<_> = comment.split(|&c| c == b':').collect();
+            if parts.len() == 4 {
+                f.read = Some(parts[0]);
+                f.filtered = Some(parts[1]);
+                f.control = Some(parts[2]);
+                f.index = Some(parts[3]);
+            }
+        }
+        f
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IlluminaVars {
+    vars: Vec<(IllVar, usize)>,
+}
+
+impl IlluminaVars {
+    pub fn new() -> IlluminaVars {
+        IlluminaVars { vars: vec![] }
+    }
+}
+
+impl VarProvider for IlluminaVars {
+    fn prefix(&self) -> Option<&str> {
+        Some("ill")
+    }
+
+    fn name(&self) -> &'static str {
+        "Illumina header"
+    }
+
+    fn register_var(&mut self, name: &str, id: usize, _: &mut VarStore) -> CliResult<bool> {
+        let var = match name {
+            "instrument" => Instrument,
+            "run" => Run,
+            "flowcell" => Flowcell,
+            "lane" => Lane,
+            "tile" => Tile,
+            "x" => X,
+            "y" => Y,
+            "umi" => Umi,
+            "read" => Read,
+            "filtered" => Filtered,
+            "control" => Control,
+            "index" => Index,
+            "index1" => Index1,
+            "index2" => Index2,
+            _ => return Ok(false),
+        };
+        self.vars.push((var, id));
+        Ok(true)
+    }
+
+    fn has_vars(&self) -> bool {
+        !self.vars.is_empty()
+    }
+
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        Ok(Some(Box::new(self.clone())))
+    }
+
+    fn set(&mut self, record: &Record, data: &mut Data) -> CliResult<()> {
+        let (id, desc) = record.id_desc_bytes();
+        let f = Fields::parse(id, desc);
+
+        for &(var, var_id) in &self.vars {
+            let sym = &mut data.symbols;
+            match var {
+                Instrument => set_text(sym, var_id, f.instrument),
+                Run => set_int(sym, var_id, f.run),
+                Flowcell => set_text(sym, var_id, f.flowcell),
+                Lane => set_int(sym, var_id, f.lane),
+                Tile => set_int(sym, var_id, f.tile),
+                X => set_int(sym, var_id, f.x),
+                Y => set_int(sym, var_id, f.y),
+                Umi => set_text(sym, var_id, f.umi),
+                Read => set_int(sym, var_id, f.read),
+                Filtered => match f.filtered {
+                    Some(b"Y") => sym.set_int(var_id, 1),
+                    Some(b"N") => sym.set_int(var_id, 0),
+                    _ => sym.set_none(var_id),
+                },
+                Control => set_int(sym, var_id, f.control),
+                Index => set_text(sym, var_id, f.index),
+                Index1 => set_text(sym, var_id, f.index.map(|i| split_index(i).0)),
+                Index2 => set_text(sym, var_id, f.index.and_then(|i| split_index(i).1)),
+            }
+        }
+        Ok(())
+    }
+}
+
+// dual indexes are separated by '+'
+fn split_index(index: &[u8]) -> (&[u8], Option<&[u8]>) {
+    if let Some(p) = index.iter().position(|&c| c == b'+') {
+        (&index[..p], Some(&index[p + 1..]))
+    } else {
+        (index, None)
+    }
+}
+
+fn set_text(symbols: &mut symbols::Table, id: usize, value: Option<&[u8]>) {
+    match value {
+        Some(v) => symbols.set_text(id, v),
+        None => symbols.set_none(id),
+    }
+}
+
+fn set_int(symbols: &mut symbols::Table, id: usize, value: Option<&[u8]>) {
+    let num = value.and_then(|v| str::from_utf8(v).ok().and_then(|v| v.parse().ok()));
+    match num {
+        Some(n) => symbols.set_int(id, n),
+        None => symbols.set_none(id),
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt;
 use std::io;
+use std::sync::Arc;
 
 use csv::{self, ByteRecord, Reader, ReaderBuilder};
 use fxhash::FxHashMap;
@@ -100,7 +101,7 @@ where
 impl<R, H> VarProvider for ListVars<R, H>
 where
     R: io::Read + Send,
-    H: IdFinder<R> + Send,
+    H: IdFinder<R> + IdFinder<io::Empty> + Send + 'static,
 {
     fn prefix(&self) -> Option<&str> {
         Some(&self.prefix)
@@ -146,6 +147,30 @@ where
         !self.columns.is_empty()
     }
 
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        if self.has_header {
+            self.rdr.read_byte_record(&mut self.record)?;
+            self.has_header = false;
+        }
+        let handler = match self.handler.fork(self.id_col, &mut self.rdr)? {
+            Some(h) => h,
+            None => return Ok(None),
+        };
+        // the copy does not read from the list anymore
+        let rdr = ReaderBuilder::new().from_reader(io::empty());
+        Ok(Some(Box::new(ListVars {
+            prefix: self.prefix.clone(),
+            rdr: rdr,
+            record: ByteRecord::new(),
+            columns: self.columns.clone(),
+            has_header: false,
+            header: self.header.clone(),
+            handler: handler,
+            id_col: self.id_col,
+            allow_missing: self.allow_missing,
+        })))
+    }
+
     fn set(&mut self, record: &Record, data: &mut Data) -> CliResult<()> {
         if self.has_header {
             self.rdr.read_byte_record(&mut self.record)?;
@@ -194,6 +219,15 @@ pub trait IdFinder<R: io::Read> {
         rdr: &mut Reader<R>,
         rec: &mut ByteRecord,
     ) -> Result<(), ListError>;
+
+    /// Returns a copy, which does not need to read from the list anymore
+    /// (used by `VarProvider::fork`).
+    fn fork(&mut self, _id_col: usize, _rdr: &mut Reader<R>) -> Result<Option<Self>, ListError>
+    where
+        Self: Sized,
+    {
+        Ok(None)
+    }
 }
 
 pub struct SyncIds;
@@ -219,11 +253,12 @@ impl<R: io::Read> IdFinder<R> for SyncIds {
     }
 }
 
-pub struct Unordered(FxHashMap<Vec<u8>, ByteRecord>);
+// The map is only shared after the whole list was read (see `fork`)
+pub struct Unordered(Arc<FxHashMap<Vec<u8>, ByteRecord>>);
 
 impl Unordered {
     pub fn new() -> Unordered {
-        Unordered(FxHashMap::default())
+        Unordered(Arc::new(FxHashMap::default()))
     }
 }
 
@@ -245,7 +280,7 @@ impl<R: io::Read> IdFinder<R> for Unordered {
                 .get(id_col)
                 .ok_or_else(|| ListError::NoId(rdr.position().clone()))?;
 
-            self.0
+            Arc::make_mut(&mut self.0)
                 .entry(row_id.to_owned())
                 .or_insert_with(|| rec.clone());
 
@@ -256,6 +291,21 @@ impl<R: io::Read> IdFinder<R> for Unordered {
 
         Err(ListError::EntryMissing(id.to_owned()))
     }
+
+    fn fork(&mut self, id_col: usize, rdr: &mut Reader<R>) -> Result<Option<Unordered>, ListError> {
+        // read the rest of the list
+        let mut rec = ByteRecord::new();
+        while rdr.read_byte_record(&mut rec)? {
+            let row_id = rec
+                .get(id_col)
+                .ok_or_else(|| ListError::NoId(rdr.position().clone()))?
+                .to_owned();
+            Arc::make_mut(&mut self.0)
+                .entry(row_id)
+                .or_insert_with(|| rec.clone());
+        }
+        Ok(Some(Unordered(self.0.clone())))
+    }
 }
 
 pub enum ListError {
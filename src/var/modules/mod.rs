@@ -4,5 +4,7 @@ pub mod builtins;
 pub mod expr;
 #[cfg(feature = "exprtk")]
 pub mod expr_exprtk;
+pub mod illumina;
 pub mod list;
+pub mod running;
 pub mod stats;
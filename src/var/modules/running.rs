@@ -0,0 +1,163 @@
+use fxhash::FxHashMap;
+
+use error::CliResult;
+use io::Record;
+use var::*;
+
+pub struct RunningHelp;
+
+impl VarHelp for RunningHelp {
+    fn name(&self) -> &'static str {
+        "Running counters and values"
+    }
+    fn usage(&self) -> &'static str {
+        "<prefix>:<variable>"
+    }
+    fn desc(&self) -> Option<&'static str> {
+        Some(
+            "Values that depend on the records seen before the current one. The variable \
+             can be any other variable, written either with or without enclosing braces \
+             (e.g. `{cnt:{a:sample}}` or `{cnt:a:sample}`). Math expressions are not \
+             accepted as variable.",
+        )
+    }
+    fn vars(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            (
+                "cnt:<var>",
+                "Number of records seen so far with the same value of <var> (including \
+                 the current record), starting with 1 for every new value.",
+            ),
+            (
+                "cumsum:<var>",
+                "Cumulative sum of the numeric variable <var> up to (and including) the \
+                 current record. Undefined values are ignored.",
+            ),
+            (
+                "prev:<var>",
+                "Value of <var> in the previous record (empty for the first record).",
+            ),
+        ])
+    }
+    fn examples(&self) -> Option<&'static [(&'static str, &'static str)]> {
+        Some(&[
+            (
+                "Numbering the records of each sample separately",
+                "st set -i {a:sample}_{cnt:{a:sample}} seqs.fa",
+            ),
+            (
+                "Adding the total number of bases seen so far",
+                "st . -a total={cumsum:s:seqlen} seqs.fa",
+            ),
+        ])
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Running {
+    Count,
+    CumSum,
+    Prev,
+}
+
+#[derive(Debug)]
+enum State {
+    Count(FxHashMap<Vec<u8>, usize>),
+    CumSum(f64),
+    Prev(Option<Vec<u8>>),
+}
+
+#[derive(Debug)]
+pub struct RunningVars {
+    kind: Running,
+    // (var_id, dependency var_id, state)
+    vars: Vec<(usize, usize, State)>,
+}
+
+impl RunningVars {
+    pub fn new(kind: Running) -> RunningVars {
+        RunningVars {
+            kind: kind,
+            vars: vec![],
+        }
+    }
+}
+
+impl VarProvider for RunningVars {
+    fn prefix(&self) -> Option<&str> {
+        Some(match self.kind {
+            Running::Count => "cnt",
+            Running::CumSum => "cumsum",
+            Running::Prev => "prev",
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "running value"
+    }
+
+    fn register_var(&mut self, name: &str, id: usize, vars: &mut VarStore) -> CliResult<bool> {
+        let dep_name = if name.starts_with('{') && name.ends_with('}') {
+            &name[1..name.len() - 1]
+        } else {
+            name
+        };
+        if dep_name.is_empty() || dep_name.starts_with('{') {
+            return fail!(format!(
+                "Invalid variable in '{}:{}'. Math expressions are not supported here.",
+                self.prefix().unwrap(),
+                name
+            ));
+        }
+        let (dep_id, _) = vars.register_var(dep_name);
+        let state = match self.kind {
+            Running::Count => State::Count(FxHashMap::default()),
+            Running::CumSum => State::CumSum(0.),
+            Running::Prev => State::Prev(None),
+        };
+        self.vars.push((id, dep_id, state));
+        Ok(true)
+    }
+
+    fn has_vars(&self) -> bool {
+        !self.vars.is_empty()
+    }
+
+    fn set(&mut self, _: &Record, data: &mut Data) -> CliResult<()> {
+        for &mut (id, dep_id, ref mut state) in &mut self.vars {
+            match *state {
+                State::Count(ref mut counts) => {
+                    let key = data.symbols.get_text(dep_id).unwrap_or(b"");
+                    // avoid allocating a new key for every record
+                    let n = if let Some(n) = counts.get_mut(key) {
+                        *n += 1;
+                        Some(*n)
+                    } else {
+                        None
+                    };
+                    let n = n.unwrap_or_else(|| {
+                        counts.insert(key.to_owned(), 1);
+                        1
+                    });
+                    data.symbols.set_int(id, n as i64);
+                }
+                State::CumSum(ref mut sum) => {
+                    if let Some(v) = data.symbols.get_float(dep_id)? {
+                        *sum += v;
+                    }
+                    data.symbols.set_float(id, *sum);
+                }
+                State::Prev(ref mut prev) => {
+                    match *prev {
+                        Some(ref v) => data.symbols.set_text(id, v),
+                        None => data.symbols.set_none(id),
+                    }
+                    let current = prev.get_or_insert_with(Vec::new);
+                    current.clear();
+                    current.extend_from_slice(data.symbols.get_text(dep_id).unwrap_or(b""));
+                }
+            }
+        }
+        Ok(())
+    }
+}
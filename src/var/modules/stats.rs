@@ -43,7 +43,7 @@ impl VarHelp for StatHelp {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Stat {
     SeqLen,
     UngappedLen,
@@ -53,7 +53,7 @@ enum Stat {
     ExpErr,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StatVars {
     stats: Vec<(Stat, usize)>,
 }
@@ -104,6 +104,10 @@ impl VarProvider for StatVars {
         !self.stats.is_empty()
     }
 
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        Ok(Some(Box::new(self.clone())))
+    }
+
     fn set(&mut self, rec: &Record, data: &mut Data) -> CliResult<()> {
         for &(ref stat, id) in &self.stats {
             match *stat {
@@ -127,6 +131,11 @@ impl VarProvider for StatVars {
         }
         Ok(())
     }
+
+    fn set_seq(&mut self, rec: &Record, data: &mut Data) -> CliResult<()> {
+        // all statistics depend on the sequence or the quality scores
+        self.set(rec, data)
+    }
 }
 
 #[inline]
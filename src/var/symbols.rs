@@ -147,6 +147,13 @@ impl Table {
         }
     }
 
+    pub fn is_numeric(&self, id: usize) -> bool {
+        match *self.get(id) {
+            Value::Int(..) | Value::Float(..) => true,
+            _ => false,
+        }
+    }
+
     pub fn is_empty(&self, id: usize) -> bool {
         match *self.get(id) {
             Value::Text(ref s, _, _) => s.is_empty(),
@@ -8,7 +8,7 @@ use std::fmt::{Debug, Write};
 use error::CliResult;
 use io::input::InputOptions;
 use io::output::OutputOptions;
-use io::{QualConverter, Record, SeqAttr};
+use io::{QualConverter, Record};
 
 use super::attr;
 use super::symbols::Table;
@@ -18,15 +18,43 @@ pub trait VarProvider: Debug + Send {
     fn name(&self) -> &'static str;
     fn register_var(&mut self, name: &str, id: usize, vars: &mut VarStore) -> CliResult<bool>;
     fn has_vars(&self) -> bool;
+    /// Returns true if the provider handles variables with the given prefix.
+    /// By default, this is only the case for `prefix()`, but providers may
+    /// accept additional (e.g. numbered) prefixes.
+    fn accepts_prefix(&self, prefix: Option<&str>) -> bool {
+        self.prefix() == prefix
+    }
+    /// Registers a variable with a prefix accepted by `accepts_prefix()`
+    fn register_prefixed(
+        &mut self,
+        _prefix: Option<&str>,
+        name: &str,
+        id: usize,
+        vars: &mut VarStore,
+    ) -> CliResult<bool> {
+        self.register_var(name, id, vars)
+    }
     fn set(&mut self, _: &Record, _: &mut Data) -> CliResult<()> {
         Ok(())
     }
+    /// Called if the sequence / quality scores of a record were modified
+    /// after `set()`. Only variables depending on these are updated, the
+    /// record count and other order-dependent values stay the same.
+    fn set_seq(&mut self, _: &Record, _: &mut Data) -> CliResult<()> {
+        Ok(())
+    }
     fn new_input(&mut self, _: &InputOptions) -> CliResult<()> {
         Ok(())
     }
     fn out_opts(&mut self, _: &OutputOptions) -> CliResult<()> {
         Ok(())
     }
+    /// Returns a copy of the provider, which is used for evaluating the variables
+    /// in another thread. `None` means that the values depend on the order of
+    /// the records, they are then always evaluated in the main thread.
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        Ok(None)
+    }
 }
 
 pub trait VarHelp {
@@ -90,15 +118,33 @@ impl<'a> VarProvider for Box<VarProvider + 'a> {
     fn has_vars(&self) -> bool {
         (**self).has_vars()
     }
+    fn accepts_prefix(&self, prefix: Option<&str>) -> bool {
+        (**self).accepts_prefix(prefix)
+    }
+    fn register_prefixed(
+        &mut self,
+        prefix: Option<&str>,
+        name: &str,
+        id: usize,
+        vars: &mut VarStore,
+    ) -> CliResult<bool> {
+        (**self).register_prefixed(prefix, name, id, vars)
+    }
     fn set<'b>(&mut self, item: &'b Record, data: &mut Data) -> CliResult<()> {
         (**self).set(item, data)
     }
+    fn set_seq<'b>(&mut self, item: &'b Record, data: &mut Data) -> CliResult<()> {
+        (**self).set_seq(item, data)
+    }
     fn new_input(&mut self, o: &InputOptions) -> CliResult<()> {
         (**self).new_input(o)
     }
     fn out_opts(&mut self, o: &OutputOptions) -> CliResult<()> {
         (**self).out_opts(o)
     }
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        (**self).fork()
+    }
 }
 
 impl<'a> VarProvider for &'a mut VarProvider {
@@ -114,18 +160,36 @@ impl<'a> VarProvider for &'a mut VarProvider {
     fn has_vars(&self) -> bool {
         (**self).has_vars()
     }
+    fn accepts_prefix(&self, prefix: Option<&str>) -> bool {
+        (**self).accepts_prefix(prefix)
+    }
+    fn register_prefixed(
+        &mut self,
+        prefix: Option<&str>,
+        name: &str,
+        id: usize,
+        vars: &mut VarStore,
+    ) -> CliResult<bool> {
+        (**self).register_prefixed(prefix, name, id, vars)
+    }
     fn set<'b>(&mut self, item: &'b Record, data: &mut Data) -> CliResult<()> {
         (**self).set(item, data)
     }
+    fn set_seq<'b>(&mut self, item: &'b Record, data: &mut Data) -> CliResult<()> {
+        (**self).set_seq(item, data)
+    }
     fn new_input(&mut self, o: &InputOptions) -> CliResult<()> {
         (**self).new_input(o)
     }
     fn out_opts(&mut self, o: &OutputOptions) -> CliResult<()> {
         (**self).out_opts(o)
     }
+    fn fork(&mut self) -> CliResult<Option<Box<VarProvider>>> {
+        (**self).fork()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Data {
     pub symbols: Table,
     pub attrs: attr::Attrs,
@@ -141,19 +205,14 @@ pub struct Vars<'a> {
 }
 
 impl<'a> Vars<'a> {
-    pub fn new(
-        attr_delim: u8,
-        attr_value_delim: u8,
-        append_attr: SeqAttr,
-        qual_converter: QualConverter,
-    ) -> Vars<'a> {
+    pub fn new(attrs: attr::Attrs, qual_converter: QualConverter) -> Vars<'a> {
         Vars {
             varstore: VarStore::new(),
             used_modules: vec![],
             modules: vec![],
             data: Data {
                 symbols: Table::new(0),
-                attrs: attr::Attrs::new(attr_delim, attr_value_delim, append_attr),
+                attrs: attrs,
                 qual_converter: qual_converter,
             },
         }
@@ -195,6 +254,25 @@ impl<'a> Vars<'a> {
             .collect();
     }
 
+    /// Returns a copy containing all used modules, which can evaluate the
+    /// variables in another thread. Returns `None` if any of the modules cannot
+    /// be copied (see `VarProvider::fork`).
+    pub fn fork(&mut self) -> CliResult<Option<Vars<'static>>> {
+        let mut modules = Vec::with_capacity(self.used_modules.len());
+        for &i in &self.used_modules {
+            match self.modules[i].fork()? {
+                Some(m) => modules.push(m),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(Vars {
+            varstore: self.varstore.clone(),
+            used_modules: (0..modules.len()).collect(),
+            modules: modules,
+            data: self.data.clone(),
+        }))
+    }
+
     pub fn add_module<M>(&mut self, m: M)
     where
         M: VarProvider + 'a,
@@ -234,6 +312,15 @@ impl<'a> Vars<'a> {
         Ok(())
     }
 
+    /// Updates the variables after the sequence of the current record
+    /// was modified (see `VarProvider::set_seq`).
+    pub fn set_seq(&mut self, record: &Record) -> CliResult<()> {
+        for i in &self.used_modules {
+            self.modules[*i].set_seq(record, &mut self.data)?;
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn symbols(&self) -> &Table {
         &self.data.symbols
@@ -325,8 +412,13 @@ impl<'a, 'b> VarBuilder<'a, 'b> {
 
     // searches for correct module given <prefix> and registers a variable with <name> and <id> to it
     fn mod_register(&mut self, prefix: &Option<String>, name: &str, id: usize) -> CliResult<()> {
-        if let Some(module) = self.modules.get_mut(prefix) {
-            let found = module.register_var(name, id, self.varstore)?;
+        let p = prefix.as_ref().map(|s| s.as_str());
+        let module = match self.modules.get_mut(prefix) {
+            Some(m) => Some(m),
+            None => self.modules.values_mut().find(|m| m.accepts_prefix(p)),
+        };
+        if let Some(module) = module {
+            let found = module.register_prefixed(p, name, id, self.varstore)?;
             if !found {
                 return fail!(format!("Unknown {} variable: '{}'.", module.name(), name));
             }
@@ -393,7 +485,11 @@ impl VarStore {
     }
 
     pub fn register_attr(&mut self, name: &str, action: Option<attr::Action>) -> usize {
-        if let Some(&(id, _, _)) = self.attrs.get(name) {
+        if let Some(&mut (id, ref mut a, registered)) = self.attrs.get_mut(name) {
+            // the attribute may have been registered before as variable without action
+            if action.is_some() && !registered {
+                *a = action;
+            }
             return id;
         }
         let id = self.num_attrs;
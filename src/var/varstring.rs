@@ -8,10 +8,10 @@ use var;
 
 lazy_static! {
     static ref VAR_RE: regex::Regex =
-        regex::Regex::new(r"(\{\{([^\}]+)\}\}|\{([^\{\}]+)\})").unwrap();
+        regex::Regex::new(r"(\{\{([^\}]+)\}\}|\{([^\{\}]+(\{[^\{\}]+\}[^\{\}]*)*)\})").unwrap();
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VarString {
     // (String before, var_id)
     parts: Vec<(Vec<u8>, usize)>,
@@ -80,6 +80,16 @@ impl VarString {
         out.extend_from_slice(&self.rest);
     }
 
+    /// Returns the variable ID if the string consists of only one variable
+    #[inline]
+    pub fn get_one_var(&self) -> Option<usize> {
+        if self.one_var {
+            Some(self.parts[0].1)
+        } else {
+            None
+        }
+    }
+
     #[inline]
     pub fn get_float(&self, table: &var::symbols::Table) -> CliResult<Option<f64>> {
         if self.one_var {